@@ -2,8 +2,8 @@ mod popups;
 pub mod theme;
 pub mod widgets;
 
-use crate::controller::{Controller, Focus, Mode, PopupState, ViewState};
-use crate::db::QueryResult;
+use crate::controller::{Controller, Focus, Mode, PopupState, SidebarRow, ViewState};
+use crate::db::{CellValue, QueryResult};
 use crate::result_table::result_table_widths;
 use crossterm::event::KeyEvent;
 use ratatui::Frame;
@@ -11,7 +11,7 @@ use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{
-    Block, BorderType, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table,
+    Block, BorderType, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, Wrap,
 };
 use tui_textarea::CursorMove;
 
@@ -46,6 +46,7 @@ impl App {
             self.controller.mode,
             key_event.code
         );
+        self.controller.current_tab_mut().clear_run_highlight();
         match self.controller.mode {
             Mode::Normal => self.controller.handle_normal_mode(key_event),
             Mode::Command => self.controller.handle_command_mode(key_event.code),
@@ -53,10 +54,12 @@ impl App {
     }
 
     pub fn handle_mouse(&mut self, mouse_event: crossterm::event::MouseEvent) {
+        self.controller.current_tab_mut().clear_run_highlight();
         self.controller.handle_mouse(mouse_event);
     }
 
     pub fn handle_paste(&mut self, text: String) {
+        self.controller.current_tab_mut().clear_run_highlight();
         self.controller.handle_paste(text);
     }
 
@@ -157,21 +160,33 @@ impl App {
             let db_name = tab.current_database.as_deref().unwrap_or("(none)");
             let table_name = tab
                 .sidebar
-                .tables
-                .get(tab.sidebar.selected)
-                .map(|t| format!(" > {}", t))
+                .selected_object()
+                .map(|o| format!(" > {}", o.name))
                 .unwrap_or_default();
             let is_readonly = conn.map(|c| c.readonly).unwrap_or(false);
             let ro_suffix = if is_readonly { " [RO]" } else { "" };
 
             let left = format!(" {}{}{} ", db_name, ro_suffix, table_name);
+            let more_indicator = if tab.result_stream.is_some() {
+                " more pages available "
+            } else {
+                ""
+            };
+            let transaction_indicator = match tab.transaction {
+                Some(pending) => format!(" TX open, {} pending ", pending),
+                None => String::new(),
+            };
             let left_len = left.len() + status_msg.len();
-            let fill = (area.width as usize).saturating_sub(left_len + pending_indicator.len());
+            let right_len =
+                transaction_indicator.len() + more_indicator.len() + pending_indicator.len();
+            let fill = (area.width as usize).saturating_sub(left_len + right_len);
 
             Paragraph::new(Line::from(vec![
                 Span::styled(left, text()),
                 Span::styled(status_msg, dim()),
                 Span::styled(" ".repeat(fill), Style::default()),
+                Span::styled(transaction_indicator, Style::default().fg(WARNING)),
+                Span::styled(more_indicator, Style::default().fg(BLUE)),
                 Span::styled(pending_indicator, Style::default().fg(ACCENT)),
             ]))
             .style(Style::default().bg(SURFACE_DIM))
@@ -208,7 +223,7 @@ impl App {
         let items: Vec<ListItem> = filtered
             .iter()
             .map(|conn| {
-                let location = if let Some(ref path) = conn.path {
+                let location = if let Some(path) = conn.path.as_ref().or(conn.socket.as_ref()) {
                     format!(" {}", path)
                 } else {
                     format!(" {}:{}", conn.host, conn.port)
@@ -408,25 +423,69 @@ impl App {
         let tab = self.controller.current_tab();
         let is_focused = tab.focus == Focus::Sidebar;
 
-        let table_count = tab.sidebar.tables.len();
-        let mut items: Vec<ListItem> = Vec::with_capacity(table_count + 1);
-
-        items.push(ListItem::new(Line::from(vec![Span::styled(
-            "Tables",
-            accent_bold(),
-        )])));
-
-        for (i, table) in tab.sidebar.tables.iter().enumerate() {
-            let is_last = i == table_count - 1;
-            let prefix = if is_last { "└─ " } else { "├─ " };
-            items.push(ListItem::new(Line::from(vec![
-                Span::styled(prefix, dim()),
-                Span::styled(table, text()),
-            ])));
+        let rows = tab.sidebar.rows();
+        let mut items: Vec<ListItem> = Vec::with_capacity(rows.len());
+
+        let objects = &tab.sidebar.objects;
+        let is_last_of_kind =
+            |idx: usize| objects.get(idx + 1).map(|o| o.kind) != Some(objects[idx].kind);
+
+        for row in &rows {
+            match *row {
+                SidebarRow::Group {
+                    kind,
+                    count,
+                    expanded,
+                } => {
+                    let marker = if expanded { "▾ " } else { "▸ " };
+                    items.push(ListItem::new(Line::from(vec![
+                        Span::styled(marker, dim()),
+                        Span::styled(kind.label(), accent_bold()),
+                        Span::styled(format!(" ({})", count), dim()),
+                    ])));
+                }
+                SidebarRow::Object(idx) => {
+                    let prefix = if is_last_of_kind(idx) {
+                        "└─ "
+                    } else {
+                        "├─ "
+                    };
+                    items.push(ListItem::new(Line::from(vec![
+                        Span::styled(prefix, dim()),
+                        Span::styled(&tab.sidebar.objects[idx].name, text()),
+                    ])));
+                }
+                SidebarRow::Column(idx, c) => {
+                    let columns = &tab.sidebar.columns[&tab.sidebar.objects[idx].name];
+                    let column = &columns[c];
+                    let indent = if is_last_of_kind(idx) { "   " } else { "│  " };
+                    let branch = if c + 1 == columns.len() {
+                        "└─ "
+                    } else {
+                        "├─ "
+                    };
+                    let mut spans = vec![
+                        Span::styled(format!("{}{}", indent, branch), dim()),
+                        Span::styled(column.name.as_str(), text()),
+                        Span::styled(format!(" {}", column.data_type), dim()),
+                    ];
+                    if column.primary_key {
+                        spans.push(Span::styled(" PK", Style::default().fg(ACCENT)));
+                    }
+                    if column.foreign_key {
+                        spans.push(Span::styled(" FK", Style::default().fg(BLUE)));
+                    }
+                    if !column.nullable {
+                        spans.push(Span::styled(" NOT NULL", dim()));
+                    }
+                    items.push(ListItem::new(Line::from(spans)));
+                }
+            }
         }
 
-        let title = if table_count > 0 {
-            format!("Explorer ({})", table_count)
+        let object_count = tab.sidebar.objects.len();
+        let title = if object_count > 0 {
+            format!("Explorer ({})", object_count)
         } else {
             "Explorer".to_string()
         };
@@ -442,10 +501,10 @@ impl App {
             .highlight_symbol(if is_focused { "> " } else { "  " });
 
         let mut list_state = ListState::default();
-        list_state.select(Some(tab.sidebar.selected + 1));
+        list_state.select(Some(tab.sidebar.selected));
 
         let visible_height = area.height.saturating_sub(2) as usize;
-        let selected = tab.sidebar.selected + 1;
+        let selected = tab.sidebar.selected;
 
         if visible_height > 0 && selected >= visible_height {
             let offset = selected - visible_height + 1;
@@ -463,6 +522,18 @@ impl App {
         let block = panel_block("Query", is_focused, Borders::BOTTOM);
 
         let tab = self.controller.current_tab_mut();
+        if tab
+            .run_highlight
+            .is_some_and(|(until, _)| until <= std::time::Instant::now())
+        {
+            tab.clear_run_highlight();
+        }
+        let selection_style = if tab.run_highlight.is_some() {
+            Style::default().bg(ACCENT).fg(SURFACE)
+        } else {
+            Style::default().bg(HIGHLIGHT).fg(TEXT)
+        };
+        tab.query_textarea.set_selection_style(selection_style);
         tab.query_textarea
             .set_style(Style::default().bg(bg_color).fg(TEXT));
         tab.query_textarea
@@ -482,19 +553,41 @@ impl App {
         let is_focused = tab.focus == Focus::Output;
         let (_, bg_color) = focus_colors(is_focused);
 
-        let row_count = match &tab.query_result {
+        // With several statements, name the one whose result is shown
+        let label = match tab.selected_statement() {
+            Some(result) if tab.statement_results.len() > 1 => {
+                let statement = result.statement.split_whitespace().collect::<Vec<_>>();
+                format!(
+                    "Results {}/{} ── {} ({:.2?})",
+                    tab.selected_result + 1,
+                    tab.statement_results.len(),
+                    truncate_str(&statement.join(" "), 40),
+                    result.duration
+                )
+            }
+            _ => "Results".to_string(),
+        };
+        let is_last = tab.selected_result + 1 == tab.statement_results.len();
+
+        let row_count = match tab.query_result() {
             Some(QueryResult::Select { rows, .. }) => Some(rows.len()),
             _ => None,
         };
         let title = match (tab.visual_select, row_count) {
             (Some(crate::controller::VisualSelect::Cell { .. }), _) => {
-                "Results ── VISUAL".to_string()
+                format!("{} ── VISUAL", label)
             }
             (Some(crate::controller::VisualSelect::Line { .. }), _) => {
-                "Results ── VISUAL LINE".to_string()
+                format!("{} ── VISUAL LINE", label)
+            }
+            (None, Some(count)) if is_last && tab.result_stream.is_some() => {
+                format!("{} ({} rows loaded, more available)", label, count)
             }
-            (None, Some(count)) => format!("Results ({} rows)", count),
-            (None, None) => "Results".to_string(),
+            (None, Some(count)) if is_last && tab.result_truncated => {
+                format!("{} ({} rows loaded, fetch stopped)", label, count)
+            }
+            (None, Some(count)) => format!("{} ({} rows)", label, count),
+            (None, None) => label,
         };
         let block = panel_block(&title, is_focused, Borders::NONE);
 
@@ -509,15 +602,21 @@ impl App {
             height: inner_area.height,
         };
 
-        match &tab.query_result {
-            Some(QueryResult::Select { columns, rows }) => {
+        match tab.selected_statement().map(|r| &r.outcome) {
+            Some(Ok(QueryResult::Select { columns, rows, .. })) => {
                 self.draw_result_table(frame, padded_area, columns, rows, is_focused, bg_color);
             }
-            Some(QueryResult::Execute { rows_affected }) => {
+            Some(Ok(QueryResult::Execute { rows_affected })) => {
                 let msg = Paragraph::new(format!("{} row(s) affected", rows_affected))
                     .style(Style::default().fg(SUCCESS).bg(bg_color));
                 frame.render_widget(msg, padded_area);
             }
+            Some(Err(e)) => {
+                let msg = Paragraph::new(format!("Error: {}", e))
+                    .wrap(Wrap { trim: false })
+                    .style(Style::default().fg(WARNING).bg(bg_color));
+                frame.render_widget(msg, padded_area);
+            }
             None => {
                 let msg = Paragraph::new(Line::from(vec![
                     Span::styled("Press ", dim()),
@@ -535,7 +634,7 @@ impl App {
         frame: &mut Frame,
         area: Rect,
         columns: &[String],
-        rows: &[Vec<String>],
+        rows: &[Vec<CellValue>],
         is_focused: bool,
         bg_color: ratatui::style::Color,
     ) {
@@ -643,20 +742,30 @@ impl App {
                     .iter()
                     .zip(visible_col_widths.iter())
                     .map(|(&col_idx, &w)| {
-                        let cell_text = row.get(col_idx).map(|s| s.as_str()).unwrap_or("");
-                        let display_text = truncate_str(cell_text, w.saturating_sub(1));
-                        let style = if is_line_visual && in_visual_range {
-                            Style::default().fg(TEXT).bg(BLUE)
-                        } else if is_cell_visual && in_visual_range && col_idx == selected_col {
-                            Style::default().fg(TEXT).bg(BLUE)
-                        } else if col_idx == selected_col && is_cursor_row {
+                        let value = row.get(col_idx).unwrap_or(&CellValue::Null);
+                        let display_text = truncate_str(&value.to_string(), w.saturating_sub(1));
+                        let is_selected_cell = (is_line_visual && in_visual_range)
+                            || (col_idx == selected_col
+                                && ((is_cell_visual && in_visual_range) || is_cursor_row));
+                        let mut style = if is_selected_cell {
                             Style::default().fg(TEXT).bg(BLUE)
                         } else if col_idx == selected_col && is_focused {
                             Style::default().fg(TEXT).bg(HIGHLIGHT)
+                        } else if value.is_null() {
+                            dim()
                         } else {
                             text()
                         };
-                        Cell::from(display_text).style(style)
+                        if value.is_null() {
+                            style = style.add_modifier(Modifier::ITALIC);
+                        }
+                        // Right-align numbers, keeping a space before the next column
+                        let line = if value.is_numeric() {
+                            Line::from(format!("{} ", display_text)).right_aligned()
+                        } else {
+                            Line::from(display_text)
+                        };
+                        Cell::from(line).style(style)
                     });
 
                 let row = Row::new(cells).height(1);
@@ -697,9 +806,30 @@ impl App {
             } => {
                 popups::draw_save_template(frame, name, connections, *editing_connections);
             }
+            PopupState::History {
+                selected,
+                filter,
+                searching,
+                all_connections,
+            } => {
+                popups::draw_history(
+                    frame,
+                    &self.controller,
+                    *selected,
+                    filter,
+                    *searching,
+                    *all_connections,
+                );
+            }
             PopupState::ConfirmDelete { name, .. } => {
                 popups::draw_confirm_delete(frame, name);
             }
+            PopupState::ConfirmClose { all } => {
+                popups::draw_confirm_close(frame, &self.controller, *all);
+            }
+            PopupState::ConfirmDestructive { statement, .. } => {
+                popups::draw_confirm_destructive(frame, statement);
+            }
             PopupState::RecordDetail {
                 row_index,
                 selected_field,
@@ -723,6 +853,13 @@ impl App {
             PopupState::Help { scroll } => {
                 popups::draw_help(frame, *scroll);
             }
+            PopupState::ExplainPlan {
+                plan,
+                selected,
+                scroll,
+            } => {
+                popups::draw_explain_plan(frame, plan, *selected, *scroll);
+            }
             PopupState::None => {}
         }
     }
@@ -1,12 +1,13 @@
 use ratatui::Frame;
-use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use ratatui::layout::{Alignment, Constraint, Direction, Layout, Margin, Rect};
 use ratatui::style::{Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
 
 use crate::completion::{Suggestion, SuggestionKind};
 use crate::controller::Controller;
 use crate::db::QueryResult;
+use crate::explain::Plan;
 use crate::templates::TemplateScope;
 
 use super::theme::*;
@@ -121,6 +122,132 @@ pub fn draw_template_list(
     frame.render_widget(Paragraph::new(help).alignment(Alignment::Center), help_area);
 }
 
+/// Draw query history popup
+pub fn draw_history(
+    frame: &mut Frame,
+    controller: &Controller,
+    selected: usize,
+    filter: &str,
+    searching: bool,
+    all_connections: bool,
+) {
+    let area = frame.area();
+    let entries = controller.filtered_history(filter, all_connections);
+
+    let popup_area = centered_rect_pct(area, 0.8, 0.8, 50, 12);
+    frame.render_widget(Clear, popup_area);
+
+    let scope = if all_connections {
+        "all connections".to_string()
+    } else {
+        controller
+            .current_tab()
+            .connections
+            .get(controller.current_tab().connected_index)
+            .map_or_else(String::new, |c| c.name.clone())
+    };
+    let title = format!("History ({}, {})", entries.len(), scope);
+    let block = popup_block(&title, BLUE);
+    let block_inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    // Reserve space for help line at bottom
+    let inner_area = Rect {
+        x: block_inner.x,
+        y: block_inner.y,
+        width: block_inner.width,
+        height: block_inner.height.saturating_sub(2),
+    };
+    let help_area = Rect {
+        x: block_inner.x,
+        y: block_inner.y + block_inner.height.saturating_sub(1),
+        width: block_inner.width,
+        height: 1,
+    };
+
+    // Split inner area if searching (list + search bar inside the border)
+    let (list_area, search_area) = if searching {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(inner_area);
+        (chunks[0], Some(chunks[1]))
+    } else {
+        (inner_area, None)
+    };
+
+    // Leave room for the highlight symbol
+    let width = list_area.width.saturating_sub(4) as usize;
+    let items: Vec<ListItem> = entries
+        .iter()
+        .map(|e| {
+            // The whole statement on one line
+            let query = e.query.split_whitespace().collect::<Vec<_>>().join(" ");
+            let mut details = vec![
+                Span::styled(format!("  {}", e.timestamp), dim()),
+                Span::styled(format!("  {}/{}", e.connection, e.database), dim()),
+            ];
+            match &e.error {
+                Some(error) => details.push(Span::styled(
+                    format!("  {}", error.lines().next().unwrap_or("")),
+                    Style::default().fg(WARNING),
+                )),
+                None => details.push(Span::styled(
+                    format!(
+                        "  {}{} row(s) in {:.2?}",
+                        if e.more_rows { "≥" } else { "" },
+                        e.rows,
+                        std::time::Duration::from_millis(e.duration_ms)
+                    ),
+                    dim(),
+                )),
+            }
+
+            ListItem::new(vec![
+                Line::from(Span::styled(truncate_str(&query, width), text())),
+                Line::from(details),
+            ])
+        })
+        .collect();
+
+    if items.is_empty() {
+        let hint = if all_connections {
+            "No matching statements"
+        } else {
+            "No matching statements on this connection, Tab shows all"
+        };
+        frame.render_widget(Paragraph::new(format!(" {}", hint)).style(dim()), list_area);
+    }
+
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(HIGHLIGHT).fg(TEXT))
+        .highlight_symbol("> ");
+
+    let mut list_state = ListState::default();
+    list_state.select(Some(selected.min(entries.len().saturating_sub(1))));
+
+    frame.render_stateful_widget(list, list_area, &mut list_state);
+
+    // Draw search input at bottom if searching (inside the border)
+    if let Some(search_area) = search_area {
+        let search_text = format!("/{}_", filter);
+        frame.render_widget(Paragraph::new(search_text).style(text()), search_area);
+    }
+
+    // Help line at bottom
+    let help = Line::from(vec![
+        Span::styled("/", Style::default().fg(TEXT)),
+        Span::styled(" search  ", dim()),
+        Span::styled("Enter", Style::default().fg(TEXT)),
+        Span::styled(" insert  ", dim()),
+        Span::styled("Tab", Style::default().fg(TEXT)),
+        Span::styled(" this / all connections  ", dim()),
+        Span::styled("Esc", Style::default().fg(TEXT)),
+        Span::styled(" close", dim()),
+    ]);
+    frame.render_widget(Paragraph::new(help).alignment(Alignment::Center), help_area);
+}
+
 /// Draw save template popup
 pub fn draw_save_template(
     frame: &mut Frame,
@@ -236,6 +363,77 @@ pub fn draw_confirm_delete(frame: &mut Frame, template_name: &str) {
     );
 }
 
+/// Draw the confirmation for closing tabs with uncommitted statements
+pub fn draw_confirm_close(frame: &mut Frame, controller: &Controller, all: bool) {
+    let area = frame.area();
+    let popup_area = centered_rect(area, 60, 5);
+    frame.render_widget(Clear, popup_area);
+
+    let block = popup_block("Open Transaction", WARNING);
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let message = if all {
+        let tabs = controller
+            .tabs
+            .iter()
+            .filter(|t| t.uncommitted() > 0)
+            .count();
+        format!(
+            "{} tab(s) have uncommitted statements. Quit and roll back? [y/N]",
+            tabs
+        )
+    } else {
+        format!(
+            "{} uncommitted statement(s). Close the tab and roll back? [y/N]",
+            controller.current_tab().uncommitted()
+        )
+    };
+
+    frame.render_widget(
+        Paragraph::new(message)
+            .style(text())
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        inner.inner(Margin::new(1, 1)),
+    );
+}
+
+/// Draw the confirmation for a statement that looks destructive
+pub fn draw_confirm_destructive(frame: &mut Frame, statement: &str) {
+    let area = frame.area();
+    let popup_area = centered_rect(area, 70, 7);
+    frame.render_widget(Clear, popup_area);
+
+    let block = popup_block("Destructive Statement", WARNING);
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .margin(1)
+        .split(inner);
+
+    let statement = statement.split_whitespace().collect::<Vec<_>>().join(" ");
+    frame.render_widget(
+        Paragraph::new(truncate_str(&statement, chunks[0].width as usize))
+            .style(bold(WARNING))
+            .alignment(Alignment::Center),
+        chunks[0],
+    );
+    frame.render_widget(
+        Paragraph::new("Run it anyway? [y/N]")
+            .style(text())
+            .alignment(Alignment::Center),
+        chunks[2],
+    );
+}
+
 /// Draw record detail popup
 pub fn draw_record_detail(
     frame: &mut Frame,
@@ -246,7 +444,12 @@ pub fn draw_record_detail(
 ) {
     let tab = controller.current_tab();
 
-    let Some(QueryResult::Select { columns, rows }) = &tab.query_result else {
+    let Some(QueryResult::Select {
+        columns,
+        column_types,
+        rows,
+    }) = tab.query_result()
+    else {
         return;
     };
 
@@ -284,12 +487,20 @@ pub fn draw_record_detail(
         height: 1,
     };
 
-    // Calculate the maximum field name width for alignment
+    // Calculate the maximum field name and type widths for alignment
     let max_name_width = columns.iter().map(|c| c.len()).max().unwrap_or(0);
+    let max_type_width = column_types.iter().map(|t| t.len()).max().unwrap_or(0);
+    // Types sit between the name and the value, after a space
+    let type_width = if max_type_width > 0 {
+        max_type_width + 1
+    } else {
+        0
+    };
     let left_padding = 2;
     let value_width = inner
         .width
-        .saturating_sub(max_name_width as u16 + 4 + left_padding) as usize;
+        .saturating_sub((max_name_width + type_width) as u16 + 4 + left_padding)
+        as usize;
 
     // Build lines for each field, tracking which line each field starts at
     let mut lines: Vec<Line> = Vec::new();
@@ -300,6 +511,10 @@ pub fn draw_record_detail(
 
         let is_selected = field_idx == selected_field;
         let field_name = format!("{:<width$}", col, width = max_name_width);
+        let field_type = match column_types.get(field_idx) {
+            Some(t) if type_width > 0 => format!(" {:<width$}", t, width = max_type_width),
+            _ => " ".repeat(type_width),
+        };
 
         // Style based on selection
         let name_style = if is_selected {
@@ -322,27 +537,31 @@ pub fn draw_record_detail(
         };
 
         let pad = "  ";
-        if value.is_empty() {
+        let value_text = value.to_string();
+        if value.is_null() || value_text.is_empty() {
+            let placeholder = if value.is_null() { "NULL" } else { "(empty)" };
             lines.push(Line::from(vec![
                 Span::raw(pad),
                 Span::styled(field_name, name_style),
+                Span::styled(field_type, dim()),
                 Span::styled(" : ", dim()),
-                Span::styled("(empty)", empty_style),
+                Span::styled(placeholder, empty_style),
             ]));
         } else {
             // Wrap long values
-            let value_lines = wrap_text(value, value_width);
+            let value_lines = wrap_text(&value_text, value_width);
             for (i, line_text) in value_lines.iter().enumerate() {
                 if i == 0 {
                     lines.push(Line::from(vec![
                         Span::raw(pad),
                         Span::styled(field_name.clone(), name_style),
+                        Span::styled(field_type.clone(), dim()),
                         Span::styled(" : ", dim()),
                         Span::styled(line_text.clone(), value_style),
                     ]));
                 } else {
                     // Continuation lines - indent to align with value
-                    let indent = " ".repeat(max_name_width + 3 + pad.len());
+                    let indent = " ".repeat(max_name_width + type_width + 3 + pad.len());
                     lines.push(Line::from(vec![
                         Span::styled(indent, Style::default()),
                         Span::styled(line_text.clone(), value_style),
@@ -381,11 +600,9 @@ pub fn draw_record_detail(
     if total_lines > visible_lines {
         let h = inner.height as usize;
         let thumb_h = (h * visible_lines / total_lines).max(1);
-        let thumb_pos = if max_scroll > 0 {
-            actual_scroll * (h - thumb_h) / max_scroll
-        } else {
-            0
-        };
+        let thumb_pos = (actual_scroll * (h - thumb_h))
+            .checked_div(max_scroll)
+            .unwrap_or(0);
 
         let scrollbar: String = (0..h)
             .map(|i| {
@@ -419,6 +636,146 @@ pub fn draw_record_detail(
     frame.render_widget(Paragraph::new(help).alignment(Alignment::Center), help_area);
 }
 
+/// Draw the `:explain` plan as a tree, highlighting the most expensive nodes
+pub fn draw_explain_plan(frame: &mut Frame, plan: &Plan, selected: usize, scroll: usize) {
+    let area = frame.area();
+    let popup_area = centered_rect_pct(area, 0.9, 0.8, 60, 12);
+    frame.render_widget(Clear, popup_area);
+
+    let block = popup_block(&plan.title, BLUE);
+    let block_inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    // Reserve space for help line at bottom
+    let inner = Rect {
+        x: block_inner.x,
+        y: block_inner.y,
+        width: block_inner.width,
+        height: block_inner.height.saturating_sub(2),
+    };
+    let help_area = Rect {
+        x: block_inner.x,
+        y: block_inner.y + block_inner.height.saturating_sub(1),
+        width: block_inner.width,
+        height: 1,
+    };
+
+    let visible = plan.visible();
+    let visible_lines = (inner.height as usize).max(1);
+    let actual_scroll = if selected < scroll {
+        selected
+    } else if selected >= scroll + visible_lines {
+        selected + 1 - visible_lines
+    } else {
+        scroll
+    };
+    let max_scroll = visible.len().saturating_sub(visible_lines);
+    let actual_scroll = actual_scroll.min(max_scroll);
+
+    let lines: Vec<Line> = visible
+        .iter()
+        .enumerate()
+        .skip(actual_scroll)
+        .take(visible_lines)
+        .map(|(row, &index)| {
+            let line = &plan.lines[index];
+            let node = &line.node;
+            let glyph = match (line.has_children, line.collapsed) {
+                (false, _) => "  ",
+                (true, false) => "▾ ",
+                (true, true) => "▸ ",
+            };
+            let label_style = if line.is_hot() { bold(WARNING) } else { text() };
+
+            let mut spans = vec![
+                Span::raw(format!(" {}", "  ".repeat(line.depth))),
+                Span::styled(glyph, dim()),
+                Span::styled(node.label.clone(), label_style),
+            ];
+            let figures = [
+                node.cost.map(|cost| format!("cost {}", plan_number(cost))),
+                node.rows.map(|rows| format!("rows {}", plan_number(rows))),
+                node.time
+                    .map(|time| format!("time {}ms", plan_number(time))),
+            ];
+            for figure in figures.into_iter().flatten() {
+                spans.push(Span::styled(
+                    format!("  {}", figure),
+                    Style::default().fg(BLUE),
+                ));
+            }
+            if let Some(share) = line.share.filter(|_| line.is_hot()) {
+                spans.push(Span::styled(
+                    format!("  {:.0}%", share * 100.0),
+                    bold(WARNING),
+                ));
+            }
+            if !node.detail.is_empty() {
+                spans.push(Span::styled(format!("  {}", node.detail), dim()));
+            }
+
+            let line = Line::from(spans);
+            if row == selected {
+                line.style(Style::default().bg(HIGHLIGHT))
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(lines).style(Style::default().bg(SURFACE)),
+        inner,
+    );
+
+    // Draw scroll indicator on the right edge if content overflows
+    if visible.len() > visible_lines {
+        let h = inner.height as usize;
+        let thumb_h = (h * visible_lines / visible.len()).max(1);
+        let thumb_pos = (actual_scroll * (h - thumb_h))
+            .checked_div(max_scroll)
+            .unwrap_or(0);
+
+        let scrollbar: String = (0..h)
+            .map(|i| {
+                if i >= thumb_pos && i < thumb_pos + thumb_h {
+                    "█"
+                } else {
+                    "░"
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let scrollbar_area = Rect {
+            x: inner.x + inner.width.saturating_sub(1),
+            y: inner.y,
+            width: 1,
+            height: inner.height,
+        };
+        frame.render_widget(Paragraph::new(scrollbar).style(dim()), scrollbar_area);
+    }
+
+    let help = Line::from(vec![
+        Span::styled("j/k", Style::default().fg(TEXT)),
+        Span::styled(" navigate  ", dim()),
+        Span::styled("Enter/h/l", Style::default().fg(TEXT)),
+        Span::styled(" collapse/expand  ", dim()),
+        Span::styled("Esc", Style::default().fg(TEXT)),
+        Span::styled(" close", dim()),
+    ]);
+    frame.render_widget(Paragraph::new(help).alignment(Alignment::Center), help_area);
+}
+
+/// Plan figures: whole numbers as they are, fractions to two decimals
+fn plan_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
 /// Draw help popup
 pub fn draw_help(frame: &mut Frame, scroll: usize) {
     let area = frame.area();
@@ -460,11 +817,9 @@ pub fn draw_help(frame: &mut Frame, scroll: usize) {
     // Scrollbar
     if total > visible {
         let thumb_h = (visible * visible / total).max(1);
-        let thumb_pos = if max_scroll > 0 {
-            scroll * (visible - thumb_h) / max_scroll
-        } else {
-            0
-        };
+        let thumb_pos = (scroll * (visible - thumb_h))
+            .checked_div(max_scroll)
+            .unwrap_or(0);
         let scrollbar: String = (0..visible)
             .map(|i| {
                 if i >= thumb_pos && i < thumb_pos + thumb_h {
@@ -513,25 +868,31 @@ fn help_lines<'a>(key_col: usize, total_width: usize) -> Vec<Line<'a>> {
         // ── DB View — General ────────────────────────────────────────────
         ("DB View", ""),
         ("^B",               "toggle sidebar"),
-        ("F5 / ^E",          "execute query"),
+        ("F5",               "run all statements in the editor"),
+        ("Esc",              "cancel running query"),
         ("^G",               "edit query in external editor"),
         ("^O",               "open template list"),
         ("^S",               "save query as template"),
+        ("^P",               "open query history"),
         ("^Space",           "trigger autocompletion"),
         ("", ""),
 
         // ── Sidebar ──────────────────────────────────────────────────────
         ("Sidebar", ""),
-        ("j / k",            "navigate tables"),
-        ("Enter",            "run SELECT * from table"),
-        ("d",                "describe table"),
-        ("r",                "refresh table list"),
-        ("l / →",            "focus query editor"),
+        ("j / k",            "navigate objects"),
+        ("Enter",            "toggle group / table columns"),
+        ("l / →",            "expand, or focus query editor"),
+        ("h / ←",            "collapse table / group"),
+        ("p",                "preview rows / definition"),
+        ("d",                "describe object"),
+        ("r",                "refresh object list"),
         ("", ""),
 
         // ── Query Editor ─────────────────────────────────────────────────
         ("Query Editor", ""),
-        ("F5 / ^E",          "execute query"),
+        ("F5",               "run all statements"),
+        ("^E",               "run selection, or statement at cursor"),
+        ("Shift+arrows",     "select text"),
         ("^Z / ^R",          "undo / redo"),
         ("^K",               "delete to end of line"),
         ("^← / ^→",          "word back / forward"),
@@ -551,6 +912,7 @@ fn help_lines<'a>(key_col: usize, total_width: usize) -> Vec<Line<'a>> {
         ("v",                "visual select (cells in column)"),
         ("V",                "visual select (whole rows)"),
         ("y",                "yank (copy) selection to clipboard"),
+        ("[ / ]",            "previous / next statement result"),
         ("Enter",            "open record detail popup"),
         ("Tab",              "focus sidebar"),
         ("", ""),
@@ -558,8 +920,18 @@ fn help_lines<'a>(key_col: usize, total_width: usize) -> Vec<Line<'a>> {
         // ── Commands ─────────────────────────────────────────────────────
         ("Commands", ""),
         (":q",               "close current tab"),
-        (":qa / :q!",        "quit"),
+        (":qa",              "quit"),
+        (":q!",              "quit without asking about open transactions"),
         (":db",              "switch database"),
+        (":schema [s, ...]", "set / show search_path (PostgreSQL)"),
+        (":timeout [secs]",  "set / show query timeout (off disables)"),
+        (":autocommit off", "manual commit: keep a transaction open"),
+        (":autocommit on",  "back to committing each statement"),
+        (":commit",          "commit the open transaction"),
+        (":rollback",        "roll back the open transaction"),
+        (":history",         "open query history"),
+        (":explain",         "plan of the statement under the cursor"),
+        (":explain analyze", "run it and show actual rows and time"),
         (":new",             "open new tab"),
         (":next / :prev",    "navigate tabs"),
         (":system",          "toggle system databases (DB list view)"),
@@ -1,33 +1,58 @@
-use crate::db::QueryResult;
-use crate::error::Result;
+use crate::db::{CellValue, ColumnInfo, DbObject, ObjectKind, PAGE_SIZE, PagedResult, RowStream};
+use crate::error::{Result, SqliError};
 use crate::format as fmt;
+use crate::tls::TlsConfig;
 
 use chrono::{DateTime, NaiveDate, Utc};
-use scylla::frame::response::result::CqlValue;
+use openssl::ssl::{SslContext, SslContextBuilder, SslFiletype, SslMethod, SslVerifyMode};
+use scylla::frame::response::result::{ColumnType, CqlValue};
 use scylla::frame::value::{CqlDate, CqlDecimal, CqlDuration, CqlTime, CqlTimestamp};
-use scylla::{Session, SessionBuilder};
+use scylla::statement::PagingState;
+use scylla::statement::query::Query;
+use scylla::transport::load_balancing::DefaultPolicy;
+use scylla::{ExecutionProfile, Session, SessionBuilder};
+use std::ops::ControlFlow;
 use std::sync::Arc;
 
+/// Rows requested per page when the connection does not set `page_size`
+const DEFAULT_PAGE_SIZE: u32 = 1000;
+
 pub struct CassandraClient {
     session: Arc<Session>,
+    page_size: i32,
 }
 
 impl CassandraClient {
     pub async fn connect(
-        host: &str,
-        port: u16,
+        contact_points: &[String],
         user: &str,
         password: &str,
         keyspace: &str,
+        tls: &TlsConfig,
+        local_datacenter: Option<&str>,
+        page_size: Option<u32>,
     ) -> Result<Self> {
-        let addr = format!("{}:{}", host, port);
-
-        let mut builder = SessionBuilder::new().known_node(&addr);
+        let mut builder = SessionBuilder::new()
+            .known_nodes(contact_points)
+            .ssl_context(ssl_context(tls)?);
 
         if !user.is_empty() {
             builder = builder.user(user, password);
         }
 
+        // Route requests to the local datacenter only
+        if let Some(dc) = local_datacenter {
+            let policy = DefaultPolicy::builder()
+                .prefer_datacenter(dc.to_string())
+                .permit_dc_failover(false)
+                .token_aware(true)
+                .build();
+            let profile = ExecutionProfile::builder()
+                .load_balancing_policy(policy)
+                .build();
+            builder = builder.default_execution_profile_handle(profile.into_handle());
+        }
+
         let session = builder.build().await?;
 
         // Use keyspace if provided
@@ -37,8 +62,10 @@ impl CassandraClient {
                 .await?;
         }
 
+        let page_size = page_size.unwrap_or(DEFAULT_PAGE_SIZE);
         Ok(Self {
             session: Arc::new(session),
+            page_size: i32::try_from(page_size).unwrap_or(i32::MAX),
         })
     }
 
@@ -74,59 +101,101 @@ impl CassandraClient {
         Ok(keyspaces)
     }
 
-    pub async fn list_tables(&self, keyspace: &str) -> Result<Vec<String>> {
+    pub async fn list_objects(&self, keyspace: &str) -> Result<Vec<DbObject>> {
+        let sources = [
+            (ObjectKind::Table, "table_name", "tables"),
+            (ObjectKind::MaterializedView, "view_name", "views"),
+            (ObjectKind::Type, "type_name", "types"),
+            (ObjectKind::Function, "function_name", "functions"),
+        ];
+
+        let mut objects = Vec::new();
+        for (kind, column, table) in sources {
+            let mut names = self.list_schema_names(column, table, keyspace).await?;
+            names.sort();
+            // Overloaded functions share a name
+            names.dedup();
+            objects.extend(names.into_iter().map(|name| DbObject::new(kind, name)));
+        }
+        Ok(objects)
+    }
+
+    /// Names from a `system_schema` table for one keyspace
+    async fn list_schema_names(
+        &self,
+        column: &str,
+        table: &str,
+        keyspace: &str,
+    ) -> Result<Vec<String>> {
         // Use WHERE clause to filter server-side for efficiency
-        let rows = self
-            .session
-            .query_unpaged(
-                "SELECT table_name FROM system_schema.tables WHERE keyspace_name = ?",
-                (keyspace,),
-            )
-            .await?;
+        let query = format!(
+            "SELECT {} FROM system_schema.{} WHERE keyspace_name = ?",
+            column, table
+        );
+        let rows = self.session.query_unpaged(query, (keyspace,)).await?;
 
-        let mut tables = Vec::new();
+        let mut names = Vec::new();
         if let Some(rows) = rows.rows {
             for row in rows {
                 if let Some(CqlValue::Text(t) | CqlValue::Ascii(t)) =
                     row.columns.first().and_then(|v| v.as_ref())
                 {
-                    tables.push(t.clone());
+                    names.push(t.clone());
                 }
             }
         }
-        tables.sort();
-        Ok(tables)
+        Ok(names)
     }
 
-    pub async fn execute_query(&self, query: &str) -> Result<QueryResult> {
-        let query_upper = query.trim().to_uppercase();
+    pub async fn execute_paged(&self, query: &str) -> Result<PagedResult> {
+        // Each request returns one page and the state to resume from,
+        // so the next page is only requested once the reader wants it.
+        // The reader's pages are the same size as the server's.
+        let query = Query::new(query).with_page_size(self.page_size);
+        let page_size = usize::try_from(self.page_size).unwrap_or(PAGE_SIZE);
+        let session = Arc::clone(&self.session);
+        let (mut writer, stream) = RowStream::channel(false, page_size);
+        let task = tokio::spawn(async move {
+            let mut paging_state = PagingState::start();
+            let mut header = true;
+            loop {
+                let page = session
+                    .query_single_page(query.clone(), &[], paging_state)
+                    .await;
+                let (result, paging) = match page {
+                    Ok(page) => page,
+                    Err(e) => return writer.fail(e.into()).await,
+                };
 
-        if query_upper.starts_with("SELECT") {
-            let result = self.session.query_unpaged(query, &[]).await?;
-
-            let columns: Vec<String> = result
-                .col_specs()
-                .iter()
-                .map(|spec| spec.name.clone())
-                .collect();
+                // Only a Rows response has rows, possibly none
+                if result.rows.is_none() {
+                    return writer.affected(0).await;
+                }
+                if header {
+                    header = false;
+                    let (columns, types) = result
+                        .col_specs()
+                        .iter()
+                        .map(|spec| (spec.name.clone(), cql_type_name(&spec.typ)))
+                        .unzip();
+                    if !writer.columns(columns, types).await {
+                        return;
+                    }
+                }
+                for row in result.rows.unwrap_or_default() {
+                    let values = row.columns.iter().map(Self::format_column_value).collect();
+                    if !writer.push(values).await {
+                        return;
+                    }
+                }
 
-            let mut data_rows: Vec<Vec<String>> = Vec::new();
-            if let Some(rows) = result.rows {
-                for row in rows {
-                    let row_data: Vec<String> =
-                        row.columns.iter().map(Self::format_column_value).collect();
-                    data_rows.push(row_data);
+                match paging.into_paging_control_flow() {
+                    ControlFlow::Continue(next) => paging_state = next,
+                    ControlFlow::Break(()) => return writer.finish().await,
                 }
             }
-
-            Ok(QueryResult::Select {
-                columns,
-                rows: data_rows,
-            })
-        } else {
-            self.session.query_unpaged(query, &[]).await?;
-            Ok(QueryResult::Execute { rows_affected: 0 })
-        }
+        });
+        stream.with_task(task).first_page().await
     }
 
     pub fn select_table_query(&self, table: &str, limit: usize, keyspace: Option<&str>) -> String {
@@ -153,69 +222,121 @@ impl CassandraClient {
         }
     }
 
-    pub async fn list_columns(&self, table: &str, keyspace: Option<&str>) -> Result<Vec<String>> {
+    pub fn definition_query(&self, obj: &DbObject, keyspace: Option<&str>) -> String {
+        let (columns, table, key) = match obj.kind {
+            ObjectKind::Type => ("type_name, field_names, field_types", "types", "type_name"),
+            ObjectKind::Function => (
+                "function_name, argument_names, argument_types, return_type, language, body",
+                "functions",
+                "function_name",
+            ),
+            _ => return self.describe_table_query(&obj.name, keyspace),
+        };
+        match keyspace {
+            Some(ks) => format!(
+                "SELECT {} FROM system_schema.{} WHERE keyspace_name = '{}' AND {} = '{}'",
+                columns, table, ks, key, obj.name
+            ),
+            None => format!(
+                "SELECT {} FROM system_schema.{} WHERE {} = '{}' ALLOW FILTERING",
+                columns, table, key, obj.name
+            ),
+        }
+    }
+
+    pub async fn list_columns(
+        &self,
+        table: &str,
+        keyspace: Option<&str>,
+    ) -> Result<Vec<ColumnInfo>> {
         let query = match keyspace {
             Some(ks) => format!(
-                "SELECT column_name FROM system_schema.columns \
+                "SELECT column_name, type, kind, position FROM system_schema.columns \
                  WHERE keyspace_name = '{}' AND table_name = '{}'",
                 ks, table
             ),
             None => format!(
-                "SELECT column_name FROM system_schema.columns WHERE table_name = '{}'",
+                "SELECT column_name, type, kind, position FROM system_schema.columns \
+                 WHERE table_name = '{}' ALLOW FILTERING",
                 table
             ),
         };
         let result = self.session.query_unpaged(query, &[]).await?;
+
+        // (kind rank, position, column)
         let mut columns = Vec::new();
         if let Some(rows) = result.rows {
             for row in rows {
-                if let Some(Some(scylla::frame::response::result::CqlValue::Text(name))) =
-                    row.columns.first()
-                {
-                    columns.push(name.clone());
-                }
+                let text = |i: usize| match row.columns.get(i) {
+                    Some(Some(CqlValue::Text(s) | CqlValue::Ascii(s))) => s.clone(),
+                    _ => String::new(),
+                };
+                let position = match row.columns.get(3) {
+                    Some(Some(CqlValue::Int(p))) => *p,
+                    _ => 0,
+                };
+                let kind = text(2);
+                let rank = match kind.as_str() {
+                    "partition_key" => 0,
+                    "clustering" => 1,
+                    "static" => 2,
+                    _ => 3,
+                };
+                let primary_key = rank < 2;
+                columns.push((
+                    rank,
+                    position,
+                    ColumnInfo {
+                        name: text(0),
+                        data_type: text(1),
+                        nullable: !primary_key,
+                        primary_key,
+                        foreign_key: false,
+                    },
+                ));
             }
         }
-        Ok(columns)
+        // Key columns first, in key order; the rest as returned (by name)
+        columns.sort_by_key(|(rank, position, _)| (*rank, *position));
+        Ok(columns.into_iter().map(|(_, _, c)| c).collect())
     }
 
-    fn format_column_value(value: &Option<CqlValue>) -> String {
+    fn format_column_value(value: &Option<CqlValue>) -> CellValue {
         match value {
-            None => "NULL".to_string(),
+            None => CellValue::Null,
             Some(v) => Self::format_cql_value(v),
         }
     }
 
-    fn format_cql_value(v: &CqlValue) -> String {
+    fn format_cql_value(v: &CqlValue) -> CellValue {
         match v {
-            CqlValue::Ascii(s) | CqlValue::Text(s) => s.clone(),
-            CqlValue::Int(i) => i.to_string(),
-            CqlValue::BigInt(i) => i.to_string(),
-            CqlValue::Float(f) => f.to_string(),
-            CqlValue::Double(d) => d.to_string(),
-            CqlValue::Boolean(b) => b.to_string(),
-            CqlValue::Uuid(u) => u.to_string(),
-            CqlValue::Timeuuid(u) => u.to_string(),
-            CqlValue::SmallInt(i) => i.to_string(),
-            CqlValue::TinyInt(i) => i.to_string(),
-            CqlValue::Counter(c) => c.0.to_string(),
-            CqlValue::Inet(addr) => addr.to_string(),
-            CqlValue::Varint(v) => fmt::varint(v.as_signed_bytes_be_slice()),
-            CqlValue::Duration(d) => Self::format_duration(d),
-            CqlValue::Timestamp(t) => Self::format_timestamp(t),
-            CqlValue::Date(d) => Self::format_date(d),
-            CqlValue::Time(t) => Self::format_time(t),
-            CqlValue::Decimal(d) => Self::format_decimal(d),
-            CqlValue::Blob(b) => fmt::bytes(b, 32),
-            CqlValue::Empty => "".to_string(),
-            CqlValue::List(l) => {
-                let items: Vec<String> = l.iter().map(Self::format_cql_value).collect();
-                format!("[{}]", items.join(","))
-            }
-            CqlValue::Set(s) => {
-                let items: Vec<String> = s.iter().map(Self::format_cql_value).collect();
-                format!("{{{}}}", items.join(","))
+            CqlValue::Ascii(s) | CqlValue::Text(s) => CellValue::Text(s.clone()),
+            CqlValue::Int(i) => CellValue::from(*i),
+            CqlValue::BigInt(i) => CellValue::from(*i),
+            CqlValue::Float(f) => CellValue::from(*f),
+            CqlValue::Double(d) => CellValue::from(*d),
+            CqlValue::Boolean(b) => CellValue::Bool(*b),
+            CqlValue::Uuid(u) => CellValue::Uuid(*u),
+            CqlValue::Timeuuid(u) => CellValue::Uuid((*u).into()),
+            CqlValue::SmallInt(i) => CellValue::from(*i),
+            CqlValue::TinyInt(i) => CellValue::from(*i),
+            CqlValue::Counter(c) => CellValue::from(c.0),
+            CqlValue::Inet(addr) => CellValue::Text(addr.to_string()),
+            CqlValue::Varint(v) => {
+                let digits = fmt::varint(v.as_signed_bytes_be_slice());
+                digits
+                    .parse()
+                    .map_or(CellValue::Decimal(digits), CellValue::Int)
             }
+            CqlValue::Duration(d) => CellValue::Temporal(Self::format_duration(d)),
+            CqlValue::Timestamp(t) => CellValue::Temporal(Self::format_timestamp(t)),
+            CqlValue::Date(d) => CellValue::Temporal(Self::format_date(d)),
+            CqlValue::Time(t) => CellValue::Temporal(Self::format_time(t)),
+            CqlValue::Decimal(d) => CellValue::Decimal(Self::format_decimal(d)),
+            CqlValue::Blob(b) => CellValue::Bytes(b.clone()),
+            CqlValue::Empty => CellValue::Text(String::new()),
+            CqlValue::List(l) => CellValue::Array(l.iter().map(Self::format_cql_value).collect()),
+            CqlValue::Set(s) => CellValue::Array(s.iter().map(Self::format_cql_value).collect()),
             CqlValue::Map(m) => {
                 let entries: Vec<String> = m
                     .iter()
@@ -223,19 +344,16 @@ impl CassandraClient {
                         format!("{}:{}", Self::format_cql_value(k), Self::format_cql_value(v))
                     })
                     .collect();
-                format!("{{{}}}", entries.join(","))
+                CellValue::Text(format!("{{{}}}", entries.join(",")))
             }
             CqlValue::Tuple(t) => {
                 let items: Vec<String> = t
                     .iter()
-                    .map(|v| match v {
-                        Some(val) => Self::format_cql_value(val),
-                        None => "NULL".to_string(),
-                    })
+                    .map(|v| Self::format_column_value(v).to_string())
                     .collect();
-                format!("({})", items.join(","))
+                CellValue::Text(format!("({})", items.join(",")))
             }
-            _ => format!("{:?}", v),
+            _ => CellValue::Text(format!("{:?}", v)),
         }
     }
 
@@ -298,3 +416,79 @@ impl CassandraClient {
         fmt::decimal_from_bytes(bytes, scale)
     }
 }
+
+/// CQL name of a column type, e.g. `map<text, int>`
+fn cql_type_name(typ: &ColumnType) -> String {
+    match typ {
+        ColumnType::Custom(name) => name.clone(),
+        ColumnType::Ascii => "ascii".to_string(),
+        ColumnType::Boolean => "boolean".to_string(),
+        ColumnType::Blob => "blob".to_string(),
+        ColumnType::Counter => "counter".to_string(),
+        ColumnType::Date => "date".to_string(),
+        ColumnType::Decimal => "decimal".to_string(),
+        ColumnType::Double => "double".to_string(),
+        ColumnType::Duration => "duration".to_string(),
+        ColumnType::Float => "float".to_string(),
+        ColumnType::Int => "int".to_string(),
+        ColumnType::BigInt => "bigint".to_string(),
+        ColumnType::Text => "text".to_string(),
+        ColumnType::Timestamp => "timestamp".to_string(),
+        ColumnType::Inet => "inet".to_string(),
+        ColumnType::List(t) => format!("list<{}>", cql_type_name(t)),
+        ColumnType::Map(k, v) => format!("map<{}, {}>", cql_type_name(k), cql_type_name(v)),
+        ColumnType::Set(t) => format!("set<{}>", cql_type_name(t)),
+        ColumnType::UserDefinedType { type_name, .. } => type_name.clone(),
+        ColumnType::SmallInt => "smallint".to_string(),
+        ColumnType::TinyInt => "tinyint".to_string(),
+        ColumnType::Time => "time".to_string(),
+        ColumnType::Timeuuid => "timeuuid".to_string(),
+        ColumnType::Tuple(types) => {
+            let types: Vec<String> = types.iter().map(cql_type_name).collect();
+            format!("tuple<{}>", types.join(", "))
+        }
+        ColumnType::Uuid => "uuid".to_string(),
+        ColumnType::Varint => "varint".to_string(),
+    }
+}
+
+/// Build the OpenSSL context for the driver, or None when TLS is disabled.
+/// Prefer has no plaintext fallback.
+fn ssl_context(tls: &TlsConfig) -> Result<Option<SslContext>> {
+    if !tls.mode.is_enabled() {
+        return Ok(None);
+    }
+    // Nodes are discovered by IP address, so there is no host name to check
+    if tls.mode.verifies_hostname() {
+        return Err(SqliError::Connection(
+            "sslmode verify-full is not supported for Cassandra, use verify-ca".to_string(),
+        ));
+    }
+
+    let mut builder = SslContextBuilder::new(SslMethod::tls())?;
+    if tls.mode.verifies_ca() {
+        builder.set_verify(SslVerifyMode::PEER);
+        match tls.root_cert {
+            Some(ref path) => builder.set_ca_file(path)?,
+            None => builder.set_default_verify_paths()?,
+        }
+    } else {
+        builder.set_verify(SslVerifyMode::NONE);
+    }
+
+    match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => {
+            builder.set_certificate_chain_file(cert)?;
+            builder.set_private_key_file(key, SslFiletype::PEM)?;
+            builder.check_private_key()?;
+        }
+        (None, None) => {}
+        _ => {
+            return Err(SqliError::Connection(
+                "TLS client certificate and key must be set together".to_string(),
+            ));
+        }
+    }
+
+    Ok(Some(builder.build()))
+}
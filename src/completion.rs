@@ -1,4 +1,5 @@
 /// SQL autocompletion support
+use crate::lexer::{self, Dialect, TokenKind};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum CompletionContext {
@@ -8,6 +9,8 @@ pub enum CompletionContext {
     Table,
     /// After a table alias and dot (e.g., "u.") - suggest columns for that table
     Column { table_or_alias: String },
+    /// Inside a string, quoted identifier or comment - nothing to suggest
+    None,
 }
 
 #[derive(Debug, Clone)]
@@ -111,19 +114,7 @@ const SQL_KEYWORDS: &[&str] = &[
 ];
 
 /// Keywords that should be followed by table names
-const TABLE_KEYWORDS: &[&str] = &[
-    "FROM",
-    "JOIN",
-    "INNER JOIN",
-    "LEFT JOIN",
-    "RIGHT JOIN",
-    "FULL JOIN",
-    "CROSS JOIN",
-    "INTO",
-    "UPDATE",
-    "TABLE",
-    "TRUNCATE",
-];
+const TABLE_KEYWORDS: &[&str] = &["FROM", "JOIN", "INTO", "UPDATE", "TABLE", "TRUNCATE"];
 
 /// Get the word being typed at cursor position
 pub fn get_current_word(text: &str, cursor_pos: usize) -> (String, usize) {
@@ -140,48 +131,48 @@ pub fn get_current_word(text: &str, cursor_pos: usize) -> (String, usize) {
 }
 
 /// Detect completion context based on text before cursor
-pub fn detect_context(text: &str, cursor_pos: usize) -> CompletionContext {
+pub fn detect_context(text: &str, cursor_pos: usize, dialect: Dialect) -> CompletionContext {
     let before_cursor = &text[..cursor_pos.min(text.len())];
-    let before_lower = before_cursor.to_lowercase();
+    let tokens = lexer::tokenize(before_cursor, dialect);
+    let mut tokens = tokens.as_slice();
+
+    if tokens.last().is_some_and(|t| t.unterminated) {
+        return CompletionContext::None;
+    }
+
+    // The word being typed is the prefix, not part of the context
+    if let [rest @ .., last] = tokens
+        && last.kind == TokenKind::Word
+    {
+        tokens = rest;
+    }
 
     // Check for table.column pattern (e.g., "users." or "u.")
-    if let Some(dot_pos) = before_cursor.rfind('.') {
-        let after_dot = &before_cursor[dot_pos + 1..];
-        // Only if we're right after the dot or typing column name
-        if after_dot.chars().all(|c| c.is_alphanumeric() || c == '_') {
-            // Find the table/alias before the dot
-            let before_dot = &before_cursor[..dot_pos];
-            let table_start = before_dot
-                .rfind(|c: char| !c.is_alphanumeric() && c != '_')
-                .map(|i| i + 1)
-                .unwrap_or(0);
-            let table_or_alias = before_dot[table_start..].to_string();
-            if !table_or_alias.is_empty() {
-                return CompletionContext::Column { table_or_alias };
-            }
-        }
+    if let [.., name, dot] = tokens
+        && dot.text == "."
+        && let Some(table_or_alias) = name.ident()
+    {
+        return CompletionContext::Column { table_or_alias };
     }
 
-    // Check if we're after a table keyword
-    for kw in TABLE_KEYWORDS {
-        let kw_lower = kw.to_lowercase();
-        // Look for keyword followed by optional whitespace at end
-        if let Some(pos) = before_lower.rfind(&kw_lower) {
-            let after_kw = &before_cursor[pos + kw.len()..];
-            // If only whitespace and maybe partial word after keyword
-            if after_kw
-                .trim_start()
-                .chars()
-                .all(|c| c.is_alphanumeric() || c == '_')
-            {
-                return CompletionContext::Table;
-            }
-        }
+    // Check if we're right after a table keyword
+    let previous = tokens.iter().rev().find(|t| !t.is_trivia());
+    if previous.is_some_and(|t| TABLE_KEYWORDS.iter().any(|kw| t.is_keyword(kw))) {
+        return CompletionContext::Table;
     }
 
     CompletionContext::General
 }
 
+/// Match a table by prefix, also on the bare name of `schema.table`
+fn table_matches(table: &str, prefix_lower: &str) -> bool {
+    let table_lower = table.to_lowercase();
+    table_lower.starts_with(prefix_lower)
+        || table_lower
+            .rsplit_once('.')
+            .is_some_and(|(_, name)| name.starts_with(prefix_lower))
+}
+
 /// Generate completion suggestions
 pub fn get_suggestions(
     context: &CompletionContext,
@@ -205,7 +196,7 @@ pub fn get_suggestions(
             }
             // Also suggest tables (might be typing table name in SELECT)
             for table in tables {
-                if table.to_lowercase().starts_with(&prefix_lower) {
+                if table_matches(table, &prefix_lower) {
                     suggestions.push(Suggestion {
                         text: table.clone(),
                         kind: SuggestionKind::Table,
@@ -216,7 +207,7 @@ pub fn get_suggestions(
         CompletionContext::Table => {
             // Suggest tables
             for table in tables {
-                if table.to_lowercase().starts_with(&prefix_lower) {
+                if table_matches(table, &prefix_lower) {
                     suggestions.push(Suggestion {
                         text: table.clone(),
                         kind: SuggestionKind::Table,
@@ -224,6 +215,7 @@ pub fn get_suggestions(
                 }
             }
         }
+        CompletionContext::None => {}
         CompletionContext::Column { .. } => {
             // Suggest columns for the table
             for col in columns {
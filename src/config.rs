@@ -1,4 +1,6 @@
 use crate::controller::{DatabaseConn, DatabaseType};
+use crate::ssh_tunnel::SshConfig;
+use crate::tls::SslMode;
 use indexmap::IndexMap;
 use serde::Deserialize;
 use std::fs;
@@ -10,6 +12,9 @@ pub struct ConnectionConfig {
     pub db_type: String,
     #[serde(default)]
     pub host: String,
+    /// Contact points for Cassandra clusters (host or host:port)
+    #[serde(default)]
+    pub hosts: Vec<String>,
     #[serde(default)]
     pub port: u16,
     #[serde(default)]
@@ -22,47 +27,134 @@ pub struct ConnectionConfig {
     pub database: Option<String>,
     #[serde(default)]
     pub path: Option<String>,
+    /// Unix socket path (MySQL)
+    #[serde(default)]
+    pub socket: Option<String>,
     #[serde(default)]
     pub tls: bool,
+    /// TLS mode: disable, prefer, require, verify-ca, verify-full
+    #[serde(default)]
+    pub sslmode: Option<String>,
+    /// CA certificate (PEM) used to verify the server
+    #[serde(default)]
+    pub sslrootcert: Option<String>,
+    /// Client certificate (PEM)
+    #[serde(default)]
+    pub sslcert: Option<String>,
+    /// Client private key (PEM, PKCS#8)
+    #[serde(default)]
+    pub sslkey: Option<String>,
     #[serde(default)]
     pub readonly: bool,
+    /// Marks a production database; enables `confirm_destructive` by default
+    #[serde(default)]
+    pub production: bool,
+    /// Ask before running DELETE/UPDATE without WHERE, DROP, TRUNCATE, ALTER ... DROP
+    #[serde(default)]
+    pub confirm_destructive: Option<bool>,
+    /// Record statements in the query history
+    #[serde(default)]
+    pub history: Option<bool>,
+    /// Statement timeout in seconds
+    #[serde(default)]
+    pub query_timeout: Option<u64>,
     #[serde(default)]
     pub group: Option<String>,
     /// Protocol for ClickHouse: "native" (default) or "http"
     #[serde(default)]
     pub protocol: Option<String>,
+    /// Local datacenter for Cassandra load balancing
+    #[serde(default)]
+    pub local_datacenter: Option<String>,
+    /// Rows per page for Cassandra queries
+    #[serde(default)]
+    pub page_size: Option<u32>,
+    /// SSH jump host; the connection is tunneled through it when set
+    #[serde(default)]
+    pub ssh_host: Option<String>,
+    #[serde(default)]
+    pub ssh_user: Option<String>,
+    #[serde(default)]
+    pub ssh_port: Option<u16>,
+    /// Private key for the SSH jump host
+    #[serde(default)]
+    pub ssh_identity: Option<String>,
 }
 
 impl ConnectionConfig {
-    pub fn to_database_conn(&self, name: &str) -> Option<DatabaseConn> {
+    pub fn to_database_conn(&self, name: &str) -> Result<DatabaseConn, String> {
         let db_type = match self.db_type.to_lowercase().as_str() {
             "postgres" | "postgresql" => DatabaseType::Postgres,
             "mysql" | "mariadb" => DatabaseType::MySql,
             "cassandra" | "scylla" => DatabaseType::Cassandra,
             "clickhouse" | "ch" => DatabaseType::ClickHouse,
             "sqlite" | "sqlite3" => DatabaseType::Sqlite,
-            _ => return None,
+            _ => return Err(format!("unknown type '{}'", self.db_type)),
+        };
+
+        let sslmode = match self.sslmode {
+            Some(ref mode) => {
+                Some(SslMode::parse(mode).ok_or_else(|| format!("invalid sslmode '{}'", mode))?)
+            }
+            None => None,
         };
 
-        Some(DatabaseConn {
+        if self.page_size == Some(0) {
+            return Err("page_size must be at least 1".to_string());
+        }
+
+        // Same rule as connection URLs, rather than dropping the options
+        if self.ssh_host.is_none()
+            && (self.ssh_user.is_some() || self.ssh_port.is_some() || self.ssh_identity.is_some())
+        {
+            return Err("SSH options require ssh_host".to_string());
+        }
+
+        let ssh = self.ssh_host.as_ref().map(|host| SshConfig {
+            host: host.clone(),
+            user: self.ssh_user.clone(),
+            port: self.ssh_port,
+            identity: self.ssh_identity.clone(),
+        });
+
+        // Show the first contact point when only `hosts` is set
+        let host = if self.host.is_empty() {
+            self.hosts.first().cloned().unwrap_or_default()
+        } else {
+            self.host.clone()
+        };
+
+        Ok(DatabaseConn {
             name: name.to_string(),
             db_type,
-            host: self.host.clone(),
+            host,
+            hosts: self.hosts.clone(),
             port: self.port,
             user: self.user.clone(),
             password: self.password.clone(),
             password_cmd: self.password_cmd.clone(),
             database: self.database.clone(),
             path: self.path.clone(),
+            socket: self.socket.clone(),
             tls: self.tls,
+            sslmode,
+            sslrootcert: self.sslrootcert.clone(),
+            sslcert: self.sslcert.clone(),
+            sslkey: self.sslkey.clone(),
             readonly: self.readonly,
+            confirm_destructive: self.confirm_destructive.unwrap_or(self.production),
+            history: self.history.unwrap_or(true),
+            query_timeout: self.query_timeout,
             group: self.group.clone(),
             protocol: self.protocol.clone(),
+            local_datacenter: self.local_datacenter.clone(),
+            page_size: self.page_size,
+            ssh,
         })
     }
 }
 
-pub fn load_config(custom_path: Option<PathBuf>) -> Vec<DatabaseConn> {
+pub fn load_config(custom_path: Option<PathBuf>) -> Result<Vec<DatabaseConn>, String> {
     use crate::debug_log;
 
     let config_path = custom_path.clone().or_else(get_config_path);
@@ -80,36 +172,31 @@ pub fn load_config(custom_path: Option<PathBuf>) -> Vec<DatabaseConn> {
             Ok(content) => match toml::from_str::<IndexMap<String, ConnectionConfig>>(&content) {
                 Ok(configs) => {
                     debug_log!("Parsed {} connection(s) from config", configs.len());
-                    let connections: Vec<DatabaseConn> = configs
+                    // A broken entry is reported rather than left out of the list
+                    let connections = configs
                         .iter()
-                        .filter_map(|(name, config)| {
-                            let conn = config.to_database_conn(name);
-                            if conn.is_none() {
-                                debug_log!(
-                                    "Skipping connection '{}': unknown type '{}'",
-                                    name,
-                                    config.db_type
-                                );
-                            }
-                            conn
+                        .map(|(name, config)| {
+                            config
+                                .to_database_conn(name)
+                                .map_err(|e| format!("connection '{}': {}", name, e))
                         })
-                        .collect();
+                        .collect::<Result<Vec<_>, _>>()?;
                     if connections.is_empty() {
-                        debug_log!("No valid connections found, using defaults");
-                        default_connections()
+                        debug_log!("No connections found, using defaults");
+                        Ok(default_connections())
                     } else {
                         debug_log!("Loaded {} connection(s)", connections.len());
-                        connections
+                        Ok(connections)
                     }
                 }
                 Err(e) => {
                     debug_log!("Failed to parse config file: {}", e);
-                    default_connections()
+                    Ok(default_connections())
                 }
             },
             Err(e) => {
                 debug_log!("Failed to read config file: {}", e);
-                default_connections()
+                Ok(default_connections())
             }
         },
         None => {
@@ -118,7 +205,7 @@ pub fn load_config(custom_path: Option<PathBuf>) -> Vec<DatabaseConn> {
                 let expected = config_dir.join("config.toml");
                 debug_log!("Expected config at: {:?}", expected);
             }
-            default_connections()
+            Ok(default_connections())
         }
     }
 }
@@ -151,23 +238,39 @@ fn default_connections() -> Vec<DatabaseConn> {
         name: "localhost".to_string(),
         db_type: DatabaseType::Postgres,
         host: "localhost".to_string(),
+        hosts: Vec::new(),
         port: 5432,
         user: "postgres".to_string(),
         password: None,
         password_cmd: None,
         database: None,
         path: None,
+        socket: None,
         tls: false,
+        sslmode: None,
+        sslrootcert: None,
+        sslcert: None,
+        sslkey: None,
         readonly: false,
+        confirm_destructive: false,
+        history: true,
+        query_timeout: None,
         group: None,
         protocol: None,
+        local_datacenter: None,
+        page_size: None,
+        ssh: None,
     }]
 }
 
 /// Parse a connection string URL into a DatabaseConn.
-/// Format: <type>://[user[:pass]@]host[:port][/database]
+/// Format: <type>://[user[:pass]@]host[:port][/database][?param=value&...]
 /// Types: pg, my, cs, ch, chh, sq (add 's' suffix for TLS: pgs, mys, css, chs, chhs)
 ///
+/// Parameters (network databases): sslmode, sslrootcert, sslcert, sslkey,
+/// local_datacenter and page_size (Cassandra), socket (MySQL), query_timeout (seconds),
+/// ssh_host, ssh_user, ssh_port, ssh_identity (tunnel through a jump host)
+///
 /// ClickHouse protocols:
 ///   ch  - Native TCP protocol (default, port 9000)
 ///   chh - HTTP API (port 8123)
@@ -182,7 +285,10 @@ fn default_connections() -> Vec<DatabaseConn> {
 ///   pg://postgres:secret@localhost:5432/mydb
 ///   pgs://postgres@secure.example.com/mydb  (with TLS)
 ///   my://root@localhost:3306
+///   my://root@/mydb?socket=/var/run/mysqld/mysqld.sock
+///   pg://app@db.internal/mydb?ssh_host=bastion.example.com&ssh_user=deploy
 ///   cs://user:pass@cassandra.example.com/keyspace
+///   pg://postgres@db.example.com/mydb?sslmode=verify-ca&sslrootcert=/etc/ssl/ca.pem
 ///   ch://default@localhost:9000/default     (Native protocol, default)
 ///   chh://default@localhost:8123/default    (HTTP API)
 ///   sq:///path/to/database.db
@@ -258,19 +364,37 @@ pub fn parse_connection_string(url: &str) -> Result<DatabaseConn, String> {
             name,
             db_type,
             host,
+            hosts: Vec::new(),
             port: 0,
             user: String::new(),
             password: None,
             password_cmd: None,
             database: None,
             path: Some(path),
+            socket: None,
             tls: false,
+            sslmode: None,
+            sslrootcert: None,
+            sslcert: None,
+            sslkey: None,
             readonly: false,
+            confirm_destructive: false,
+            history: true,
+            query_timeout: None,
             group: None,
             protocol: None,
+            local_datacenter: None,
+            page_size: None,
+            ssh: None,
         });
     }
 
+    // Split off query parameters (?sslmode=require&sslrootcert=/path/ca.pem)
+    let (rest, params) = match rest.split_once('?') {
+        Some((rest, params)) => (rest, Some(params)),
+        None => (rest, None),
+    };
+
     // Parse user:pass@host:port/database
     let (auth_host, database) = if let Some(idx) = rest.rfind('/') {
         let db = &rest[idx + 1..];
@@ -309,12 +433,20 @@ pub fn parse_connection_string(url: &str) -> Result<DatabaseConn, String> {
         (default_user.to_string(), None, auth_host)
     };
 
-    // Split host and port
-    let (host, port) = if let Some(colon_idx) = host_port.rfind(':') {
-        let h = &host_port[..colon_idx];
-        let p = &host_port[colon_idx + 1..];
+    // Split host and port; an IPv6 literal is bracketed, as in `[::1]:9042`
+    let (host_part, port_part) = match host_port.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((h, tail)) => (h, tail.strip_prefix(':')),
+            None => (host_port, None),
+        },
+        None => match host_port.rsplit_once(':') {
+            Some((h, p)) => (h, Some(p)),
+            None => (host_port, None),
+        },
+    };
+    let (host, port) = if let Some(p) = port_part {
         let port: u16 = p.parse().map_err(|_| format!("Invalid port: '{}'", p))?;
-        (h.to_string(), port)
+        (host_part.to_string(), port)
     } else {
         // Use default port for the database type
         let default_port = match (&db_type, &protocol) {
@@ -325,7 +457,7 @@ pub fn parse_connection_string(url: &str) -> Result<DatabaseConn, String> {
             (DatabaseType::ClickHouse, _) => 9000, // native is default
             (DatabaseType::Sqlite, _) => 0,
         };
-        (host_port.to_string(), default_port)
+        (host_part.to_string(), default_port)
     };
 
     // Use localhost if host is empty
@@ -342,27 +474,108 @@ pub fn parse_connection_string(url: &str) -> Result<DatabaseConn, String> {
         format!("{}@{}", user, host)
     };
 
-    Ok(DatabaseConn {
+    let mut conn = DatabaseConn {
         name,
         db_type,
         host,
+        hosts: Vec::new(),
         port,
         user,
         password,
         password_cmd: None,
         database,
         path: None,
+        socket: None,
         tls,
+        sslmode: None,
+        sslrootcert: None,
+        sslcert: None,
+        sslkey: None,
         readonly: false,
+        confirm_destructive: false,
+        history: true,
+        query_timeout: None,
         group: None,
         protocol,
-    })
+        local_datacenter: None,
+        page_size: None,
+        ssh: None,
+    };
+
+    if let Some(params) = params {
+        apply_url_params(&mut conn, params)?;
+    }
+
+    Ok(conn)
+}
+
+/// Apply `key=value&...` query parameters from a connection URL.
+fn apply_url_params(conn: &mut DatabaseConn, params: &str) -> Result<(), String> {
+    for pair in params.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid parameter: '{}'", pair))?;
+        match key {
+            "sslmode" => {
+                let mode =
+                    SslMode::parse(value).ok_or_else(|| format!("Invalid sslmode: '{}'", value))?;
+                conn.sslmode = Some(mode);
+            }
+            "sslrootcert" => conn.sslrootcert = Some(value.to_string()),
+            "sslcert" => conn.sslcert = Some(value.to_string()),
+            "sslkey" => conn.sslkey = Some(value.to_string()),
+            "local_datacenter" => conn.local_datacenter = Some(value.to_string()),
+            "query_timeout" => {
+                let secs: u64 = value
+                    .parse()
+                    .map_err(|_| format!("Invalid query_timeout: '{}'", value))?;
+                conn.query_timeout = Some(secs);
+            }
+            "page_size" => {
+                let rows: u32 = value
+                    .parse()
+                    .ok()
+                    .filter(|&rows| rows > 0)
+                    .ok_or_else(|| format!("Invalid page_size: '{}'", value))?;
+                conn.page_size = Some(rows);
+            }
+            "socket" if matches!(conn.db_type, DatabaseType::MySql) => {
+                conn.socket = Some(value.to_string())
+            }
+            "socket" => return Err("socket is only supported for MySQL".to_string()),
+            "ssh_host" => conn.ssh.get_or_insert_with(SshConfig::default).host = value.to_string(),
+            "ssh_user" => {
+                conn.ssh.get_or_insert_with(SshConfig::default).user = Some(value.to_string())
+            }
+            "ssh_port" => {
+                let port: u16 = value
+                    .parse()
+                    .map_err(|_| format!("Invalid ssh_port: '{}'", value))?;
+                conn.ssh.get_or_insert_with(SshConfig::default).port = Some(port);
+            }
+            "ssh_identity" => {
+                conn.ssh.get_or_insert_with(SshConfig::default).identity = Some(value.to_string())
+            }
+            _ => return Err(format!("Unknown parameter: '{}'", key)),
+        }
+    }
+    if conn.ssh.as_ref().is_some_and(|ssh| ssh.host.is_empty()) {
+        return Err("SSH options require ssh_host".to_string());
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// A PostgreSQL connection from the config file with `extra` keys
+    fn pg_config(extra: &str) -> Result<DatabaseConn, String> {
+        let config: ConnectionConfig =
+            toml::from_str(&format!("type = \"postgres\"\n{}", extra)).unwrap();
+        config.to_database_conn("pg")
+    }
+
     #[test]
     fn test_parse_postgres_full() {
         let conn = parse_connection_string("pg://myuser:secret@dbhost:5433/mydb").unwrap();
@@ -474,6 +687,181 @@ mod tests {
         assert!(conn.tls);
     }
 
+    #[test]
+    fn test_cassandra_contact_points() {
+        let config: ConnectionConfig = toml::from_str(
+            r#"
+            type = "cassandra"
+            hosts = ["scylla1.local", "scylla2.local:19042"]
+            port = 9042
+            local_datacenter = "dc1"
+            "#,
+        )
+        .unwrap();
+        let conn = config.to_database_conn("cluster").unwrap();
+        assert_eq!(conn.host, "scylla1.local");
+        assert_eq!(conn.local_datacenter, Some("dc1".to_string()));
+        assert_eq!(
+            conn.contact_points(),
+            vec!["scylla1.local:9042", "scylla2.local:19042"]
+        );
+
+        let conn = parse_connection_string("cs://cassandra.local/ks?local_datacenter=dc2").unwrap();
+        assert_eq!(conn.contact_points(), vec!["cassandra.local:9042"]);
+        assert_eq!(conn.local_datacenter, Some("dc2".to_string()));
+    }
+
+    #[test]
+    fn test_cassandra_contact_points_ipv6() {
+        let config: ConnectionConfig = toml::from_str(
+            r#"
+            type = "cassandra"
+            hosts = ["::1", "[fe80::1]", "[fe80::2]:19042", "10.0.0.1:19042"]
+            port = 9042
+            "#,
+        )
+        .unwrap();
+        let conn = config.to_database_conn("cluster").unwrap();
+        assert_eq!(
+            conn.contact_points(),
+            vec![
+                "[::1]:9042",
+                "[fe80::1]:9042",
+                "[fe80::2]:19042",
+                "10.0.0.1:19042"
+            ]
+        );
+
+        let conn = parse_connection_string("cs://[::1]/ks").unwrap();
+        assert_eq!(conn.host, "::1");
+        assert_eq!(conn.contact_points(), vec!["[::1]:9042"]);
+        let conn = parse_connection_string("cs://[fe80::1]:19042/ks").unwrap();
+        assert_eq!((conn.host.as_str(), conn.port), ("fe80::1", 19042));
+    }
+
+    #[test]
+    fn test_cassandra_page_size() {
+        let config: ConnectionConfig = toml::from_str(
+            r#"
+            type = "cassandra"
+            host = "cassandra.local"
+            page_size = 500
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.to_database_conn("cs").unwrap().page_size, Some(500));
+
+        let conn = parse_connection_string("cs://cassandra.local/ks?page_size=200").unwrap();
+        assert_eq!(conn.page_size, Some(200));
+        let result = parse_connection_string("cs://cassandra.local/ks?page_size=0");
+        assert!(result.unwrap_err().contains("Invalid page_size"));
+    }
+
+    #[test]
+    fn test_confirm_destructive_defaults_to_production() {
+        assert!(!pg_config("").unwrap().confirm_destructive);
+        assert!(pg_config("production = true").unwrap().confirm_destructive);
+        assert!(
+            !pg_config("production = true\nconfirm_destructive = false")
+                .unwrap()
+                .confirm_destructive
+        );
+        assert!(
+            pg_config("confirm_destructive = true")
+                .unwrap()
+                .confirm_destructive
+        );
+        assert!(
+            !parse_connection_string("pg://localhost/db")
+                .unwrap()
+                .confirm_destructive
+        );
+    }
+
+    #[test]
+    fn test_history_defaults_to_on() {
+        assert!(pg_config("").unwrap().history);
+        assert!(pg_config("production = true").unwrap().history);
+        assert!(!pg_config("history = false").unwrap().history);
+    }
+
+    #[test]
+    fn test_toml_ssh_options_require_ssh_host() {
+        let result = pg_config("ssh_user = \"deploy\"");
+        assert!(result.unwrap_err().contains("ssh_host"));
+    }
+
+    #[test]
+    fn test_parse_mysql_socket() {
+        let conn =
+            parse_connection_string("my://root@/mydb?socket=/var/run/mysqld/mysqld.sock").unwrap();
+        assert!(matches!(conn.db_type, DatabaseType::MySql));
+        assert_eq!(conn.socket, Some("/var/run/mysqld/mysqld.sock".to_string()));
+        assert_eq!(conn.database, Some("mydb".to_string()));
+        assert_eq!(conn.host, "localhost");
+
+        let result = parse_connection_string("pg://localhost/db?socket=/tmp/.s.PGSQL.5432");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_ssh_tunnel_params() {
+        let conn = parse_connection_string(
+            "pg://app@db.internal/mydb?ssh_host=bastion.example.com&ssh_user=deploy&ssh_port=2222",
+        )
+        .unwrap();
+        assert_eq!(conn.host, "db.internal");
+        assert_eq!(
+            conn.ssh,
+            Some(SshConfig {
+                host: "bastion.example.com".to_string(),
+                user: Some("deploy".to_string()),
+                port: Some(2222),
+                identity: None,
+            })
+        );
+
+        let result = parse_connection_string("pg://app@db.internal/mydb?ssh_user=deploy");
+        assert!(result.unwrap_err().contains("ssh_host"));
+    }
+
+    #[test]
+    fn test_parse_ssl_params() {
+        let conn = parse_connection_string(
+            "pg://postgres@db.example.com/mydb?sslmode=verify-ca&sslrootcert=/etc/ssl/ca.pem",
+        )
+        .unwrap();
+        assert_eq!(conn.host, "db.example.com");
+        assert_eq!(conn.database, Some("mydb".to_string()));
+        assert_eq!(conn.sslmode, Some(SslMode::VerifyCa));
+        assert_eq!(conn.sslrootcert, Some("/etc/ssl/ca.pem".to_string()));
+        assert_eq!(conn.tls_config().mode, SslMode::VerifyCa);
+    }
+
+    #[test]
+    fn test_parse_invalid_sslmode() {
+        let result = parse_connection_string("pg://localhost/db?sslmode=sometimes");
+        assert!(result.unwrap_err().contains("Invalid sslmode"));
+        let result = parse_connection_string("pg://localhost/db?foo=bar");
+        assert!(result.unwrap_err().contains("Unknown parameter"));
+    }
+
+    #[test]
+    fn test_parse_query_timeout() {
+        let conn = parse_connection_string("my://root@localhost/db?query_timeout=30").unwrap();
+        assert_eq!(conn.query_timeout, Some(30));
+        let result = parse_connection_string("my://root@localhost/db?query_timeout=soon");
+        assert!(result.unwrap_err().contains("Invalid query_timeout"));
+    }
+
+    #[test]
+    fn test_tls_flag_defaults_to_verify_full() {
+        let conn = parse_connection_string("pgs://postgres@secure.example.com/mydb").unwrap();
+        assert_eq!(conn.tls_config().mode, SslMode::VerifyFull);
+        let conn = parse_connection_string("pg://postgres@localhost/mydb").unwrap();
+        assert_eq!(conn.tls_config().mode, SslMode::Disable);
+    }
+
     #[test]
     fn test_parse_no_tls_by_default() {
         let conn = parse_connection_string("pg://postgres@localhost").unwrap();
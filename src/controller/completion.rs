@@ -1,5 +1,6 @@
 use super::{Controller, PopupState};
 use crate::completion::{self, CompletionContext, Suggestion};
+use crate::lexer::{self, Token};
 use crossterm::event::{KeyCode, KeyEvent};
 use tui_textarea::CursorMove;
 
@@ -21,21 +22,43 @@ impl Controller {
 
         // Get current word and context
         let (current_word, word_start) = completion::get_current_word(&query, cursor_pos);
-        let context = completion::detect_context(&query, cursor_pos);
+        let dialect = self.current_tab().dialect();
+        let context = completion::detect_context(&query, cursor_pos, dialect);
 
-        // Get tables from sidebar
-        let tables: Vec<String> = self.current_tab().sidebar.tables.clone();
+        // Get tables and views from sidebar
+        let tables: Vec<String> = self.current_tab().sidebar.tables();
 
-        // Get columns if needed for column context
-        let columns: Vec<String> = if let CompletionContext::Column { ref table_or_alias } = context
-        {
-            self.get_columns_for_completion(table_or_alias, &query)
-        } else {
-            Vec::new()
+        // "schema." completes the tables of that schema
+        let schema_prefix = match context {
+            CompletionContext::Column { ref table_or_alias } => {
+                format!("{}.", table_or_alias.to_lowercase())
+            }
+            _ => String::new(),
         };
+        let schema_tables: Vec<String> = tables
+            .iter()
+            .filter(|t| !schema_prefix.is_empty() && t.to_lowercase().starts_with(&schema_prefix))
+            .map(|t| t[schema_prefix.len()..].to_string())
+            .collect();
 
         // Get suggestions
-        let suggestions = completion::get_suggestions(&context, &current_word, &tables, &columns);
+        let suggestions = if !schema_tables.is_empty() {
+            completion::get_suggestions(
+                &CompletionContext::Table,
+                &current_word,
+                &schema_tables,
+                &[],
+            )
+        } else {
+            // Get columns if needed for column context
+            let columns: Vec<String> =
+                if let CompletionContext::Column { ref table_or_alias } = context {
+                    self.get_columns_for_completion(table_or_alias, &query)
+                } else {
+                    Vec::new()
+                };
+            completion::get_suggestions(&context, &current_word, &tables, &columns)
+        };
 
         if suggestions.is_empty() {
             self.current_tab_mut().status_message = Some("No completions available".to_string());
@@ -62,6 +85,7 @@ impl Controller {
         }
 
         // Try to fetch columns from database
+        let released = self.release_session();
         let tab = self.current_tab();
         let db_client = match &tab.db_client {
             Some(client) => client.clone(),
@@ -70,6 +94,7 @@ impl Controller {
         let current_db = tab.current_database.clone();
 
         let result = self.runtime.block_on(async {
+            released.wait().await;
             db_client
                 .list_columns(&table_name, current_db.as_deref())
                 .await
@@ -78,10 +103,11 @@ impl Controller {
         match result {
             Ok(columns) => {
                 // Cache the columns
+                let names: Vec<String> = columns.into_iter().map(|c| c.name).collect();
                 self.current_tab_mut()
                     .column_cache
-                    .insert(table_name, columns.clone());
-                columns
+                    .insert(table_name, names.clone());
+                names
             }
             Err(_) => Vec::new(),
         }
@@ -89,21 +115,46 @@ impl Controller {
 
     /// Try to resolve a table alias to the actual table name by parsing the query
     fn resolve_table_alias(&self, alias: &str, query: &str) -> Option<String> {
-        let query_lower = query.to_lowercase();
         let alias_lower = alias.to_lowercase();
+        let tab = self.current_tab();
+        let tables = tab.sidebar.tables();
+        let tokens: Vec<Token> = lexer::tokenize(query, tab.dialect())
+            .into_iter()
+            .filter(|t| !t.is_trivia())
+            .collect();
 
-        // Look for patterns like "FROM table_name alias" or "FROM table_name AS alias"
-        // or "JOIN table_name alias" or "JOIN table_name AS alias"
-        let tables = &self.current_tab().sidebar.tables;
-
-        for table in tables {
-            let table_lower = table.to_lowercase();
-
-            // Pattern: "table alias" or "table AS alias"
-            let pattern1 = format!("{} {}", table_lower, alias_lower);
-            let pattern2 = format!("{} as {}", table_lower, alias_lower);
+        // Look for "table alias" or "table AS alias", where the table may be
+        // schema-qualified or referenced without the schema
+        for (i, token) in tokens.iter().enumerate() {
+            if token
+                .ident()
+                .is_none_or(|a| a.to_lowercase() != alias_lower)
+            {
+                continue;
+            }
+            let end = if i > 0 && tokens[i - 1].is_keyword("AS") {
+                i - 1
+            } else {
+                i
+            };
+            let Some(name) = end.checked_sub(1).and_then(|j| tokens[j].ident()) else {
+                continue;
+            };
+            let schema = end
+                .checked_sub(3)
+                .filter(|&j| tokens[j + 1].text == ".")
+                .and_then(|j| tokens[j].ident());
+            let name_lower = match schema {
+                Some(schema) => format!("{}.{}", schema, name).to_lowercase(),
+                None => name.to_lowercase(),
+            };
 
-            if query_lower.contains(&pattern1) || query_lower.contains(&pattern2) {
+            let found = tables.iter().find(|table| {
+                let table_lower = table.to_lowercase();
+                table_lower == name_lower
+                    || table_lower.rsplit('.').next() == Some(name_lower.as_str())
+            });
+            if let Some(table) = found {
                 return Some(table.clone());
             }
         }
@@ -203,12 +254,8 @@ impl Controller {
 
         // word_start is global position, convert to position in current line
         let line_start = pos;
-        let word_start_in_line = if word_start >= line_start {
-            word_start - line_start
-        } else {
-            // Word spans multiple lines? Shouldn't happen, but handle it
-            0
-        };
+        // Word spans multiple lines? Shouldn't happen, but clamp to line start
+        let word_start_in_line = word_start.saturating_sub(line_start);
 
         // Select from word start to current position and replace
         // Move cursor to word start
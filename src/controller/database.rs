@@ -1,12 +1,36 @@
-use super::{Controller, DatabaseType, Focus, PendingOperation};
-use crate::db::{DatabaseClient, QueryResult};
+use super::{
+    Controller, DatabaseType, Focus, PendingOperation, PopupState, RunResult, SidebarRow,
+    StatementResult,
+};
+use crate::db::{DatabaseClient, DbObject, PagedResult};
 use crate::debug_log;
 use crate::error::SqliError;
+use crate::explain::{self, ExplainMode, Plan};
+use crate::lexer::{self, Dialect};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
 
 const CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait for the server to acknowledge a cancel request
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Extra time given to a server-enforced query timeout before cancelling locally
+const QUERY_TIMEOUT_GRACE: Duration = Duration::from_secs(2);
+
+/// A result stream being closed in the background by `release_session`
+#[must_use]
+pub(super) struct SessionRelease(Option<JoinHandle<()>>);
+
+impl SessionRelease {
+    /// Wait until the closed stream has given the connection back
+    pub(super) async fn wait(self) {
+        if let Some(task) = self.0 {
+            let _ = task.await;
+        }
+    }
+}
 
 impl Controller {
     /// Step 1: User selects a connection - either connect directly (if database configured)
@@ -64,15 +88,17 @@ impl Controller {
         let include_system = tab.show_system_databases;
         let conn_name = conn.name.clone();
 
+        let tunnel = self.current_tab().ssh_tunnel.clone();
+
         let (tx, rx) = oneshot::channel();
         self.runtime.spawn(async move {
             let db_name = conn.db_type.default_database().to_string();
             let result = tokio::time::timeout(CONNECTION_TIMEOUT, async {
-                let (client, pwd_warning) = conn.create_client(&db_name).await?;
+                let (client, tunnel, pwd_warning) = conn.create_client(&db_name, tunnel).await?;
                 if let Some(warn) = pwd_warning {
                     crate::debug_log!("Password warning for {}: {}", conn.name, warn);
                 }
-                client.list_databases(include_system).await
+                Ok((client.list_databases(include_system).await?, tunnel))
             })
             .await
             .unwrap_or_else(|_| Err(SqliError::Connection("Connection timed out".to_string())));
@@ -111,15 +137,17 @@ impl Controller {
         let include_system = tab.show_system_databases;
         let conn_name = conn.name.clone();
 
+        let tunnel = self.current_tab().ssh_tunnel.clone();
+
         let (tx, rx) = oneshot::channel();
         self.runtime.spawn(async move {
             let db_name = conn.db_type.default_database().to_string();
             let result = tokio::time::timeout(CONNECTION_TIMEOUT, async {
-                let (client, pwd_warning) = conn.create_client(&db_name).await?;
+                let (client, tunnel, pwd_warning) = conn.create_client(&db_name, tunnel).await?;
                 if let Some(warn) = pwd_warning {
                     crate::debug_log!("Password warning for {}: {}", conn.name, warn);
                 }
-                client.list_databases(include_system).await
+                Ok((client.list_databases(include_system).await?, tunnel))
             })
             .await
             .unwrap_or_else(|_| Err(SqliError::Connection("Connection timed out".to_string())));
@@ -145,15 +173,17 @@ impl Controller {
         let include_system = tab.show_system_databases;
         let conn_name = conn.name.clone();
 
+        let tunnel = self.current_tab().ssh_tunnel.clone();
+
         let (tx, rx) = oneshot::channel();
         self.runtime.spawn(async move {
             let db_name = conn.db_type.default_database().to_string();
             let result = tokio::time::timeout(CONNECTION_TIMEOUT, async {
-                let (client, pwd_warning) = conn.create_client(&db_name).await?;
+                let (client, tunnel, pwd_warning) = conn.create_client(&db_name, tunnel).await?;
                 if let Some(warn) = pwd_warning {
                     crate::debug_log!("Password warning for {}: {}", conn.name, warn);
                 }
-                client.list_databases(include_system).await
+                Ok((client.list_databases(include_system).await?, tunnel))
             })
             .await
             .unwrap_or_else(|_| Err(SqliError::Connection("Connection timed out".to_string())));
@@ -199,22 +229,22 @@ impl Controller {
         let conn_name = conn.name.clone();
         let db_name_clone = db_name.clone();
 
-        // Determine connection database and schema for listing tables
         let connect_db = db_name.clone();
-        let schema = match conn.db_type {
-            DatabaseType::Postgres => "public".to_string(),
-            _ => db_name.clone(),
-        };
+        // Go through the tunnel the database list was fetched with
+        let tunnel = tab.ssh_tunnel.clone();
 
         let (tx, rx) = oneshot::channel();
         self.runtime.spawn(async move {
             let result = tokio::time::timeout(CONNECTION_TIMEOUT, async {
-                let (client, pwd_warning) = conn.create_client(&connect_db).await?;
+                let (client, tunnel, pwd_warning) = conn.create_client(&connect_db, tunnel).await?;
                 if let Some(warn) = pwd_warning {
                     crate::debug_log!("Password warning for {}: {}", conn.name, warn);
                 }
-                let tables = client.list_tables(&schema).await?;
-                Ok((client, tables))
+                client
+                    .set_query_timeout(conn.query_timeout.map(Duration::from_secs))
+                    .await?;
+                let objects = client.list_objects(&connect_db).await?;
+                Ok((client, tunnel, objects))
             })
             .await
             .unwrap_or_else(|_| Err(SqliError::Connection("Connection timed out".to_string())));
@@ -228,119 +258,217 @@ impl Controller {
         });
     }
 
-    pub(super) fn execute_query(&mut self) {
-        let raw_query: String = self.current_tab().query_textarea.lines().join("\n");
-        let query = Self::strip_comments(&raw_query);
+    /// Run everything in the editor
+    pub(super) fn execute_all(&mut self) {
+        let tab = self.current_tab_mut();
+        let lines = tab.query_textarea.lines();
+        let end = (
+            lines.len() - 1,
+            lines.last().map_or(0, |l| l.chars().count()),
+        );
+        let raw_query = lines.join("\n");
+        tab.highlight_run((0, 0), end);
+
+        let query = lexer::strip_comments(&raw_query, self.current_tab().dialect());
         self.run_query(&query);
     }
 
-    /// Strip SQL comments while respecting string literals.
-    /// Handles:
-    /// - Single-line comments (-- ...)
-    /// - Multi-line comments (/* ... */)
-    /// - String literals ('...' and "...")
-    fn strip_comments(query: &str) -> String {
-        let mut result = String::with_capacity(query.len());
-        let mut chars = query.chars().peekable();
-        let mut in_single_quote = false;
-        let mut in_double_quote = false;
-
-        while let Some(ch) = chars.next() {
-            // Handle string literals (SQL escapes quotes by doubling: '' or "")
-            if ch == '\'' && !in_double_quote {
-                if in_single_quote && chars.peek() == Some(&'\'') {
-                    // Escaped quote inside string: '' — push both and stay in string
-                    result.push(ch);
-                    result.push(chars.next().unwrap());
-                    continue;
+    /// Run the selected text, or the statement under the cursor
+    pub(super) fn execute_at_cursor(&mut self) {
+        match self.query_at_cursor() {
+            Some((query, true)) => self.run_query(&query),
+            // Run it as one statement: splitting it again would lose a custom DELIMITER
+            Some((statement, false)) => self.run_statements(vec![statement], true),
+            None => {}
+        }
+    }
+
+    /// The selected text, or else the statement under the cursor, which is
+    /// highlighted. Comments are stripped; the flag is set for a selection.
+    fn query_at_cursor(&mut self) -> Option<(String, bool)> {
+        let tab = self.current_tab_mut();
+        tab.clear_run_highlight();
+        let dialect = tab.dialect();
+        let lines = tab.query_textarea.lines();
+        let text = lines.join("\n");
+
+        if let Some((start, end)) = tab.query_textarea.selection_range()
+            && start != end
+        {
+            let selected = &text[text_offset(lines, start)..text_offset(lines, end)];
+            return Some((lexer::strip_comments(selected, dialect), true));
+        }
+
+        let cursor = text_offset(lines, tab.query_textarea.cursor());
+        let ranges = lexer::statement_ranges(&text, dialect);
+        // Between statements the cursor belongs to the one before it
+        let range = ranges
+            .iter()
+            .rev()
+            .find(|r| r.start <= cursor)
+            .or(ranges.first())
+            .cloned()?;
+        tab.highlight_run(
+            text_position(&text, range.start),
+            text_position(&text, range.end),
+        );
+
+        let statement = lexer::strip_comments(&text[range], dialect);
+        Some((statement.trim().to_string(), false))
+    }
+
+    /// Show the plan of the selected statement or the one under the cursor.
+    /// `arg` is empty for the estimated plan, `analyze` or `pipeline`.
+    pub(super) fn explain(&mut self, arg: &str) {
+        let Some(mode) = ExplainMode::parse(arg) else {
+            self.current_tab_mut().status_message = Some(format!(
+                "Unknown plan: '{}' (analyze or pipeline)",
+                arg.trim()
+            ));
+            return;
+        };
+        let tab = self.current_tab();
+        let (Some(conn), Some(client)) = (
+            tab.connections.get(tab.connected_index).cloned(),
+            tab.db_client.as_ref().map(Arc::clone),
+        ) else {
+            self.current_tab_mut().status_message = Some("Not connected".to_string());
+            return;
+        };
+        let dialect = tab.dialect();
+
+        let statement = match self.query_at_cursor() {
+            Some((query, true)) => {
+                let mut statements = lexer::split_statements(&query, dialect);
+                if statements.len() != 1 {
+                    self.current_tab_mut().status_message =
+                        Some("Select a single statement to explain".to_string());
+                    return;
                 }
-                in_single_quote = !in_single_quote;
-                result.push(ch);
-                continue;
+                statements.remove(0)
             }
-            if ch == '"' && !in_single_quote {
-                if in_double_quote && chars.peek() == Some(&'"') {
-                    // Escaped quote inside identifier: "" — push both and stay in string
-                    result.push(ch);
-                    result.push(chars.next().unwrap());
-                    continue;
-                }
-                in_double_quote = !in_double_quote;
-                result.push(ch);
-                continue;
+            Some((statement, false)) => statement,
+            None => return,
+        };
+
+        // ANALYZE runs the statement. PostgreSQL undoes it afterwards, MySQL
+        // cannot, so there it is limited to queries.
+        if mode == ExplainMode::Analyze && !Self::is_read_query(&statement, dialect) {
+            let refusal = if conn.readonly {
+                Some("Connection is read-only, only select queries allowed")
+            } else if matches!(conn.db_type, DatabaseType::MySql) {
+                Some("EXPLAIN ANALYZE would change data on MySQL, only select queries allowed")
+            } else {
+                None
+            };
+            if let Some(refusal) = refusal {
+                self.current_tab_mut().status_message = Some(refusal.to_string());
+                return;
             }
+        }
+        // PostgreSQL runs it inside a transaction or savepoint rolled back after
+        let undone = matches!(conn.db_type, DatabaseType::Postgres) && mode == ExplainMode::Analyze;
+
+        let query = match explain::explain_query(&conn.db_type, mode, &statement) {
+            Ok(query) => query,
+            Err(e) => {
+                self.current_tab_mut().status_message = Some(e.to_string());
+                return;
+            }
+        };
+        debug_log!("Explaining: {}", query.replace('\n', " "));
 
-            // Skip comments only when not in a string
-            if !in_single_quote && !in_double_quote {
-                // Single-line comment: --
-                if ch == '-' && chars.peek() == Some(&'-') {
-                    // Skip until end of line
-                    for c in chars.by_ref() {
-                        if c == '\n' {
-                            result.push('\n'); // Preserve line structure
-                            break;
-                        }
+        let released = self.release_session();
+        let tab = self.current_tab_mut();
+        tab.loading = true;
+        tab.status_message = Some("Explaining...".to_string());
+        let timeout = tab.query_timeout;
+
+        let (tx, rx) = oneshot::channel();
+        self.runtime.spawn(async move {
+            released.wait().await;
+            let undo = if undone {
+                match client.in_transaction_block().await {
+                    Ok(in_block) => Some(explain_undo(in_block)),
+                    Err(e) => {
+                        let _ = tx.send(Err(e));
+                        return;
                     }
-                    continue;
                 }
-                // Multi-line comment: /* ... */
-                if ch == '/' && chars.peek() == Some(&'*') {
-                    chars.next(); // consume '*'
-                    // Skip until */
-                    let mut found_end = false;
-                    while let Some(c) = chars.next() {
-                        if c == '*' && chars.peek() == Some(&'/') {
-                            chars.next(); // consume '/'
-                            found_end = true;
-                            break;
-                        }
-                        // Preserve newlines for line counting
-                        if c == '\n' {
-                            result.push('\n');
-                        }
-                    }
-                    if !found_end {
-                        // Unclosed comment - just skip to end
+            } else {
+                None
+            };
+            if let Some((open, _)) = undo
+                && let Err(e) = client.execute_query(open).await
+            {
+                let _ = tx.send(Err(e));
+                return;
+            }
+            let run = async {
+                client
+                    .execute_query(&query)
+                    .await
+                    .map(|result| (result, None))
+            };
+            // The timeout only covers the EXPLAIN, so the rollback always runs
+            let mut result = match timeout {
+                Some(limit) => Self::execute_with_timeout(&client, limit, run).await,
+                None => run.await,
+            };
+            if let Some((_, close)) = undo {
+                for statement in close {
+                    let closed = client.execute_query(statement).await;
+                    if let Err(e) = closed
+                        && result.is_ok()
+                    {
+                        result = Err(e);
                     }
-                    continue;
                 }
             }
+            let plan = result
+                .and_then(|(result, _)| explain::parse_plan(&conn.db_type, mode, &result))
+                .map(|nodes| Plan::new(mode.title(), nodes));
+            let _ = tx.send(plan);
+        });
 
-            result.push(ch);
-        }
+        self.current_tab_mut().pending_operation = Some(PendingOperation::Explain {
+            receiver: rx,
+            cancelled: false,
+        });
+    }
 
-        result
+    /// Ask the server to stop the statement behind a cancelled `:explain analyze`.
+    /// The tab stays busy until the explain has finished and been rolled back.
+    pub(super) fn cancel_explain(&mut self) {
+        let Some(client) = self.current_tab().db_client.clone() else {
+            return;
+        };
+        self.runtime.spawn(async move {
+            let _ = tokio::time::timeout(CANCEL_TIMEOUT, client.cancel_query()).await;
+        });
     }
 
     pub(super) fn refresh_tables(&mut self) {
+        let released = self.release_session();
         let tab = self.current_tab();
         let client = match &tab.db_client {
             Some(c) => Arc::clone(c),
             None => return,
         };
 
-        let conn = match tab.connections.get(tab.connected_index) {
-            Some(c) => c.clone(),
-            None => return,
-        };
-
         let db_name = match &tab.current_database {
             Some(db) => db.clone(),
             None => return,
         };
 
-        let schema = match conn.db_type {
-            DatabaseType::Postgres => "public".to_string(),
-            _ => db_name.clone(),
-        };
-
         let tab = self.current_tab_mut();
         tab.loading = true;
         tab.status_message = Some("Refreshing...".to_string());
 
         let (tx, rx) = oneshot::channel();
         self.runtime.spawn(async move {
-            let result = tokio::time::timeout(CONNECTION_TIMEOUT, client.list_tables(&schema))
+            released.wait().await;
+            let result = tokio::time::timeout(CONNECTION_TIMEOUT, client.list_objects(&db_name))
                 .await
                 .unwrap_or_else(|_| Err(SqliError::Query("Refresh timed out".to_string())));
             let _ = tx.send(result);
@@ -350,91 +478,356 @@ impl Controller {
             Some(PendingOperation::RefreshTables { receiver: rx });
     }
 
+    /// The current tab's client, ready for a command on its session once the
+    /// returned release has been waited for.
+    /// Returns `None` with a status message while another operation is running.
+    fn session_client(&mut self) -> Option<(Arc<DatabaseClient>, SessionRelease)> {
+        let tab = self.current_tab();
+        let Some(client) = tab.db_client.as_ref().map(Arc::clone) else {
+            self.current_tab_mut().status_message = Some("Not connected".to_string());
+            return None;
+        };
+        if tab.pending_operation.is_some() {
+            self.current_tab_mut().status_message =
+                Some("Wait for the running operation to finish".to_string());
+            return None;
+        }
+        Some((client, self.release_session()))
+    }
+
+    /// Set the PostgreSQL search_path, or show it when no schemas are given.
+    pub(super) fn set_schema(&mut self, schemas: &str) {
+        let schemas: Vec<String> = schemas
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect();
+        if schemas.is_empty() {
+            self.run_query("SHOW search_path");
+            return;
+        }
+
+        let Some((client, released)) = self.session_client() else {
+            return;
+        };
+
+        let tab = self.current_tab_mut();
+        tab.loading = true;
+        tab.status_message = Some("Setting search_path...".to_string());
+
+        let search_path = schemas.join(", ");
+        let (tx, rx) = oneshot::channel();
+        self.runtime.spawn(async move {
+            released.wait().await;
+            let schemas: Vec<&str> = schemas.iter().map(String::as_str).collect();
+            let result = tokio::time::timeout(CONNECTION_TIMEOUT, client.set_search_path(&schemas))
+                .await
+                .unwrap_or_else(|_| Err(SqliError::Query("Timed out".to_string())));
+            let _ = tx.send(result);
+        });
+
+        self.current_tab_mut().pending_operation = Some(PendingOperation::SetSchema {
+            receiver: rx,
+            search_path,
+        });
+    }
+
+    /// Set the statement timeout for this tab, or show it when no argument is given.
+    /// Accepts a number of seconds, or `off`/`0` to disable it.
+    pub(super) fn set_query_timeout(&mut self, arg: &str) {
+        let arg = arg.trim();
+        if arg.is_empty() {
+            let message = match self.current_tab().query_timeout {
+                Some(t) => format!("Query timeout is {}s", t.as_secs()),
+                None => "Query timeout is off".to_string(),
+            };
+            self.current_tab_mut().status_message = Some(message);
+            return;
+        }
+
+        let timeout = match arg {
+            "off" | "0" => None,
+            secs => match secs.parse::<u64>() {
+                Ok(secs) => Some(Duration::from_secs(secs)),
+                Err(_) => {
+                    self.current_tab_mut().status_message =
+                        Some(format!("Invalid timeout: '{}'", arg));
+                    return;
+                }
+            },
+        };
+
+        let Some((client, released)) = self.session_client() else {
+            return;
+        };
+
+        let tab = self.current_tab_mut();
+        tab.loading = true;
+        tab.status_message = Some("Setting query timeout...".to_string());
+
+        let (tx, rx) = oneshot::channel();
+        self.runtime.spawn(async move {
+            released.wait().await;
+            let result =
+                tokio::time::timeout(CONNECTION_TIMEOUT, client.set_query_timeout(timeout))
+                    .await
+                    .unwrap_or_else(|_| Err(SqliError::Query("Timed out".to_string())));
+            let _ = tx.send(result);
+        });
+
+        self.current_tab_mut().pending_operation = Some(PendingOperation::SetQueryTimeout {
+            receiver: rx,
+            timeout,
+        });
+    }
+
+    /// Switch this tab between autocommit and manual commit, or show the mode
+    /// when no argument is given. Manual commit keeps a transaction open,
+    /// which `:commit` and `:rollback` end before opening the next one.
+    pub(super) fn set_autocommit(&mut self, arg: &str) {
+        let tab = self.current_tab();
+        let manual = match arg.trim() {
+            "" => {
+                let message = match tab.transaction {
+                    Some(pending) => format!("Manual commit, {} pending statement(s)", pending),
+                    None => "Autocommit is on".to_string(),
+                };
+                self.current_tab_mut().status_message = Some(message);
+                return;
+            }
+            "off" => true,
+            "on" => false,
+            other => {
+                self.current_tab_mut().status_message = Some(format!(
+                    "Invalid autocommit setting: '{}' (on or off)",
+                    other
+                ));
+                return;
+            }
+        };
+        let message = match (tab.transaction, manual) {
+            (Some(_), true) => Some("Already in manual commit mode".to_string()),
+            (None, false) => Some("Autocommit is already on".to_string()),
+            (Some(pending), false) if pending > 0 => Some(format!(
+                "Commit or roll back the {} pending statement(s) first",
+                pending
+            )),
+            _ => None,
+        };
+        if let Some(message) = message {
+            self.current_tab_mut().status_message = Some(message);
+            return;
+        }
+        let Some((client, released)) = self.session_client() else {
+            return;
+        };
+
+        let tab = self.current_tab_mut();
+        tab.loading = true;
+        tab.status_message = Some("Setting autocommit...".to_string());
+
+        // Leaving manual commit rolls back the empty transaction left open
+        let (tx, rx) = oneshot::channel();
+        self.runtime.spawn(async move {
+            released.wait().await;
+            let result = tokio::time::timeout(CONNECTION_TIMEOUT, async {
+                if manual {
+                    client.begin().await
+                } else {
+                    client.rollback().await
+                }
+            })
+            .await
+            .unwrap_or_else(|_| Err(SqliError::Query("Timed out".to_string())));
+            let _ = tx.send(result);
+        });
+
+        self.current_tab_mut().pending_operation = Some(PendingOperation::SetAutocommit {
+            receiver: rx,
+            manual,
+        });
+    }
+
+    /// Commit or roll back the open transaction and open the next one
+    pub(super) fn end_transaction(&mut self, commit: bool) {
+        let Some(pending) = self.current_tab().transaction else {
+            self.current_tab_mut().status_message =
+                Some("No transaction is open (:autocommit off starts one)".to_string());
+            return;
+        };
+        let Some((client, released)) = self.session_client() else {
+            return;
+        };
+
+        let tab = self.current_tab_mut();
+        tab.loading = true;
+        tab.status_message = Some(if commit {
+            "Committing...".to_string()
+        } else {
+            "Rolling back...".to_string()
+        });
+
+        let (tx, rx) = oneshot::channel();
+        self.runtime.spawn(async move {
+            released.wait().await;
+            let result = tokio::time::timeout(CONNECTION_TIMEOUT, async {
+                if commit {
+                    client.commit().await
+                } else {
+                    client.rollback().await
+                }
+            })
+            .await
+            .unwrap_or_else(|_| Err(SqliError::Query("Timed out".to_string())));
+
+            // A failed commit leaves the transaction open to retry or roll back
+            let reopened = if commit && result.is_err() {
+                None
+            } else {
+                Some(
+                    tokio::time::timeout(CONNECTION_TIMEOUT, client.begin())
+                        .await
+                        .unwrap_or_else(|_| Err(SqliError::Query("Timed out".to_string()))),
+                )
+            };
+            let _ = tx.send((result, reopened));
+        });
+
+        self.current_tab_mut().pending_operation = Some(PendingOperation::EndTransaction {
+            receiver: rx,
+            commit,
+            pending,
+        });
+    }
+
+    /// Enter in the sidebar: toggle a group or a table's columns,
+    /// or preview objects that have no columns
+    pub(super) fn sidebar_activate(&mut self) {
+        match self.current_tab().sidebar.selected_row() {
+            Some(SidebarRow::Group { kind, .. }) => {
+                self.current_tab_mut().sidebar.toggle_group(kind);
+            }
+            Some(SidebarRow::Object(idx)) => {
+                let sidebar = &self.current_tab().sidebar;
+                let object = &sidebar.objects[idx];
+                if !object.kind.is_relation() {
+                    self.select_table();
+                } else if sidebar.columns.contains_key(&object.name) {
+                    self.current_tab_mut().sidebar.collapse_table(idx);
+                } else {
+                    self.expand_table(idx);
+                }
+            }
+            Some(SidebarRow::Column(..)) | None => {}
+        }
+    }
+
+    /// Expand the selected group or table in place.
+    /// Returns false if there is nothing to expand.
+    pub(super) fn sidebar_expand(&mut self) -> bool {
+        let sidebar = &self.current_tab().sidebar;
+        match sidebar.selected_row() {
+            Some(SidebarRow::Group {
+                kind,
+                expanded: false,
+                ..
+            }) => {
+                self.current_tab_mut().sidebar.toggle_group(kind);
+                true
+            }
+            Some(SidebarRow::Object(idx))
+                if sidebar.objects[idx].kind.is_relation()
+                    && !sidebar.columns.contains_key(&sidebar.objects[idx].name) =>
+            {
+                self.expand_table(idx);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Load a table's columns into the sidebar and the completion cache
+    fn expand_table(&mut self, idx: usize) {
+        let Some(table) = self
+            .current_tab()
+            .sidebar
+            .objects
+            .get(idx)
+            .map(|o| o.name.clone())
+        else {
+            return;
+        };
+        let Some((client, released)) = self.session_client() else {
+            return;
+        };
+        let tab = self.current_tab_mut();
+        let db_name = tab.current_database.clone();
+        tab.loading = true;
+        tab.status_message = Some(format!("Loading columns of {}...", table));
+
+        let (tx, rx) = oneshot::channel();
+        let name = table.clone();
+        self.runtime.spawn(async move {
+            released.wait().await;
+            let result = tokio::time::timeout(
+                CONNECTION_TIMEOUT,
+                client.list_columns(&name, db_name.as_deref()),
+            )
+            .await
+            .unwrap_or_else(|_| Err(SqliError::Query("Timed out".to_string())));
+            let _ = tx.send(result);
+        });
+
+        self.current_tab_mut().pending_operation = Some(PendingOperation::ExpandTable {
+            receiver: rx,
+            table,
+        });
+    }
+
+    /// Preview the selected object: rows for relations, the definition otherwise
     pub(super) fn select_table(&mut self) {
-        if let Some(query) = self.table_query(|c, t, db| c.select_table_query(t, 50, Some(db))) {
+        if let Some(query) = self.object_query(|c, o, db| c.preview_query(o, 50, Some(db))) {
             self.run_query(&query);
             self.current_tab_mut().focus = Focus::Output;
         }
     }
 
     pub(super) fn describe_table(&mut self) {
-        if let Some(query) = self.table_query(|c, t, db| c.describe_table_query(t, Some(db))) {
+        if let Some(query) = self.object_query(|c, o, db| c.describe_query(o, Some(db))) {
             self.run_query(&query);
             self.current_tab_mut().focus = Focus::Output;
         }
     }
 
-    /// Helper to generate a query for the currently selected table
-    fn table_query<F>(&self, f: F) -> Option<String>
+    /// Helper to generate a query for the currently selected object
+    fn object_query<F>(&self, f: F) -> Option<String>
     where
-        F: FnOnce(&crate::db::DatabaseClient, &str, &str) -> String,
+        F: FnOnce(&DatabaseClient, &DbObject, &str) -> String,
     {
         let tab = self.current_tab();
-        let table = tab.sidebar.tables.get(tab.sidebar.selected)?;
+        let object = tab.sidebar.selected_object()?;
         let db_name = tab.current_database.as_ref()?;
         let client = tab.db_client.as_ref()?;
-        Some(f(client, table, db_name))
-    }
-
-    /// Split query text into individual statements by semicolon.
-    /// Handles semicolons inside string literals (both single and double quotes).
-    fn split_queries(query: &str) -> Vec<String> {
-        let mut statements = Vec::new();
-        let mut current = String::new();
-        let mut in_single_quote = false;
-        let mut in_double_quote = false;
-        let mut chars = query.chars().peekable();
-
-        while let Some(ch) = chars.next() {
-            match ch {
-                '\'' if !in_double_quote => {
-                    if in_single_quote && chars.peek() == Some(&'\'') {
-                        // Escaped quote: '' — push both and stay in string
-                        current.push(ch);
-                        current.push(chars.next().unwrap());
-                    } else {
-                        in_single_quote = !in_single_quote;
-                        current.push(ch);
-                    }
-                }
-                '"' if !in_single_quote => {
-                    if in_double_quote && chars.peek() == Some(&'"') {
-                        // Escaped quote: "" — push both and stay in identifier
-                        current.push(ch);
-                        current.push(chars.next().unwrap());
-                    } else {
-                        in_double_quote = !in_double_quote;
-                        current.push(ch);
-                    }
-                }
-                ';' if !in_single_quote && !in_double_quote => {
-                    let trimmed = current.trim().to_string();
-                    if !trimmed.is_empty() {
-                        statements.push(trimmed);
-                    }
-                    current.clear();
-                }
-                _ => {
-                    current.push(ch);
-                }
-            }
-        }
-
-        // Don't forget the last statement
-        let trimmed = current.trim().to_string();
-        if !trimmed.is_empty() {
-            statements.push(trimmed);
-        }
-
-        statements
+        Some(f(client, object, db_name))
     }
 
     /// Execute a query string (may contain multiple statements separated by `;`)
     fn run_query(&mut self, query: &str) {
-        let statements = Self::split_queries(query);
+        let statements = lexer::split_statements(query, self.current_tab().dialect());
+        self.run_statements(statements, true);
+    }
+
+    /// Execute statements the user confirmed in the destructive statement popup
+    pub(super) fn run_confirmed_statements(&mut self, statements: Vec<String>) {
+        self.run_statements(statements, false);
+    }
+
+    fn run_statements(&mut self, statements: Vec<String>, confirm: bool) {
         if statements.is_empty() {
             return;
         }
+        let dialect = self.current_tab().dialect();
 
         let tab = self.current_tab();
         let conn = match tab.connections.get(tab.connected_index) {
@@ -445,7 +838,7 @@ impl Controller {
         // Check readonly for all statements
         if conn.readonly {
             for stmt in &statements {
-                if !Self::is_read_query(stmt) {
+                if !Self::is_read_query(stmt, dialect) {
                     self.current_tab_mut().status_message =
                         Some("Connection is read-only, only select queries allowed".to_string());
                     return;
@@ -453,6 +846,19 @@ impl Controller {
             }
         }
 
+        if confirm
+            && conn.confirm_destructive
+            && let Some(stmt) = statements
+                .iter()
+                .find(|s| lexer::is_destructive(s, dialect))
+        {
+            self.popup_state = PopupState::ConfirmDestructive {
+                statement: stmt.clone(),
+                statements,
+            };
+            return;
+        }
+
         let client = match &tab.db_client {
             Some(c) => Arc::clone(c),
             None => {
@@ -464,20 +870,25 @@ impl Controller {
         debug_log!(
             "Executing {} statement(s): {}",
             statements.len(),
-            query.trim().replace('\n', " ")
+            statements.join("; ").replace('\n', " ")
         );
 
+        // The new result replaces the current one, so stop fetching its rows
+        let released = self.release_session();
         {
             let tab = self.current_tab_mut();
+            tab.result_stream = None;
             tab.loading = true;
             tab.status_message = Some("Executing...".to_string());
         }
 
         let start = std::time::Instant::now();
+        let timeout = self.current_tab().query_timeout;
 
         let (tx, rx) = oneshot::channel();
         self.runtime.spawn(async move {
-            let result = Self::execute_statements(&client, statements).await;
+            released.wait().await;
+            let result = Self::execute_statements(&client, statements, timeout).await;
             let _ = tx.send(result);
         });
 
@@ -487,38 +898,207 @@ impl Controller {
         });
     }
 
-    /// Execute multiple statements sequentially, combining results
+    /// Cancel the running query on the server and wait briefly for it to stop
+    /// in the background. The tab stays busy until the cancel has a message
+    /// saying whether the server confirmed it.
+    pub(super) fn cancel_query(&mut self, receiver: oneshot::Receiver<RunResult>) {
+        let Some(client) = self.current_tab().db_client.clone() else {
+            self.current_tab_mut().status_message = Some("Cancelled".to_string());
+            return;
+        };
+
+        let tab = self.current_tab_mut();
+        tab.loading = true;
+        tab.status_message = Some("Cancelling...".to_string());
+
+        let (tx, rx) = oneshot::channel();
+        self.runtime.spawn(async move {
+            let sent = tokio::time::timeout(CANCEL_TIMEOUT, client.cancel_query())
+                .await
+                .unwrap_or_else(|_| Err(SqliError::Query("request timed out".to_string())));
+            let message = if let Err(e) = sent {
+                format!("Cancelled locally, server cancel failed: {}", e)
+            } else {
+                // The query task finishes once the server has stopped the statement
+                match tokio::time::timeout(CANCEL_TIMEOUT, receiver).await {
+                    Ok(Ok((results, _))) if results.last().is_some_and(|r| r.outcome.is_ok()) => {
+                        "Query finished before it could be cancelled".to_string()
+                    }
+                    Ok(_) => "Query cancelled on server".to_string(),
+                    Err(_) => "Cancel sent, server has not confirmed yet".to_string(),
+                }
+            };
+            debug_log!("{}", message);
+            let _ = tx.send(message);
+        });
+
+        self.current_tab_mut().pending_operation = Some(PendingOperation::Cancel { receiver: rx });
+    }
+
+    /// Stop streaming the current result if its rows keep the connection
+    /// busy. The stream is closed in the background; the next operation on
+    /// the client waits for the returned release so it does not run first.
+    pub(super) fn release_session(&mut self) -> SessionRelease {
+        let tab = self.current_tab_mut();
+        let Some(stream) = tab.result_stream.take_if(|s| s.holds_session()) else {
+            return SessionRelease(None);
+        };
+        tab.result_truncated = true;
+        SessionRelease(Some(self.runtime.spawn(async move {
+            if tokio::time::timeout(CANCEL_TIMEOUT, stream.close())
+                .await
+                .is_err()
+            {
+                debug_log!("Result stream did not stop in time");
+            }
+        })))
+    }
+
+    /// Execute statements in order, keeping each one's result, and stop at
+    /// the first failure. Only the last statement's rows are streamed; earlier
+    /// results are read in full so the connection is free for the next one.
     async fn execute_statements(
-        client: &DatabaseClient,
+        client: &Arc<DatabaseClient>,
         statements: Vec<String>,
-    ) -> crate::error::Result<QueryResult> {
-        let mut last_select: Option<QueryResult> = None;
-        let mut total_rows_affected: u64 = 0;
+        timeout: Option<Duration>,
+    ) -> RunResult {
+        let mut results = Vec::with_capacity(statements.len());
+        let mut last_rows = None;
+        let count = statements.len();
 
-        for stmt in statements {
-            let result = client.execute_query(&stmt).await?;
+        for (i, statement) in statements.into_iter().enumerate() {
+            let start = std::time::Instant::now();
+            let run = async {
+                if i + 1 == count {
+                    client.execute_paged(&statement).await
+                } else {
+                    Ok((client.execute_query(&statement).await?, None))
+                }
+            };
+            let result = match timeout {
+                Some(limit) => Self::execute_with_timeout(client, limit, run).await,
+                None => run.await,
+            };
+            let duration = start.elapsed();
             match result {
-                QueryResult::Select { .. } => {
-                    last_select = Some(result);
+                Ok((result, rows)) => {
+                    results.push(StatementResult {
+                        statement,
+                        duration,
+                        outcome: Ok(result),
+                    });
+                    last_rows = rows;
                 }
-                QueryResult::Execute { rows_affected } => {
-                    total_rows_affected += rows_affected;
+                Err(e) => {
+                    results.push(StatementResult {
+                        statement,
+                        duration,
+                        outcome: Err(e.to_string()),
+                    });
+                    break;
                 }
             }
         }
 
-        // Return last SELECT result if any, otherwise return combined Execute result
-        Ok(last_select.unwrap_or(QueryResult::Execute {
-            rows_affected: total_rows_affected,
-        }))
+        (results, last_rows)
     }
 
-    pub fn is_read_query(query: &str) -> bool {
-        let first_word = query.split_whitespace().next().unwrap_or("").to_uppercase();
+    /// Run one statement under a time limit. Backends that enforce the limit
+    /// on the server report their own error; for the rest the statement is
+    /// cancelled on the server once the limit expires.
+    async fn execute_with_timeout(
+        client: &Arc<DatabaseClient>,
+        limit: Duration,
+        run: impl Future<Output = crate::error::Result<PagedResult>>,
+    ) -> crate::error::Result<PagedResult> {
+        let deadline = if client.enforces_query_timeout() {
+            limit + QUERY_TIMEOUT_GRACE
+        } else {
+            limit
+        };
+
+        // Cancel from a separate task, since SQLite blocks the thread running the query
+        let expired = Arc::new(AtomicBool::new(false));
+        let watchdog = tokio::spawn({
+            let client = Arc::clone(client);
+            let expired = Arc::clone(&expired);
+            async move {
+                tokio::time::sleep(deadline).await;
+                expired.store(true, Ordering::SeqCst);
+                let _ = tokio::time::timeout(CANCEL_TIMEOUT, client.cancel_query()).await;
+            }
+        });
 
-        matches!(
-            first_word.as_str(),
-            "SELECT" | "SHOW" | "DESCRIBE" | "DESC" | "EXPLAIN" | "WITH" | "USE" | "HELP" | "LIST"
+        // Stop waiting on backends that cannot cancel on the server (Cassandra)
+        let result = tokio::time::timeout(deadline + CANCEL_TIMEOUT, run).await;
+        watchdog.abort();
+
+        match result {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(e)) if !expired.load(Ordering::SeqCst) => Err(e),
+            _ => Err(SqliError::Query(format!(
+                "Query timed out after {}s",
+                limit.as_secs()
+            ))),
+        }
+    }
+
+    /// Statements that only read. `WITH` counts unless any of its words is
+    /// INSERT, UPDATE, DELETE or MERGE, since a CTE can modify data.
+    pub fn is_read_query(query: &str, dialect: Dialect) -> bool {
+        let first_word = lexer::first_keyword(query, dialect).unwrap_or_default();
+        let tokens = lexer::tokenize(query, dialect);
+        // PostgreSQL's set_config() could switch the read-only default off
+        let sets_config = tokens.iter().any(|t| {
+            t.ident()
+                .is_some_and(|i| i.eq_ignore_ascii_case("set_config"))
+        });
+
+        match first_word.as_str() {
+            "SELECT" | "SHOW" | "DESCRIBE" | "DESC" | "EXPLAIN" | "USE" | "HELP" | "LIST" => {
+                !sets_config
+            }
+            "WITH" => {
+                !sets_config
+                    && !tokens.iter().any(|t| {
+                        ["INSERT", "UPDATE", "DELETE", "MERGE"]
+                            .iter()
+                            .any(|k| t.is_keyword(k))
+                    })
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Statements run before `EXPLAIN ANALYZE` and after it to roll it back. An
+/// open transaction block, whoever opened it, is kept through a savepoint;
+/// only an idle session gets a transaction of its own.
+fn explain_undo(in_transaction_block: bool) -> (&'static str, &'static [&'static str]) {
+    if in_transaction_block {
+        (
+            "SAVEPOINT sqli_explain",
+            &[
+                "ROLLBACK TO SAVEPOINT sqli_explain",
+                "RELEASE SAVEPOINT sqli_explain",
+            ],
         )
+    } else {
+        ("BEGIN", &["ROLLBACK"])
     }
 }
+
+/// Byte offset of a (row, column) editor position in the lines joined with `\n`
+fn text_offset(lines: &[String], (row, col): (usize, usize)) -> usize {
+    let before: usize = lines.iter().take(row).map(|l| l.len() + 1).sum();
+    let line = lines.get(row).map_or("", String::as_str);
+    before + line.char_indices().nth(col).map_or(line.len(), |(i, _)| i)
+}
+
+/// (row, column) editor position of a byte offset in `text`
+fn text_position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let row = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (row, before[line_start..].chars().count())
+}
@@ -0,0 +1,131 @@
+use super::{Controller, Focus, PopupState, ViewState};
+use crate::history::HistoryEntry;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+impl Controller {
+    /// Open the history popup. Entries go into the query editor, so it
+    /// takes the focus.
+    pub(super) fn open_history_popup(&mut self) {
+        if self.current_tab().view_state != ViewState::DatabaseView {
+            self.current_tab_mut().status_message =
+                Some("Connect to a database to use the history".to_string());
+            return;
+        }
+        if self.history.entries.is_empty() {
+            self.current_tab_mut().status_message =
+                Some("No history yet, statements are recorded as they run".to_string());
+            return;
+        }
+
+        self.current_tab_mut().focus = Focus::Query;
+        self.popup_state = PopupState::History {
+            selected: 0,
+            filter: String::new(),
+            searching: false,
+            all_connections: false,
+        };
+    }
+
+    /// History entries matching the popup's search, newest first
+    pub fn filtered_history(&self, filter: &str, all_connections: bool) -> Vec<&HistoryEntry> {
+        let connection = if all_connections {
+            None
+        } else {
+            self.current_connection_name()
+        };
+        self.history.search(filter, connection)
+    }
+
+    pub(super) fn handle_history_keys(
+        &mut self,
+        key_event: KeyEvent,
+        selected: usize,
+        mut filter: String,
+        searching: bool,
+        all_connections: bool,
+    ) {
+        let popup = |selected, filter, searching| PopupState::History {
+            selected,
+            filter,
+            searching,
+            all_connections,
+        };
+
+        if searching {
+            // Search mode: typing in the filter
+            match key_event.code {
+                KeyCode::Esc => {
+                    // Exit search mode and clear filter
+                    self.popup_state = popup(0, String::new(), false);
+                }
+                KeyCode::Enter => {
+                    // Exit search mode, keep filter
+                    self.popup_state = popup(selected, filter, false);
+                }
+                KeyCode::Char('u') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.popup_state = popup(0, String::new(), true);
+                }
+                KeyCode::Char(c) => {
+                    filter.push(c);
+                    self.popup_state = popup(0, filter, true);
+                }
+                KeyCode::Backspace => {
+                    filter.pop();
+                    self.popup_state = popup(0, filter, true);
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        let max = self
+            .filtered_history(&filter, all_connections)
+            .len()
+            .saturating_sub(1);
+        match key_event.code {
+            KeyCode::Esc => {
+                self.popup_state = PopupState::None;
+            }
+            KeyCode::Enter => {
+                self.insert_history_entry(selected, &filter, all_connections);
+            }
+            KeyCode::Char('/') => {
+                self.popup_state = popup(selected, filter, true);
+            }
+            KeyCode::Tab => {
+                // Switch between this connection and all of them
+                self.popup_state = PopupState::History {
+                    selected: 0,
+                    filter,
+                    searching: false,
+                    all_connections: !all_connections,
+                };
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.popup_state = popup((selected + 1).min(max), filter, false);
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.popup_state = popup(selected.saturating_sub(1), filter, false);
+            }
+            _ => {}
+        }
+    }
+
+    /// Replace the editor's query with the selected entry
+    fn insert_history_entry(&mut self, selected: usize, filter: &str, all_connections: bool) {
+        let Some(query) = self
+            .filtered_history(filter, all_connections)
+            .get(selected)
+            .map(|e| e.query.clone())
+        else {
+            return;
+        };
+
+        let tab = self.current_tab_mut();
+        tab.query_textarea.select_all();
+        tab.query_textarea.cut();
+        tab.query_textarea.insert_str(&query);
+        tab.status_message = Some("Inserted query from history".to_string());
+        self.popup_state = PopupState::None;
+    }
+}
@@ -10,6 +10,22 @@ impl Controller {
             return;
         }
 
+        if let PopupState::ConfirmClose { all } = self.popup_state {
+            self.handle_confirm_close_keys(key_event.code, all);
+            return;
+        }
+
+        if matches!(self.popup_state, PopupState::ExplainPlan { .. }) {
+            self.handle_explain_plan_keys(key_event.code);
+            return;
+        }
+
+        if let PopupState::ConfirmDestructive { ref statements, .. } = self.popup_state {
+            let statements = statements.clone();
+            self.handle_confirm_destructive_keys(key_event.code, statements);
+            return;
+        }
+
         // Esc cancels pending operation (if any)
         if key_event.code == KeyCode::Esc && self.cancel_pending_operation() {
             return;
@@ -29,7 +45,7 @@ impl Controller {
         };
 
         // Total help lines (must match the entries count in popups::help_lines)
-        const HELP_TOTAL_LINES: usize = 57;
+        const HELP_TOTAL_LINES: usize = 74;
 
         match key_code {
             KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('?') => {
@@ -67,6 +83,102 @@ impl Controller {
         }
     }
 
+    fn handle_explain_plan_keys(&mut self, key_code: KeyCode) {
+        let PopupState::ExplainPlan {
+            plan,
+            selected,
+            scroll,
+        } = &mut self.popup_state
+        else {
+            return;
+        };
+
+        let visible = plan.visible();
+        let last = visible.len().saturating_sub(1);
+        let line = visible.get(*selected).copied().unwrap_or(0);
+        let Some(current) = plan.lines.get(line) else {
+            return;
+        };
+        let (depth, has_children, collapsed) =
+            (current.depth, current.has_children, current.collapsed);
+
+        match key_code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.popup_state = PopupState::None;
+                return;
+            }
+            KeyCode::Char('j') | KeyCode::Down => *selected = (*selected + 1).min(last),
+            KeyCode::Char('k') | KeyCode::Up => *selected = selected.saturating_sub(1),
+            KeyCode::PageDown => *selected = (*selected + 10).min(last),
+            KeyCode::PageUp => *selected = selected.saturating_sub(10),
+            KeyCode::Char('g') | KeyCode::Home => *selected = 0,
+            KeyCode::Char('G') | KeyCode::End => *selected = last,
+            KeyCode::Enter | KeyCode::Char(' ') if has_children => {
+                plan.lines[line].collapsed = !collapsed;
+            }
+            KeyCode::Char('l') | KeyCode::Right if has_children && collapsed => {
+                plan.lines[line].collapsed = false;
+            }
+            KeyCode::Char('h') | KeyCode::Left if has_children && !collapsed => {
+                plan.lines[line].collapsed = true;
+            }
+            // Otherwise move to the parent
+            KeyCode::Char('h') | KeyCode::Left => {
+                if let Some(parent) = visible[..*selected]
+                    .iter()
+                    .rposition(|&i| plan.lines[i].depth < depth)
+                {
+                    *selected = parent;
+                }
+            }
+            _ => {}
+        }
+
+        // Keep the selected line in view, estimating the popup height as drawn
+        let term_height = crossterm::terminal::size().map(|(_, h)| h).unwrap_or(24);
+        let visible_lines = ((term_height as f32 * 0.8) as usize)
+            .saturating_sub(4)
+            .max(1);
+        if *selected < *scroll {
+            *scroll = *selected;
+        } else if *selected >= *scroll + visible_lines {
+            *scroll = *selected + 1 - visible_lines;
+        }
+    }
+
+    fn handle_confirm_close_keys(&mut self, key_code: KeyCode, all: bool) {
+        match key_code {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                self.popup_state = PopupState::None;
+                // Dropping a client closes its connection, and the server
+                // rolls back the open transaction
+                if all {
+                    self.quit = true;
+                } else {
+                    self.force_close_current_tab();
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                self.popup_state = PopupState::None;
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_confirm_destructive_keys(&mut self, key_code: KeyCode, statements: Vec<String>) {
+        match key_code {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                self.popup_state = PopupState::None;
+                self.run_confirmed_statements(statements);
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                self.popup_state = PopupState::None;
+                self.current_tab_mut().status_message = Some("Query not run".to_string());
+            }
+            _ => {}
+        }
+    }
+
     fn open_help_popup(&mut self) {
         self.popup_state = PopupState::Help { scroll: 0 };
     }
@@ -74,6 +186,18 @@ impl Controller {
     /// Cancel any pending async operation on the current tab. Returns true if something was cancelled.
     fn cancel_pending_operation(&mut self) -> bool {
         let tab = self.current_tab_mut();
+        // The tab would no longer know whether a transaction is open, and a
+        // cancel is already stopping the query
+        if matches!(
+            tab.pending_operation,
+            Some(
+                super::PendingOperation::SetAutocommit { .. }
+                    | super::PendingOperation::EndTransaction { .. }
+                    | super::PendingOperation::Cancel { .. }
+            )
+        ) {
+            return false;
+        }
         let Some(op) = tab.pending_operation.take() else {
             return false;
         };
@@ -82,17 +206,30 @@ impl Controller {
         tab.pending_g = false;
         tab.status_message = Some("Cancelled".to_string());
 
-        // Reset view state for connection operations
-        if matches!(
+        if let super::PendingOperation::Query { receiver, .. } = op {
+            // Stop the statement on the server too, not just the local wait
+            self.cancel_query(receiver);
+        } else if let super::PendingOperation::Explain { receiver, .. } = op {
+            // Kept pending until the statement has stopped and been rolled back
+            tab.loading = true;
+            tab.status_message = Some("Cancelling...".to_string());
+            tab.pending_operation = Some(super::PendingOperation::Explain {
+                receiver,
+                cancelled: true,
+            });
+            self.cancel_explain();
+        } else if matches!(
             op,
             super::PendingOperation::ListDatabases { .. } | super::PendingOperation::Connect { .. }
         ) {
+            // Reset view state for connection operations
             if tab.db_client.is_some() {
                 // Already connected - return to database view
                 tab.view_state = ViewState::DatabaseView;
             } else {
                 tab.view_state = ViewState::ConnectionList;
                 tab.name = "new".to_string();
+                tab.ssh_tunnel = None;
             }
             tab.databases.clear();
         }
@@ -164,6 +301,7 @@ impl Controller {
                     tab.view_state = ViewState::ConnectionList;
                     tab.name = "new".to_string();
                     tab.databases.clear();
+                    tab.ssh_tunnel = None;
                 }
             }
             KeyCode::Enter => {
@@ -264,9 +402,18 @@ impl Controller {
                 self.current_tab_mut().focus = Focus::Output;
             }
             KeyCode::Char('l') | KeyCode::Right => {
-                self.current_tab_mut().focus = Focus::Query;
+                let expanded = self.sidebar_expand();
+                if !expanded {
+                    self.current_tab_mut().focus = Focus::Query;
+                }
+            }
+            KeyCode::Char('h') | KeyCode::Left => {
+                self.current_tab_mut().sidebar.collapse_selected();
             }
             KeyCode::Enter => {
+                self.sidebar_activate();
+            }
+            KeyCode::Char('p') => {
                 self.select_table();
             }
             KeyCode::Char('d') => {
@@ -279,7 +426,7 @@ impl Controller {
                 self.open_help_popup();
             }
             KeyCode::F(5) => {
-                self.execute_query();
+                self.execute_all();
             }
             _ => {}
         }
@@ -316,9 +463,9 @@ impl Controller {
             return;
         }
 
-        // Check for execute shortcut
+        // Check for execute shortcuts
         if key_event.code == KeyCode::F(5) {
-            self.execute_query();
+            self.execute_all();
             return;
         }
 
@@ -326,7 +473,7 @@ impl Controller {
         if key_event.modifiers.contains(KeyModifiers::CONTROL) {
             match key_event.code {
                 KeyCode::Char('e') => {
-                    self.execute_query();
+                    self.execute_at_cursor();
                     return;
                 }
                 KeyCode::Char('r') => {
@@ -341,6 +488,10 @@ impl Controller {
                     self.open_save_template_popup();
                     return;
                 }
+                KeyCode::Char('p') => {
+                    self.open_history_popup();
+                    return;
+                }
                 KeyCode::Char('g') => {
                     self.edit_query_in_editor();
                     return;
@@ -410,8 +561,7 @@ impl Controller {
             // Get max field count from current query result
             let max_field = self
                 .current_tab()
-                .query_result
-                .as_ref()
+                .query_result()
                 .map(|r| match r {
                     crate::db::QueryResult::Select { columns, .. } => {
                         columns.len().saturating_sub(1)
@@ -450,11 +600,11 @@ impl Controller {
                 KeyCode::Char('y') => {
                     // Copy the selected field value to clipboard
                     if let Some(crate::db::QueryResult::Select { rows, .. }) =
-                        &self.current_tab().query_result
+                        self.current_tab().query_result()
                         && let Some(row) = rows.get(row_index)
                     {
-                        let value = row.get(*selected_field).cloned().unwrap_or_default();
-                        self.copy_to_clipboard(&value, 1, "value");
+                        let value = row.get(*selected_field).map(|v| v.to_string());
+                        self.copy_to_clipboard(&value.unwrap_or_default(), 1, "value");
                     }
                     return;
                 }
@@ -554,6 +704,14 @@ impl Controller {
             KeyCode::Char('y') => {
                 self.yank_selected_rows();
             }
+            KeyCode::Char('[') => {
+                let tab = self.current_tab_mut();
+                tab.select_result(tab.selected_result.saturating_sub(1));
+            }
+            KeyCode::Char(']') => {
+                let tab = self.current_tab_mut();
+                tab.select_result(tab.selected_result + 1);
+            }
             KeyCode::Esc => {
                 let tab = self.current_tab_mut();
                 if tab.visual_select.is_some() {
@@ -564,7 +722,7 @@ impl Controller {
                 self.open_record_detail();
             }
             KeyCode::F(5) => {
-                self.execute_query();
+                self.execute_all();
             }
             _ => {}
         }
@@ -601,13 +759,37 @@ impl Controller {
     fn exec_command(&mut self) {
         match self.command_buffer.as_str() {
             "q" | "quit" => self.close_current_tab(),
-            "qa" | "q!" | "quitall" => self.quit = true,
+            "qa" | "quitall" => self.quit_all(),
+            "q!" => self.quit = true,
             "next" => self.next_tab(),
             "prev" => self.previous_tab(),
             "new" => self.new_tab(),
             "db" => self.open_database_select(),
             "system" => self.toggle_system_databases(),
             "help" | "h" => self.show_help(),
+            "schema" => self.set_schema(""),
+            cmd if cmd.starts_with("schema ") => {
+                let schemas = cmd["schema ".len()..].to_string();
+                self.set_schema(&schemas);
+            }
+            "timeout" => self.set_query_timeout(""),
+            cmd if cmd.starts_with("timeout ") => {
+                let arg = cmd["timeout ".len()..].to_string();
+                self.set_query_timeout(&arg);
+            }
+            "autocommit" => self.set_autocommit(""),
+            cmd if cmd.starts_with("autocommit ") => {
+                let arg = cmd["autocommit ".len()..].to_string();
+                self.set_autocommit(&arg);
+            }
+            "commit" => self.end_transaction(true),
+            "rollback" => self.end_transaction(false),
+            "history" => self.open_history_popup(),
+            "explain" => self.explain(""),
+            cmd if cmd.starts_with("explain ") => {
+                let arg = cmd["explain ".len()..].to_string();
+                self.explain(&arg);
+            }
             cmd => {
                 self.current_tab_mut().status_message = Some(format!("Unknown command: {}", cmd));
             }
@@ -741,8 +923,8 @@ impl Controller {
                 if !sidebar_hidden && x < SIDEBAR_WIDTH {
                     // Scroll in sidebar
                     let tab = self.current_tab_mut();
-                    let table_count = tab.sidebar.tables.len();
-                    if table_count == 0 {
+                    let row_count = tab.sidebar.rows().len();
+                    if row_count == 0 {
                         return;
                     }
 
@@ -750,7 +932,7 @@ impl Controller {
                         tab.sidebar.selected = tab.sidebar.selected.saturating_sub(SCROLL_AMOUNT);
                     } else {
                         tab.sidebar.selected =
-                            (tab.sidebar.selected + SCROLL_AMOUNT).min(table_count - 1);
+                            (tab.sidebar.selected + SCROLL_AMOUNT).min(row_count - 1);
                     }
                 } else {
                     // Scroll in output area
@@ -866,15 +1048,15 @@ impl Controller {
             // Clicked on sidebar
             self.current_tab_mut().focus = Focus::Sidebar;
 
-            // Calculate which table was clicked
-            // Layout: tab bar (1) + block title row (1) + "Tables" header (1) = 3 rows before first table
-            let table_count = self.current_tab().sidebar.tables.len();
-            let clicked_visual_row = y.saturating_sub(3) as usize;
+            // Calculate which row was clicked
+            // Layout: tab bar (1) + block title row (1) = 2 rows before the first tree row
+            let row_count = self.current_tab().sidebar.rows().len();
+            let clicked_visual_row = y.saturating_sub(2) as usize;
 
             // Account for scroll offset in sidebar
             // The sidebar scrolls when selected item is near bottom
             let visible_height = term_size.1.saturating_sub(5) as usize; // approximate
-            let selected = self.current_tab().sidebar.selected + 1; // +1 for "Tables" header
+            let selected = self.current_tab().sidebar.selected;
             let scroll_offset = if visible_height > 0 && selected >= visible_height {
                 selected - visible_height + 1
             } else {
@@ -882,7 +1064,7 @@ impl Controller {
             };
 
             let clicked_index = clicked_visual_row + scroll_offset;
-            if clicked_index < table_count {
+            if clicked_index < row_count {
                 self.current_tab_mut().sidebar.selected = clicked_index;
                 if is_double_click {
                     self.select_table();
@@ -927,7 +1109,7 @@ impl Controller {
 
             let mut clicked_valid_row = false;
             let tab = self.current_tab_mut();
-            if let Some(crate::db::QueryResult::Select { rows, .. }) = &tab.query_result {
+            if let Some(crate::db::QueryResult::Select { rows, .. }) = tab.query_result() {
                 let actual_row = tab.result_scroll + clicked_row;
                 if actual_row < rows.len() {
                     tab.result_cursor = actual_row;
@@ -1,16 +1,25 @@
 mod completion;
 mod database;
+mod history;
 mod input;
 mod navigation;
 mod templates;
 
-use crate::db::{DatabaseClient, QueryResult};
-use crate::error::Result;
+use crate::db::{ColumnInfo, DatabaseClient, DbObject, ObjectKind, QueryResult, RowStream};
+use crate::error::{Result, SqliError};
+use crate::explain::Plan;
+use crate::history::{HistoryEntry, HistoryStore, redact_passwords};
+use crate::lexer::Dialect;
+use crate::ssh_tunnel::{SshConfig, SshTunnel};
 use crate::templates::{Template, TemplateStore};
+use crate::tls::{SslMode, TlsConfig};
+use std::collections::{HashMap, HashSet};
+use std::net::Ipv6Addr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::runtime::Runtime;
 use tokio::sync::oneshot;
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
 
 use std::process::Command;
 
@@ -19,23 +28,76 @@ use crate::clickhouse::ClickHouseClient;
 use crate::mysql::MySqlClient;
 use crate::postgres::PostgresClient;
 
+/// Fetch the next result page when the cursor is this close to the last loaded row
+const FETCH_AHEAD_ROWS: usize = 200;
+
+/// How long the statement that ran stays highlighted in the editor
+const RUN_HIGHLIGHT: Duration = Duration::from_millis(400);
+
+/// A new client, the SSH tunnel it runs through, and its object list
+pub type Connected = (DatabaseClient, Option<Arc<SshTunnel>>, Vec<DbObject>);
+
+/// Databases on the server and the SSH tunnel used to list them
+pub type Listed = (Vec<String>, Option<Arc<SshTunnel>>);
+
+/// A statement from the last run and what it returned
+pub struct StatementResult {
+    pub statement: String,
+    pub duration: Duration,
+    /// The statement's result, or the error it failed with
+    pub outcome: std::result::Result<QueryResult, String>,
+}
+
+/// Results of a run, plus the unread rows of the last statement's result
+pub type RunResult = (Vec<StatementResult>, Option<RowStream>);
+
 pub enum PendingOperation {
     ListDatabases {
-        receiver: oneshot::Receiver<Result<Vec<String>>>,
+        receiver: oneshot::Receiver<Result<Listed>>,
         conn_name: String,
     },
     Connect {
-        receiver: oneshot::Receiver<Result<(DatabaseClient, Vec<String>)>>,
+        receiver: oneshot::Receiver<Result<Connected>>,
         conn_name: String,
         db_name: String,
     },
     Query {
-        receiver: oneshot::Receiver<Result<QueryResult>>,
+        receiver: oneshot::Receiver<RunResult>,
         start: std::time::Instant,
     },
     RefreshTables {
-        receiver: oneshot::Receiver<Result<Vec<String>>>,
+        receiver: oneshot::Receiver<Result<Vec<DbObject>>>,
+    },
+    SetSchema {
+        receiver: oneshot::Receiver<Result<()>>,
+        search_path: String,
+    },
+    ExpandTable {
+        receiver: oneshot::Receiver<Result<Vec<ColumnInfo>>>,
+        table: String,
     },
+    SetQueryTimeout {
+        receiver: oneshot::Receiver<Result<()>>,
+        timeout: Option<Duration>,
+    },
+    SetAutocommit {
+        receiver: oneshot::Receiver<Result<()>>,
+        manual: bool,
+    },
+    /// The commit or rollback, then the next transaction's BEGIN unless the
+    /// commit failed
+    EndTransaction {
+        receiver: oneshot::Receiver<(Result<()>, Option<Result<()>>)>,
+        commit: bool,
+        pending: usize,
+    },
+    Explain {
+        receiver: oneshot::Receiver<Result<Plan>>,
+        /// Esc was pressed; the plan is dropped once the explain has finished
+        cancelled: bool,
+    },
+    /// A cancelled query being stopped on the server
+    Cancel { receiver: oneshot::Receiver<String> },
 }
 
 fn run_password_command(cmd: &str) -> std::io::Result<String> {
@@ -94,6 +156,23 @@ pub enum PopupState {
         name: String,
         filter: String, // Preserve filter to restore on cancel
     },
+    /// Query history, of the current connection unless `all_connections`
+    History {
+        selected: usize,
+        filter: String,
+        searching: bool,
+        all_connections: bool,
+    },
+    /// Closing the current tab, or quitting when `all`, would roll back
+    /// uncommitted statements
+    ConfirmClose {
+        all: bool,
+    },
+    /// `statement` looks destructive; all `statements` run once confirmed
+    ConfirmDestructive {
+        statement: String,
+        statements: Vec<String>,
+    },
     RecordDetail {
         row_index: usize,
         selected_field: usize,
@@ -102,6 +181,12 @@ pub enum PopupState {
     Help {
         scroll: usize,
     },
+    /// Plan from `:explain`; `selected` and `scroll` count visible lines
+    ExplainPlan {
+        plan: Plan,
+        selected: usize,
+        scroll: usize,
+    },
     Completion {
         suggestions: Vec<crate::completion::Suggestion>,
         selected: usize,
@@ -156,20 +241,79 @@ pub struct DatabaseConn {
     pub name: String,
     pub db_type: DatabaseType,
     pub host: String,
+    /// Additional contact points (Cassandra); `host` is used when empty
+    pub hosts: Vec<String>,
     pub port: u16,
     pub user: String,
     pub password: Option<String>,
     pub password_cmd: Option<String>,
     pub database: Option<String>,
     pub path: Option<String>,
+    /// Unix socket path (MySQL); overrides host and port
+    pub socket: Option<String>,
     pub tls: bool,
+    /// Explicit TLS mode; when unset, `tls` selects verify-full or disable
+    pub sslmode: Option<SslMode>,
+    pub sslrootcert: Option<String>,
+    pub sslcert: Option<String>,
+    pub sslkey: Option<String>,
     pub readonly: bool,
+    /// Ask before running statements that look destructive
+    pub confirm_destructive: bool,
+    /// Record statements in the query history
+    pub history: bool,
+    /// Statement timeout in seconds
+    pub query_timeout: Option<u64>,
     pub group: Option<String>,
     /// Protocol for ClickHouse: "native" (default) or "http"
     pub protocol: Option<String>,
+    /// Local datacenter for Cassandra load balancing
+    pub local_datacenter: Option<String>,
+    /// Rows per page for Cassandra queries
+    pub page_size: Option<u32>,
+    /// Jump host to tunnel the connection through
+    pub ssh: Option<SshConfig>,
 }
 
 impl DatabaseConn {
+    /// Resolve the effective TLS settings for this connection.
+    pub fn tls_config(&self) -> TlsConfig {
+        let mode = self.sslmode.unwrap_or(if self.tls {
+            SslMode::VerifyFull
+        } else {
+            SslMode::Disable
+        });
+        TlsConfig {
+            mode,
+            root_cert: self.sslrootcert.clone(),
+            client_cert: self.sslcert.clone(),
+            client_key: self.sslkey.clone(),
+            server_name: None,
+        }
+    }
+
+    /// Contact points as host:port, falling back to `host` when `hosts` is empty.
+    /// IPv6 literals may be bare (`::1`) or bracketed (`[::1]`, `[::1]:9042`).
+    pub fn contact_points(&self) -> Vec<String> {
+        let hosts = if self.hosts.is_empty() {
+            std::slice::from_ref(&self.host)
+        } else {
+            self.hosts.as_slice()
+        };
+        hosts
+            .iter()
+            .map(|h| {
+                if h.parse::<Ipv6Addr>().is_ok() {
+                    format!("[{}]:{}", h, self.port)
+                } else if !h.contains(':') || (h.starts_with('[') && h.ends_with(']')) {
+                    format!("{}:{}", h, self.port)
+                } else {
+                    h.clone()
+                }
+            })
+            .collect()
+    }
+
     /// Resolve the password, either from password_cmd or password field.
     /// Returns (password, Option<warning_message>) where warning is set if password_cmd failed.
     pub fn resolve_password(&self) -> (String, Option<String>) {
@@ -190,24 +334,71 @@ impl DatabaseConn {
         (self.password.clone().unwrap_or_default(), None)
     }
 
-    pub async fn create_client(&self, database: &str) -> Result<(DatabaseClient, Option<String>)> {
+    /// Connect to the database, opening the SSH tunnel first if configured.
+    /// `tunnel` is reused when it still forwards to this server. The tunnel
+    /// must be kept alive for as long as the client is used.
+    pub async fn create_client(
+        &self,
+        database: &str,
+        tunnel: Option<Arc<SshTunnel>>,
+    ) -> Result<(DatabaseClient, Option<Arc<SshTunnel>>, Option<String>)> {
         let (password, pwd_warning) = self.resolve_password();
+        let mut tls = self.tls_config();
+
+        let tunnel = match self.ssh {
+            Some(ref ssh) => {
+                if matches!(self.db_type, DatabaseType::Cassandra | DatabaseType::Sqlite) {
+                    return Err(SqliError::Connection(format!(
+                        "SSH tunnels are not supported for {}",
+                        self.db_type.as_str()
+                    )));
+                }
+                match tunnel {
+                    Some(t) if t.forwards(ssh, &self.host, self.port) => Some(t),
+                    _ => Some(Arc::new(SshTunnel::open(ssh, &self.host, self.port).await?)),
+                }
+            }
+            None => None,
+        };
+        // Point the driver at the local end of the tunnel, but keep verifying
+        // the certificate against the real server name
+        let (host, port) = match tunnel {
+            Some(ref t) => {
+                tls.server_name = Some(self.host.clone());
+                ("127.0.0.1", t.local_port())
+            }
+            None => (self.host.as_str(), self.port),
+        };
+
         let client = match self.db_type {
             DatabaseType::Postgres => {
                 let client =
-                    PostgresClient::connect(&self.host, self.port, &self.user, &password, database)
+                    PostgresClient::connect(host, port, &self.user, &password, database, &tls)
                         .await?;
                 DatabaseClient::Postgres(client)
             }
             DatabaseType::MySql => {
-                let client =
-                    MySqlClient::connect(&self.host, self.port, &self.user, &password, database)
-                        .await?;
+                let client = MySqlClient::connect(
+                    host,
+                    port,
+                    self.socket.as_deref(),
+                    &self.user,
+                    &password,
+                    database,
+                    &tls,
+                )
+                .await?;
                 DatabaseClient::MySql(client)
             }
             DatabaseType::Cassandra => {
                 let client = CassandraClient::connect(
-                    &self.host, self.port, &self.user, &password, database,
+                    &self.contact_points(),
+                    &self.user,
+                    &password,
+                    database,
+                    &tls,
+                    self.local_datacenter.as_deref(),
+                    self.page_size,
                 )
                 .await?;
                 DatabaseClient::Cassandra(client)
@@ -218,7 +409,7 @@ impl DatabaseConn {
                     .as_ref()
                     .is_some_and(|p| p.eq_ignore_ascii_case("http"));
                 let client = ClickHouseClient::connect(
-                    &self.host, self.port, &self.user, &password, database, self.tls, use_http,
+                    host, port, &self.user, &password, database, &tls, use_http,
                 )
                 .await?;
                 DatabaseClient::ClickHouse(client)
@@ -227,17 +418,26 @@ impl DatabaseConn {
                 let path = self.path.as_deref().unwrap_or_default();
                 if self.host.is_empty() {
                     // Local SQLite
-                    let client = crate::sqlite::SqliteClient::connect(path).await?;
+                    let client = crate::sqlite::SqliteClient::connect(path, self.readonly).await?;
                     DatabaseClient::Sqlite(client)
                 } else {
                     // Remote SQLite via SSH
-                    let client =
-                        crate::ssh_sqlite::SshSqliteClient::connect(&self.host, path).await?;
+                    let client = crate::ssh_sqlite::SshSqliteClient::connect(
+                        &self.host,
+                        path,
+                        self.readonly,
+                    )
+                    .await?;
                     DatabaseClient::SshSqlite(client)
                 }
             }
         };
-        Ok((client, pwd_warning))
+        // The statement check in the controller can be bypassed (a data-modifying
+        // CTE, a function with side effects), so the server enforces it too
+        if self.readonly {
+            client.set_readonly().await?;
+        }
+        Ok((client, tunnel, pwd_warning))
     }
 }
 
@@ -249,12 +449,165 @@ pub enum VisualSelect {
     Line { anchor: usize },
 }
 
-#[derive(Debug, Default)]
+/// A visible line in the sidebar object tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SidebarRow {
+    /// Collapsible heading for one kind of object
+    Group {
+        kind: ObjectKind,
+        count: usize,
+        expanded: bool,
+    },
+    /// Index into `SidebarState::objects`
+    Object(usize),
+    /// Column of an expanded table: object index, column index
+    Column(usize, usize),
+}
+
+#[derive(Debug)]
 pub struct SidebarState {
-    pub tables: Vec<String>,
+    /// Objects sorted by kind, then name
+    pub objects: Vec<DbObject>,
+    /// Kinds whose group is expanded
+    pub expanded: HashSet<ObjectKind>,
+    /// Columns of tables expanded in place, by table name
+    pub columns: HashMap<String, Vec<ColumnInfo>>,
+    /// Index into `rows()`
     pub selected: usize,
 }
 
+impl Default for SidebarState {
+    fn default() -> Self {
+        Self {
+            objects: Vec::new(),
+            expanded: HashSet::from([ObjectKind::Table]),
+            columns: HashMap::new(),
+            selected: 0,
+        }
+    }
+}
+
+impl SidebarState {
+    /// Replace the object list, keeping the selection in range.
+    /// Expanded tables are collapsed so their columns get reloaded.
+    pub fn set_objects(&mut self, mut objects: Vec<DbObject>) {
+        objects.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.name.cmp(&b.name)));
+        self.objects = objects;
+        self.columns.clear();
+        self.clamp_selection();
+    }
+
+    /// Names of objects that can be queried like tables (for completion)
+    pub fn tables(&self) -> Vec<String> {
+        self.objects
+            .iter()
+            .filter(|o| o.kind.is_relation())
+            .map(|o| o.name.clone())
+            .collect()
+    }
+
+    /// Visible rows: a group heading per kind, followed by its objects when
+    /// expanded, each followed by its columns when expanded
+    pub fn rows(&self) -> Vec<SidebarRow> {
+        let mut rows = Vec::new();
+        let mut i = 0;
+        while i < self.objects.len() {
+            let kind = self.objects[i].kind;
+            let count = self.objects[i..]
+                .iter()
+                .take_while(|o| o.kind == kind)
+                .count();
+            let expanded = self.expanded.contains(&kind);
+            rows.push(SidebarRow::Group {
+                kind,
+                count,
+                expanded,
+            });
+            if expanded {
+                for idx in i..i + count {
+                    rows.push(SidebarRow::Object(idx));
+                    if let Some(columns) = self.columns.get(&self.objects[idx].name) {
+                        rows.extend((0..columns.len()).map(|c| SidebarRow::Column(idx, c)));
+                    }
+                }
+            }
+            i += count;
+        }
+        rows
+    }
+
+    pub fn selected_row(&self) -> Option<SidebarRow> {
+        self.rows().get(self.selected).copied()
+    }
+
+    /// The selected object, or the table owning the selected column
+    pub fn selected_object(&self) -> Option<&DbObject> {
+        match self.selected_row()? {
+            SidebarRow::Object(idx) | SidebarRow::Column(idx, _) => self.objects.get(idx),
+            SidebarRow::Group { .. } => None,
+        }
+    }
+
+    /// Show a table's columns below it
+    pub fn expand_table(&mut self, name: &str, columns: Vec<ColumnInfo>) {
+        self.columns.insert(name.to_string(), columns);
+    }
+
+    /// Hide a table's columns, moving the selection onto the table
+    pub fn collapse_table(&mut self, idx: usize) {
+        if let Some(obj) = self.objects.get(idx) {
+            self.columns.remove(&obj.name);
+        }
+        if let Some(pos) = self
+            .rows()
+            .iter()
+            .position(|r| *r == SidebarRow::Object(idx))
+        {
+            self.selected = pos;
+        }
+    }
+
+    /// Expand or collapse a group, moving the selection onto its heading
+    pub fn toggle_group(&mut self, kind: ObjectKind) {
+        if !self.expanded.remove(&kind) {
+            self.expanded.insert(kind);
+        }
+        if let Some(pos) = self
+            .rows()
+            .iter()
+            .position(|r| matches!(r, SidebarRow::Group { kind: k, .. } if *k == kind))
+        {
+            self.selected = pos;
+        }
+    }
+
+    /// Collapse the table owning the selected column, the selected table
+    /// if expanded, or else the group containing the selected row
+    pub fn collapse_selected(&mut self) {
+        let kind = match self.selected_row() {
+            Some(SidebarRow::Group { kind, .. }) => kind,
+            Some(SidebarRow::Column(idx, _)) => return self.collapse_table(idx),
+            Some(SidebarRow::Object(idx)) => {
+                if self.columns.contains_key(&self.objects[idx].name) {
+                    return self.collapse_table(idx);
+                }
+                self.objects[idx].kind
+            }
+            None => return,
+        };
+        if self.expanded.contains(&kind) {
+            self.toggle_group(kind);
+        }
+    }
+
+    fn clamp_selection(&mut self) {
+        let count = self.rows().len();
+        if self.selected >= count {
+            self.selected = count.saturating_sub(1);
+        }
+    }
+}
+
 pub struct Tab {
     pub id: usize,
     pub name: String,
@@ -264,14 +617,32 @@ pub struct Tab {
     pub view_state: ViewState,
     pub focus: Focus,
     pub db_client: Option<Arc<DatabaseClient>>,
+    /// SSH tunnel backing `db_client`, or the one the database list was
+    /// fetched through; dropping the last reference closes it
+    pub ssh_tunnel: Option<Arc<SshTunnel>>,
     pub current_database: Option<String>, // the selected database we're connected to
     pub databases: Vec<String>,           // list of databases for DatabaseList view
     pub database_selected: usize,         // selected index in database list
     pub sidebar: SidebarState,
     pub sidebar_hidden: bool, // whether the sidebar is hidden in DatabaseView
     pub column_cache: std::collections::HashMap<String, Vec<String>>, // table -> columns
+    /// Statement timeout, starting from the connection's `query_timeout`
+    pub query_timeout: Option<Duration>,
+    /// Manual commit mode: a transaction is open, and this counts the
+    /// statements run in it that did not return rows
+    pub transaction: Option<usize>,
     pub query_textarea: TextArea<'static>,
-    pub query_result: Option<QueryResult>,
+    /// The statement that just ran is shown as a selection; this is when
+    /// to clear it and the cursor position to restore
+    pub run_highlight: Option<(std::time::Instant, (usize, usize))>,
+    /// One entry per statement of the last run, up to the first that failed
+    pub statement_results: Vec<StatementResult>,
+    /// Index of the statement result shown in the output pane
+    pub selected_result: usize,
+    /// Rows of the last statement's result not fetched yet
+    pub result_stream: Option<RowStream>,
+    /// Fetching stopped before all rows of the last result were read
+    pub result_truncated: bool,
     pub result_scroll: usize,
     pub result_cursor: usize,
     pub result_h_scroll: usize,
@@ -311,14 +682,21 @@ impl Tab {
             view_state: ViewState::ConnectionList,
             focus: Focus::Sidebar,
             db_client: None,
+            ssh_tunnel: None,
             current_database: None,
             databases: Vec::new(),
             database_selected: 0,
             sidebar: SidebarState::default(),
             sidebar_hidden: false,
             column_cache: std::collections::HashMap::new(),
+            query_timeout: None,
+            transaction: None,
             query_textarea: TextArea::default(),
-            query_result: None,
+            run_highlight: None,
+            statement_results: Vec::new(),
+            selected_result: 0,
+            result_stream: None,
+            result_truncated: false,
             result_scroll: 0,
             result_cursor: 0,
             result_h_scroll: 0,
@@ -377,11 +755,13 @@ impl Tab {
     }
 
     pub fn sidebar_next(&mut self) {
-        cycle_next(&mut self.sidebar.selected, self.sidebar.tables.len());
+        let count = self.sidebar.rows().len();
+        cycle_next(&mut self.sidebar.selected, count);
     }
 
     pub fn sidebar_prev(&mut self) {
-        cycle_prev(&mut self.sidebar.selected, self.sidebar.tables.len());
+        let count = self.sidebar.rows().len();
+        cycle_prev(&mut self.sidebar.selected, count);
     }
 
     pub fn database_next(&mut self) {
@@ -392,6 +772,77 @@ impl Tab {
         cycle_prev(&mut self.database_selected, self.databases.len());
     }
 
+    /// Select `start..end` (row, column) in the editor for a moment to show what ran
+    pub fn highlight_run(&mut self, start: (usize, usize), end: (usize, usize)) {
+        self.clear_run_highlight();
+        let cursor = self.query_textarea.cursor();
+        let textarea = &mut self.query_textarea;
+        textarea.cancel_selection();
+        textarea.move_cursor(CursorMove::Jump(start.0 as u16, start.1 as u16));
+        textarea.start_selection();
+        textarea.move_cursor(CursorMove::Jump(end.0 as u16, end.1 as u16));
+        self.run_highlight = Some((std::time::Instant::now() + RUN_HIGHLIGHT, cursor));
+    }
+
+    /// Drop the run highlight and put the cursor back where it was
+    pub fn clear_run_highlight(&mut self) {
+        if let Some((_, (row, col))) = self.run_highlight.take() {
+            self.query_textarea.cancel_selection();
+            self.query_textarea
+                .move_cursor(CursorMove::Jump(row as u16, col as u16));
+        }
+    }
+
+    /// Lexical rules of the connected database
+    pub fn dialect(&self) -> Dialect {
+        self.connections
+            .get(self.connected_index)
+            .map(|c| Dialect::from(&c.db_type))
+            .unwrap_or_default()
+    }
+
+    /// Statements that closing the tab would roll back
+    pub fn uncommitted(&self) -> usize {
+        self.transaction.unwrap_or(0)
+    }
+
+    /// History entries for the statements of a finished run, none if the
+    /// connection keeps no history. `more_rows` tells whether the last
+    /// result has rows still to fetch.
+    fn history_entries(&self, results: &[StatementResult], more_rows: bool) -> Vec<HistoryEntry> {
+        let Some(conn) = self.connections.get(self.connected_index) else {
+            return Vec::new();
+        };
+        if !conn.history {
+            return Vec::new();
+        }
+        let database = self.current_database.clone().unwrap_or_default();
+        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let dialect = self.dialect();
+
+        results
+            .iter()
+            .enumerate()
+            .map(|(i, result)| {
+                let (rows, error) = match &result.outcome {
+                    Ok(QueryResult::Select { rows, .. }) => (rows.len() as u64, None),
+                    Ok(QueryResult::Execute { rows_affected }) => (*rows_affected, None),
+                    Err(e) => (0, Some(e.clone())),
+                };
+                HistoryEntry {
+                    query: redact_passwords(&result.statement, dialect),
+                    connection: conn.name.clone(),
+                    database: database.clone(),
+                    timestamp: timestamp.clone(),
+                    duration_ms: result.duration.as_millis() as u64,
+                    rows,
+                    more_rows: more_rows && i + 1 == results.len(),
+                    error,
+                }
+            })
+            .collect()
+    }
+
     /// Get the visual selection row range (start..=end) if visual mode is active
     pub fn visual_selection_range(&self) -> Option<(usize, usize)> {
         let anchor = match self.visual_select? {
@@ -402,6 +853,78 @@ impl Tab {
         let end = anchor.max(self.result_cursor);
         Some((start, end))
     }
+
+    /// The statement result shown in the output pane
+    pub fn selected_statement(&self) -> Option<&StatementResult> {
+        self.statement_results.get(self.selected_result)
+    }
+
+    /// The shown result, unless its statement failed
+    pub fn query_result(&self) -> Option<&QueryResult> {
+        self.selected_statement()?.outcome.as_ref().ok()
+    }
+
+    /// Show another statement's result, starting at its first row
+    pub fn select_result(&mut self, index: usize) {
+        if index >= self.statement_results.len() || index == self.selected_result {
+            return;
+        }
+        self.selected_result = index;
+        self.result_scroll = 0;
+        self.result_cursor = 0;
+        self.result_h_scroll = 0;
+        self.result_selected_col = 0;
+        self.visual_select = None;
+    }
+
+    /// Append the next page of the result once the cursor gets close to the
+    /// last loaded row. Pages are read ahead in the background, so this only
+    /// takes a page that has already arrived.
+    pub fn fetch_result_page(&mut self) {
+        // The stream belongs to the last statement; fetch while it is shown
+        if self.selected_result + 1 != self.statement_results.len() {
+            return;
+        }
+        let Some(StatementResult {
+            outcome: Ok(QueryResult::Select { rows, .. }),
+            ..
+        }) = self.statement_results.last_mut()
+        else {
+            return;
+        };
+        let Some(stream) = self.result_stream.as_mut() else {
+            return;
+        };
+        if self.result_cursor + FETCH_AHEAD_ROWS < rows.len() {
+            return;
+        }
+
+        match stream.try_next_page() {
+            Some(Ok(page)) => {
+                rows.extend(page);
+                crate::debug_log!("Fetched result page, {} row(s) loaded", rows.len());
+                if stream.finished() {
+                    self.result_stream = None;
+                }
+            }
+            Some(Err(e)) => {
+                self.result_stream = None;
+                self.result_truncated = true;
+                self.status_message = Some(format!("Error fetching rows: {}", e));
+            }
+            None => {}
+        }
+    }
+}
+
+/// Statements of a run that the open transaction holds until it ends: every
+/// one that succeeded and is not a read, including a write with RETURNING,
+/// whose result has rows
+fn pending_writes(results: &[StatementResult], dialect: Dialect) -> usize {
+    results
+        .iter()
+        .filter(|r| r.outcome.is_ok() && !Controller::is_read_query(&r.statement, dialect))
+        .count()
 }
 
 fn cycle_next(index: &mut usize, len: usize) {
@@ -426,6 +949,7 @@ pub struct Controller {
     pub popup_state: PopupState,
     pub template_store: TemplateStore,
     pub template_list_cache: Vec<Template>,
+    pub history: HistoryStore,
     pub needs_redraw: bool,
     pub pending_escape: bool,
     pub pending_ctrl_w: bool,
@@ -443,6 +967,7 @@ impl Controller {
     ) -> Self {
         let runtime = Runtime::new().expect("Failed to create tokio runtime");
         let template_store = TemplateStore::load();
+        let history = HistoryStore::load();
         let clipboard = arboard::Clipboard::new()
             .map_err(|e| crate::debug_log!("Failed to init clipboard: {}", e))
             .ok();
@@ -465,6 +990,7 @@ impl Controller {
             template_store,
             spinner_state: 0,
             template_list_cache: Vec::new(),
+            history,
             needs_redraw: false,
             pending_escape: false,
             pending_ctrl_w: false,
@@ -507,6 +1033,8 @@ impl Controller {
         let current_tab_idx = self.current_tab;
 
         for (tab_idx, tab) in self.tabs.iter_mut().enumerate() {
+            tab.fetch_result_page();
+
             let op = match tab.pending_operation.take() {
                 Some(op) => op,
                 None => continue,
@@ -520,8 +1048,11 @@ impl Controller {
                     Ok(result) => {
                         tab.loading = false;
                         match result {
-                            Ok(databases) => {
+                            Ok((databases, tunnel)) => {
                                 crate::debug_log!("Found {} database(s)", databases.len());
+                                if tunnel.is_some() {
+                                    tab.ssh_tunnel = tunnel;
+                                }
                                 tab.name = conn_name;
                                 // Pre-select current database if switching
                                 let selected = tab
@@ -559,17 +1090,25 @@ impl Controller {
                     Ok(result) => {
                         tab.loading = false;
                         match result {
-                            Ok((client, tables)) => {
+                            Ok((client, tunnel, objects)) => {
                                 crate::debug_log!(
-                                    "Connected to '{}', found {} table(s)",
+                                    "Connected to '{}', found {} object(s)",
                                     db_name,
-                                    tables.len()
+                                    objects.len()
                                 );
                                 tab.name = conn_name;
                                 tab.current_database = Some(db_name);
+                                tab.result_stream = None;
                                 tab.db_client = Some(Arc::new(client));
-                                tab.sidebar.tables = tables;
+                                tab.ssh_tunnel = tunnel;
+                                tab.transaction = None;
+                                tab.query_timeout = tab
+                                    .connections
+                                    .get(tab.connected_index)
+                                    .and_then(|c| c.query_timeout)
+                                    .map(Duration::from_secs);
                                 tab.sidebar.selected = 0;
+                                tab.sidebar.set_objects(objects);
                                 tab.status_message = None;
                                 tab.view_state = ViewState::DatabaseView;
                                 tab.focus = Focus::Query;
@@ -600,7 +1139,7 @@ impl Controller {
                     mut receiver,
                     start,
                 } => match receiver.try_recv() {
-                    Ok(result) => {
+                    Ok((results, stream)) => {
                         let elapsed = start.elapsed();
                         tab.loading = false;
                         tab.result_scroll = 0;
@@ -608,45 +1147,76 @@ impl Controller {
                         tab.result_h_scroll = 0;
                         tab.result_selected_col = 0;
                         tab.visual_select = None;
+                        tab.result_truncated = false;
                         let timestamp = Local::now().format("%H:%M:%S");
+                        let more = if stream.is_some() {
+                            ", more available"
+                        } else {
+                            ""
+                        };
+                        for entry in tab.history_entries(&results, stream.is_some()) {
+                            if let Err(e) = self.history.add(entry) {
+                                crate::debug_log!("Failed to save history: {}", e);
+                            }
+                        }
+                        tab.result_stream = stream;
+                        let writes = pending_writes(&results, tab.dialect());
+                        if let Some(pending) = tab.transaction.as_mut() {
+                            *pending += writes;
+                        }
+                        tab.statement_results = results;
+                        // A run stops at the first failure, so the last result
+                        // is either the error or the final statement
+                        let count = tab.statement_results.len();
+                        tab.selected_result = count.saturating_sub(1);
 
-                        match result {
-                            Ok(query_result) => {
-                                tab.query_result = Some(query_result.clone());
-                                match &query_result {
-                                    QueryResult::Select { rows, .. } => {
-                                        crate::debug_log!(
-                                            "Query returned {} row(s) in {:?}",
-                                            rows.len(),
-                                            elapsed
-                                        );
-                                        tab.status_message = Some(format!(
-                                            "[{}] {} row(s) returned in {:.2?}",
-                                            timestamp,
-                                            rows.len(),
-                                            elapsed
-                                        ));
-                                    }
-                                    QueryResult::Execute { rows_affected } => {
-                                        crate::debug_log!(
-                                            "Query affected {} row(s) in {:?}",
-                                            rows_affected,
-                                            elapsed
-                                        );
-                                        tab.status_message = Some(format!(
-                                            "[{}] {} row(s) affected in {:.2?}",
-                                            timestamp, rows_affected, elapsed
-                                        ));
-                                    }
-                                }
+                        let status = match tab.statement_results.last().map(|r| &r.outcome) {
+                            Some(Ok(QueryResult::Select { rows, .. })) => {
+                                crate::debug_log!(
+                                    "Query returned {} row(s){} in {:?}",
+                                    rows.len(),
+                                    more,
+                                    elapsed
+                                );
+                                format!(
+                                    "[{}] {} row(s) returned in {:.2?}{}",
+                                    timestamp,
+                                    rows.len(),
+                                    elapsed,
+                                    more
+                                )
                             }
-                            Err(e) => {
+                            Some(Ok(QueryResult::Execute { rows_affected })) => {
+                                crate::debug_log!(
+                                    "Query affected {} row(s) in {:?}",
+                                    rows_affected,
+                                    elapsed
+                                );
+                                format!(
+                                    "[{}] {} row(s) affected in {:.2?}",
+                                    timestamp, rows_affected, elapsed
+                                )
+                            }
+                            Some(Err(e)) => {
                                 let db_name = tab.current_database.as_deref().unwrap_or("unknown");
                                 crate::debug_log!("Query error on database '{}': {}", db_name, e);
-                                tab.query_result = None;
-                                tab.status_message = Some(format!("Error: {}", e));
+                                if count > 1 {
+                                    format!("Error in statement {}: {}", count, e)
+                                } else {
+                                    format!("Error: {}", e)
+                                }
                             }
-                        }
+                            None => String::new(),
+                        };
+                        let failed = tab.query_result().is_none();
+                        tab.status_message = Some(if count > 1 && !failed {
+                            format!(
+                                "[{}] {} statements run in {:.2?}, [ and ] switch results",
+                                timestamp, count, elapsed
+                            )
+                        } else {
+                            status
+                        });
                     }
                     Err(oneshot::error::TryRecvError::Empty) => {
                         tab.pending_operation = Some(PendingOperation::Query { receiver, start });
@@ -660,13 +1230,9 @@ impl Controller {
                     Ok(result) => {
                         tab.loading = false;
                         match result {
-                            Ok(tables) => {
-                                crate::debug_log!("Refreshed tables: {} table(s)", tables.len());
-                                tab.sidebar.tables = tables;
-                                if tab.sidebar.selected >= tab.sidebar.tables.len() {
-                                    tab.sidebar.selected =
-                                        tab.sidebar.tables.len().saturating_sub(1);
-                                }
+                            Ok(objects) => {
+                                crate::debug_log!("Refreshed objects: {} object(s)", objects.len());
+                                tab.sidebar.set_objects(objects);
                                 tab.status_message = None;
                             }
                             Err(e) => {
@@ -683,6 +1249,228 @@ impl Controller {
                         tab.status_message = Some("Refresh task failed".to_string());
                     }
                 },
+                PendingOperation::SetSchema {
+                    mut receiver,
+                    search_path,
+                } => match receiver.try_recv() {
+                    Ok(result) => {
+                        tab.loading = false;
+                        match result {
+                            Ok(()) => {
+                                // Bare table names may now resolve to different schemas
+                                tab.column_cache.clear();
+                                tab.status_message =
+                                    Some(format!("search_path set to {}", search_path));
+                            }
+                            Err(e) => {
+                                tab.status_message = Some(format!("Error: {}", e));
+                            }
+                        }
+                    }
+                    Err(oneshot::error::TryRecvError::Empty) => {
+                        tab.pending_operation = Some(PendingOperation::SetSchema {
+                            receiver,
+                            search_path,
+                        });
+                    }
+                    Err(oneshot::error::TryRecvError::Closed) => {
+                        tab.loading = false;
+                        tab.status_message = Some("Schema task failed".to_string());
+                    }
+                },
+                PendingOperation::ExpandTable {
+                    mut receiver,
+                    table,
+                } => match receiver.try_recv() {
+                    Ok(result) => {
+                        tab.loading = false;
+                        match result {
+                            Ok(columns) => {
+                                tab.status_message = None;
+                                tab.column_cache.insert(
+                                    table.clone(),
+                                    columns.iter().map(|c| c.name.clone()).collect(),
+                                );
+                                tab.sidebar.expand_table(&table, columns);
+                            }
+                            Err(e) => {
+                                tab.status_message = Some(format!("Error: {}", e));
+                            }
+                        }
+                    }
+                    Err(oneshot::error::TryRecvError::Empty) => {
+                        tab.pending_operation =
+                            Some(PendingOperation::ExpandTable { receiver, table });
+                    }
+                    Err(oneshot::error::TryRecvError::Closed) => {
+                        tab.loading = false;
+                        tab.status_message = Some("Column task failed".to_string());
+                    }
+                },
+                PendingOperation::SetQueryTimeout {
+                    mut receiver,
+                    timeout,
+                } => match receiver.try_recv() {
+                    Ok(result) => {
+                        tab.loading = false;
+                        match result {
+                            Ok(()) => {
+                                tab.query_timeout = timeout;
+                                tab.status_message = Some(match timeout {
+                                    Some(t) => format!("Query timeout set to {}s", t.as_secs()),
+                                    None => "Query timeout disabled".to_string(),
+                                });
+                            }
+                            Err(e) => {
+                                tab.status_message = Some(format!("Error: {}", e));
+                            }
+                        }
+                    }
+                    Err(oneshot::error::TryRecvError::Empty) => {
+                        tab.pending_operation =
+                            Some(PendingOperation::SetQueryTimeout { receiver, timeout });
+                    }
+                    Err(oneshot::error::TryRecvError::Closed) => {
+                        tab.loading = false;
+                        tab.status_message = Some("Timeout task failed".to_string());
+                    }
+                },
+                PendingOperation::SetAutocommit {
+                    mut receiver,
+                    manual,
+                } => match receiver.try_recv() {
+                    Ok(result) => {
+                        tab.loading = false;
+                        match result {
+                            Ok(()) if manual => {
+                                tab.transaction = Some(0);
+                                tab.status_message = Some(
+                                    "Manual commit: :commit or :rollback ends the transaction"
+                                        .to_string(),
+                                );
+                            }
+                            Ok(()) => {
+                                tab.transaction = None;
+                                tab.status_message = Some("Autocommit is on".to_string());
+                            }
+                            Err(e) => {
+                                tab.status_message = Some(format!("Error: {}", e));
+                            }
+                        }
+                    }
+                    Err(oneshot::error::TryRecvError::Empty) => {
+                        tab.pending_operation =
+                            Some(PendingOperation::SetAutocommit { receiver, manual });
+                    }
+                    Err(oneshot::error::TryRecvError::Closed) => {
+                        tab.loading = false;
+                        tab.status_message = Some("Autocommit task failed".to_string());
+                    }
+                },
+                PendingOperation::EndTransaction {
+                    mut receiver,
+                    commit,
+                    pending,
+                } => match receiver.try_recv() {
+                    Ok((result, reopened)) => {
+                        tab.loading = false;
+                        let ended = match result {
+                            Ok(()) if commit => format!("Committed {} statement(s)", pending),
+                            Ok(()) => format!("Rolled back {} statement(s)", pending),
+                            Err(e) if commit => format!("Commit failed: {}", e),
+                            Err(e) => format!("Rollback failed: {}", e),
+                        };
+                        // A failed rollback means the session is gone, and the
+                        // transaction with it
+                        match reopened {
+                            None => tab.status_message = Some(ended),
+                            Some(Ok(())) => {
+                                tab.transaction = Some(0);
+                                tab.status_message = Some(ended);
+                            }
+                            Some(Err(e)) => {
+                                tab.transaction = None;
+                                tab.status_message = Some(format!(
+                                    "{}; autocommit is back on, a new transaction failed: {}",
+                                    ended, e
+                                ));
+                            }
+                        }
+                    }
+                    Err(oneshot::error::TryRecvError::Empty) => {
+                        tab.pending_operation = Some(PendingOperation::EndTransaction {
+                            receiver,
+                            commit,
+                            pending,
+                        });
+                    }
+                    Err(oneshot::error::TryRecvError::Closed) => {
+                        tab.loading = false;
+                        tab.status_message = Some("Transaction task failed".to_string());
+                    }
+                },
+                // The plan opens over its own tab, once no other popup is open
+                PendingOperation::Explain {
+                    receiver,
+                    cancelled: false,
+                } if tab_idx != current_tab_idx
+                    || !matches!(self.popup_state, PopupState::None) =>
+                {
+                    tab.pending_operation = Some(PendingOperation::Explain {
+                        receiver,
+                        cancelled: false,
+                    });
+                }
+                PendingOperation::Explain {
+                    mut receiver,
+                    cancelled,
+                } => match receiver.try_recv() {
+                    Ok(_) if cancelled => {
+                        tab.loading = false;
+                        tab.status_message = Some("Cancelled".to_string());
+                    }
+                    Ok(result) => {
+                        tab.loading = false;
+                        match result {
+                            Ok(plan) => {
+                                crate::debug_log!("Plan with {} node(s)", plan.lines.len());
+                                tab.status_message = None;
+                                self.popup_state = PopupState::ExplainPlan {
+                                    plan,
+                                    selected: 0,
+                                    scroll: 0,
+                                };
+                            }
+                            Err(e) => {
+                                crate::debug_log!("Explain failed: {}", e);
+                                tab.status_message = Some(format!("Error: {}", e));
+                            }
+                        }
+                    }
+                    Err(oneshot::error::TryRecvError::Empty) => {
+                        tab.pending_operation = Some(PendingOperation::Explain {
+                            receiver,
+                            cancelled,
+                        });
+                    }
+                    Err(oneshot::error::TryRecvError::Closed) => {
+                        tab.loading = false;
+                        tab.status_message = Some("Explain task failed".to_string());
+                    }
+                },
+                PendingOperation::Cancel { mut receiver } => match receiver.try_recv() {
+                    Ok(message) => {
+                        tab.loading = false;
+                        tab.status_message = Some(message);
+                    }
+                    Err(oneshot::error::TryRecvError::Empty) => {
+                        tab.pending_operation = Some(PendingOperation::Cancel { receiver });
+                    }
+                    Err(oneshot::error::TryRecvError::Closed) => {
+                        tab.loading = false;
+                        tab.status_message = Some("Cancel task failed".to_string());
+                    }
+                },
             }
         }
 
@@ -692,3 +1480,60 @@ impl Controller {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CellValue;
+
+    fn result(
+        statement: &str,
+        outcome: std::result::Result<QueryResult, String>,
+    ) -> StatementResult {
+        StatementResult {
+            statement: statement.to_string(),
+            duration: Duration::ZERO,
+            outcome,
+        }
+    }
+
+    fn rows() -> QueryResult {
+        QueryResult::Select {
+            columns: vec!["id".to_string()],
+            column_types: vec!["int4".to_string()],
+            rows: vec![vec![CellValue::Int(1)]],
+        }
+    }
+
+    #[test]
+    fn test_pending_writes_count_returning() {
+        let dialect = Dialect::from(&DatabaseType::Postgres);
+        let results = vec![
+            result("SELECT * FROM t", Ok(rows())),
+            result("INSERT INTO t VALUES (1) RETURNING id", Ok(rows())),
+            result(
+                "UPDATE t SET a = 1 WHERE id = 1",
+                Ok(QueryResult::Execute { rows_affected: 1 }),
+            ),
+            result("DELETE FROM t", Err("permission denied".to_string())),
+        ];
+        assert_eq!(pending_writes(&results, dialect), 2);
+    }
+
+    #[test]
+    fn test_read_query_rejects_set_config() {
+        let dialect = Dialect::from(&DatabaseType::Postgres);
+        assert!(Controller::is_read_query(
+            "SELECT current_setting('work_mem')",
+            dialect
+        ));
+        for query in [
+            "SELECT set_config('default_transaction_read_only', 'off', false)",
+            "SELECT pg_catalog.SET_CONFIG('transaction_read_only', 'off', true)",
+            "WITH s AS (SELECT \"set_config\"('default_transaction_read_only', 'off', false)) SELECT * FROM s",
+            "SET default_transaction_read_only = off",
+        ] {
+            assert!(!Controller::is_read_query(query, dialect), "{}", query);
+        }
+    }
+}
@@ -45,7 +45,26 @@ impl Controller {
         }
     }
 
+    /// Close the current tab, asking first if that would roll back
+    /// uncommitted statements
     pub fn close_current_tab(&mut self) {
+        if self.current_tab().uncommitted() > 0 {
+            self.popup_state = PopupState::ConfirmClose { all: false };
+            return;
+        }
+        self.force_close_current_tab();
+    }
+
+    /// Quit, asking first if any tab has uncommitted statements
+    pub(super) fn quit_all(&mut self) {
+        if self.tabs.iter().any(|t| t.uncommitted() > 0) {
+            self.popup_state = PopupState::ConfirmClose { all: true };
+            return;
+        }
+        self.quit = true;
+    }
+
+    pub(super) fn force_close_current_tab(&mut self) {
         if self.tabs.len() == 1 {
             // Last tab, quit the app.
             self.quit = true;
@@ -109,7 +128,7 @@ impl Controller {
 
     pub(super) fn scroll_to_end(&mut self) {
         let tab = self.current_tab_mut();
-        if let Some(QueryResult::Select { rows, .. }) = &tab.query_result {
+        if let Some(QueryResult::Select { rows, .. }) = tab.query_result() {
             let max_cursor = rows.len().saturating_sub(1);
             tab.result_cursor = max_cursor;
             // Compute visible height to position scroll so last row is at bottom
@@ -129,7 +148,7 @@ impl Controller {
 
     pub(super) fn move_cursor(&mut self, delta: i32, visible_height: usize) {
         let tab = self.current_tab_mut();
-        if let Some(QueryResult::Select { rows, .. }) = &tab.query_result {
+        if let Some(QueryResult::Select { rows, .. }) = tab.query_result() {
             let max_cursor = rows.len().saturating_sub(1);
 
             // Move cursor
@@ -150,7 +169,7 @@ impl Controller {
 
     pub(super) fn open_record_detail(&mut self) {
         let tab = self.current_tab();
-        if let Some(QueryResult::Select { rows, .. }) = &tab.query_result
+        if let Some(QueryResult::Select { rows, .. }) = tab.query_result()
             && !rows.is_empty()
         {
             self.popup_state = PopupState::RecordDetail {
@@ -167,8 +186,8 @@ impl Controller {
         let term_width = crossterm::terminal::size().map(|(w, _)| w).unwrap_or(80) as usize;
         let visible_width = output_table_visible_width(term_width, sidebar_hidden);
 
-        let (total_width, max_col) = match &tab.query_result {
-            Some(QueryResult::Select { columns, rows }) => {
+        let (total_width, max_col) = match tab.query_result() {
+            Some(QueryResult::Select { columns, rows, .. }) => {
                 let widths = result_table_widths(columns, rows, visible_width);
                 let total: usize = widths.iter().sum();
                 (total, columns.len().saturating_sub(1))
@@ -188,8 +207,8 @@ impl Controller {
         let visible_width = output_table_visible_width(term_width, sidebar_hidden);
 
         // Get column info from query result
-        let (col_widths, max_col) = match &tab.query_result {
-            Some(QueryResult::Select { columns, rows }) => {
+        let (col_widths, max_col) = match tab.query_result() {
+            Some(QueryResult::Select { columns, rows, .. }) => {
                 let widths = result_table_widths(columns, rows, visible_width);
                 (widths, columns.len().saturating_sub(1))
             }
@@ -262,7 +281,7 @@ impl Controller {
         let cursor = tab.result_cursor;
         let selected_col = tab.result_selected_col;
 
-        let Some(QueryResult::Select { columns, rows }) = &tab.query_result else {
+        let Some(QueryResult::Select { columns, rows, .. }) = tab.query_result() else {
             return;
         };
 
@@ -275,8 +294,8 @@ impl Controller {
                 let col_name = columns.get(selected_col).cloned().unwrap_or_default();
                 let mut lines = vec![col_name];
                 for row in rows.iter().skip(sel_start).take(sel_end - sel_start + 1) {
-                    let val = row.get(selected_col).cloned().unwrap_or_default();
-                    lines.push(val);
+                    let val = row.get(selected_col).map(|v| v.to_string());
+                    lines.push(val.unwrap_or_default());
                 }
                 let text = lines.join("\n");
                 let count = sel_end - sel_start + 1;
@@ -291,7 +310,8 @@ impl Controller {
                 let header = columns.join("\t");
                 let mut lines = vec![header];
                 for row in rows.iter().skip(sel_start).take(sel_end - sel_start + 1) {
-                    lines.push(row.join("\t"));
+                    let cells: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+                    lines.push(cells.join("\t"));
                 }
                 let text = lines.join("\n");
                 let count = sel_end - sel_start + 1;
@@ -301,8 +321,8 @@ impl Controller {
             None => {
                 // No visual mode — yank the single cell at cursor
                 if let Some(row) = rows.get(cursor) {
-                    let val = row.get(selected_col).cloned().unwrap_or_default();
-                    self.copy_to_clipboard(&val, 1, "cell(s)");
+                    let val = row.get(selected_col).map(|v| v.to_string());
+                    self.copy_to_clipboard(&val.unwrap_or_default(), 1, "cell(s)");
                 }
             }
         }
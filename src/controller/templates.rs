@@ -4,7 +4,7 @@ use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use tui_textarea::CursorMove;
 
 impl Controller {
-    fn current_connection_name(&self) -> Option<&str> {
+    pub(super) fn current_connection_name(&self) -> Option<&str> {
         let tab = self.current_tab();
         tab.connections
             .get(tab.connected_index)
@@ -81,10 +81,27 @@ impl Controller {
             } => {
                 self.handle_confirm_delete_keys(key_event, *index, name.clone(), filter.clone());
             }
+            PopupState::History {
+                selected,
+                filter,
+                searching,
+                all_connections,
+            } => {
+                self.handle_history_keys(
+                    key_event,
+                    *selected,
+                    filter.clone(),
+                    *searching,
+                    *all_connections,
+                );
+            }
             PopupState::RecordDetail { .. } => {
                 // Handled in handle_output_keys
             }
-            PopupState::Help { .. } => {
+            PopupState::Help { .. }
+            | PopupState::ExplainPlan { .. }
+            | PopupState::ConfirmClose { .. }
+            | PopupState::ConfirmDestructive { .. } => {
                 // Handled in handle_normal_mode (before any view dispatch)
             }
             PopupState::Completion {
@@ -217,14 +234,13 @@ impl Controller {
                 }
                 KeyCode::Char('g') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
                     // Edit from filtered list
-                    if let Some(template) = filtered.get(selected) {
-                        if let Some(idx) = self
+                    if let Some(template) = filtered.get(selected)
+                        && let Some(idx) = self
                             .template_list_cache
                             .iter()
                             .position(|t| t.name == template.name)
-                        {
-                            self.edit_template_in_editor(idx);
-                        }
+                    {
+                        self.edit_template_in_editor(idx);
                     }
                 }
                 _ => {}
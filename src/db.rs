@@ -1,17 +1,462 @@
-use crate::error::Result;
+use crate::error::{Result, SqliError};
+
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
 
 /// Result of executing a query
 #[derive(Debug, Clone)]
 pub enum QueryResult {
     Select {
         columns: Vec<String>,
-        rows: Vec<Vec<String>>,
+        /// Type name of each column as reported by the driver, empty if unknown
+        column_types: Vec<String>,
+        rows: Vec<Vec<CellValue>>,
     },
     Execute {
         rows_affected: u64,
     },
 }
 
+/// A single result cell, typed by the backend that read it
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellValue {
+    Null,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    /// Exact numeric, kept in its decimal text form
+    Decimal(String),
+    Text(String),
+    Bytes(Vec<u8>),
+    /// Serialized JSON document
+    Json(String),
+    /// Date, time, timestamp or interval as formatted by the backend
+    Temporal(String),
+    Uuid(Uuid),
+    Array(Vec<CellValue>),
+}
+
+impl CellValue {
+    pub fn array<T: Into<CellValue>>(items: impl IntoIterator<Item = T>) -> Self {
+        CellValue::Array(items.into_iter().map(Into::into).collect())
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, CellValue::Null)
+    }
+
+    /// Numbers are right-aligned in the result table
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            CellValue::Int(_) | CellValue::Float(_) | CellValue::Decimal(_)
+        )
+    }
+
+    /// Length of the displayed text, without formatting string values
+    pub fn display_len(&self) -> usize {
+        match self {
+            CellValue::Decimal(s)
+            | CellValue::Text(s)
+            | CellValue::Json(s)
+            | CellValue::Temporal(s) => s.len(),
+            _ => self.to_string().len(),
+        }
+    }
+}
+
+impl std::fmt::Display for CellValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CellValue::Null => f.write_str("NULL"),
+            CellValue::Bool(b) => write!(f, "{}", b),
+            CellValue::Int(i) => write!(f, "{}", i),
+            CellValue::Float(x) => write!(f, "{}", x),
+            CellValue::Decimal(s)
+            | CellValue::Text(s)
+            | CellValue::Json(s)
+            | CellValue::Temporal(s) => f.write_str(s),
+            CellValue::Bytes(b) => f.write_str(&crate::format::bytes(b, 32)),
+            CellValue::Uuid(u) => write!(f, "{}", u),
+            CellValue::Array(items) => {
+                f.write_str("{")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl<T: Into<CellValue>> From<Option<T>> for CellValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(CellValue::Null, Into::into)
+    }
+}
+
+impl From<bool> for CellValue {
+    fn from(value: bool) -> Self {
+        CellValue::Bool(value)
+    }
+}
+
+macro_rules! int_cell {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for CellValue {
+                fn from(value: $t) -> Self {
+                    CellValue::Int(value as i128)
+                }
+            }
+        )*
+    };
+}
+
+int_cell!(i8, i16, i32, i64, i128, u8, u16, u32, u64);
+
+impl From<f64> for CellValue {
+    fn from(value: f64) -> Self {
+        CellValue::Float(value)
+    }
+}
+
+impl From<f32> for CellValue {
+    /// Widen through the shortest decimal form so 0.1f32 stays 0.1
+    fn from(value: f32) -> Self {
+        CellValue::Float(value.to_string().parse().unwrap_or(value as f64))
+    }
+}
+
+impl From<String> for CellValue {
+    fn from(value: String) -> Self {
+        CellValue::Text(value)
+    }
+}
+
+impl From<&str> for CellValue {
+    fn from(value: &str) -> Self {
+        CellValue::Text(value.to_string())
+    }
+}
+
+impl From<Uuid> for CellValue {
+    fn from(value: Uuid) -> Self {
+        CellValue::Uuid(value)
+    }
+}
+
+impl From<serde_json::Value> for CellValue {
+    fn from(value: serde_json::Value) -> Self {
+        CellValue::Json(value.to_string())
+    }
+}
+
+/// Rows per page when a result set is streamed, unless the backend has its own
+pub const PAGE_SIZE: usize = 1000;
+
+/// First page of a result, plus the rest of its rows if more may follow
+pub type PagedResult = (QueryResult, Option<RowStream>);
+
+enum Chunk {
+    Columns(Vec<String>, Vec<String>),
+    /// A page of rows, and whether it is the last one
+    Rows(Vec<Vec<CellValue>>, bool),
+    /// The statement returned no result set
+    Affected(u64),
+}
+
+/// Rows of a SELECT still being read from the server. A background task reads
+/// at most one page ahead of the consumer; dropping the stream stops it.
+pub struct RowStream {
+    receiver: mpsc::Receiver<Result<Chunk>>,
+    task: Option<JoinHandle<()>>,
+    /// The task keeps the client's connection busy until it has finished
+    holds_session: bool,
+    /// The last page has been taken
+    finished: bool,
+}
+
+impl RowStream {
+    /// Create a stream of `page_size` row pages and the writer a backend task
+    /// fills it through
+    pub fn channel(holds_session: bool, page_size: usize) -> (PageWriter, RowStream) {
+        let (sender, receiver) = mpsc::channel(1);
+        let writer = PageWriter {
+            sender,
+            page: Vec::with_capacity(page_size.min(PAGE_SIZE)),
+            page_size,
+        };
+        let stream = RowStream {
+            receiver,
+            task: None,
+            holds_session,
+            finished: false,
+        };
+        (writer, stream)
+    }
+
+    /// Attach the task writing the stream, so `close` can wait for it
+    pub fn with_task(mut self, task: JoinHandle<()>) -> Self {
+        self.task = Some(task);
+        self
+    }
+
+    pub fn holds_session(&self) -> bool {
+        self.holds_session
+    }
+
+    /// The last page has been taken, so there are no more rows to fetch
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Wait for the column names and types and the first page, or for the
+    /// affected row count if the statement turned out not to return rows
+    pub async fn first_page(mut self) -> Result<PagedResult> {
+        let (columns, column_types) = match self.receiver.recv().await.transpose()? {
+            Some(Chunk::Columns(columns, types)) => (columns, types),
+            Some(Chunk::Affected(rows_affected)) => {
+                if let Some(task) = self.task.take() {
+                    let _ = task.await;
+                }
+                return Ok((QueryResult::Execute { rows_affected }, None));
+            }
+            _ => (Vec::new(), Vec::new()),
+        };
+        let (rows, last) = match self.receiver.recv().await.transpose()? {
+            Some(Chunk::Rows(rows, last)) => (rows, last),
+            _ => (Vec::new(), true),
+        };
+        let more = !last;
+        let result = QueryResult::Select {
+            columns,
+            column_types,
+            rows,
+        };
+        Ok((result, more.then_some(self)))
+    }
+
+    /// The next page if it has arrived; `finished` tells whether it was the last.
+    pub fn try_next_page(&mut self) -> Option<Result<Vec<Vec<CellValue>>>> {
+        match self.receiver.try_recv() {
+            Ok(Ok(Chunk::Rows(rows, last))) => {
+                self.finished = last;
+                Some(Ok(rows))
+            }
+            Ok(Ok(Chunk::Columns(..) | Chunk::Affected(_))) | Err(TryRecvError::Empty) => None,
+            Ok(Err(e)) => Some(Err(e)),
+            Err(TryRecvError::Disconnected) => {
+                self.finished = true;
+                Some(Ok(Vec::new()))
+            }
+        }
+    }
+
+    /// Read all remaining rows
+    pub async fn read_all(mut self) -> Result<Vec<Vec<CellValue>>> {
+        let mut rows = Vec::new();
+        while let Some(chunk) = self.receiver.recv().await {
+            if let Chunk::Rows(page, _) = chunk? {
+                rows.extend(page);
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Stop reading and wait until the task has released the connection
+    pub async fn close(mut self) {
+        self.receiver.close();
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+/// Read the remaining pages of a result into its first page
+pub async fn collect_paged((mut result, stream): PagedResult) -> Result<QueryResult> {
+    if let (QueryResult::Select { rows, .. }, Some(stream)) = (&mut result, stream) {
+        rows.extend(stream.read_all().await?);
+    }
+    Ok(result)
+}
+
+/// Writing half of a `RowStream`, used by the backend task reading rows.
+/// The `push` methods return false once the consumer has gone away.
+pub struct PageWriter {
+    sender: mpsc::Sender<Result<Chunk>>,
+    page: Vec<Vec<CellValue>>,
+    page_size: usize,
+}
+
+impl PageWriter {
+    /// Send the column names with their type names, which may be empty
+    pub async fn columns(&mut self, columns: Vec<String>, types: Vec<String>) -> bool {
+        let chunk = Chunk::Columns(columns, types);
+        self.sender.send(Ok(chunk)).await.is_ok()
+    }
+
+    pub async fn push(&mut self, row: Vec<CellValue>) -> bool {
+        self.page.push(row);
+        if self.page.len() < self.page_size {
+            return true;
+        }
+        let page = std::mem::replace(
+            &mut self.page,
+            Vec::with_capacity(self.page_size.min(PAGE_SIZE)),
+        );
+        self.sender.send(Ok(Chunk::Rows(page, false))).await.is_ok()
+    }
+
+    /// Send the last, possibly empty, page, which ends the stream
+    pub async fn finish(self) {
+        let _ = self.sender.send(Ok(Chunk::Rows(self.page, true))).await;
+    }
+
+    pub async fn fail(self, error: SqliError) {
+        let _ = self.sender.send(Err(error)).await;
+    }
+
+    /// Report a statement without a result set, in place of columns and rows
+    pub async fn affected(self, rows: u64) {
+        let _ = self.sender.send(Ok(Chunk::Affected(rows))).await;
+    }
+
+    // Variants for backends reading rows on a blocking thread
+
+    pub fn blocking_columns(&mut self, columns: Vec<String>, types: Vec<String>) -> bool {
+        let chunk = Chunk::Columns(columns, types);
+        self.sender.blocking_send(Ok(chunk)).is_ok()
+    }
+
+    pub fn blocking_push(&mut self, row: Vec<CellValue>) -> bool {
+        self.page.push(row);
+        if self.page.len() < self.page_size {
+            return true;
+        }
+        let page = std::mem::replace(
+            &mut self.page,
+            Vec::with_capacity(self.page_size.min(PAGE_SIZE)),
+        );
+        self.sender
+            .blocking_send(Ok(Chunk::Rows(page, false)))
+            .is_ok()
+    }
+
+    pub fn blocking_finish(self) {
+        let _ = self.sender.blocking_send(Ok(Chunk::Rows(self.page, true)));
+    }
+
+    pub fn blocking_fail(self, error: SqliError) {
+        let _ = self.sender.blocking_send(Err(error));
+    }
+
+    pub fn blocking_affected(self, rows: u64) {
+        let _ = self.sender.blocking_send(Ok(Chunk::Affected(rows)));
+    }
+}
+
+/// Kind of database object shown in the sidebar, in display order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ObjectKind {
+    Table,
+    View,
+    MaterializedView,
+    Dictionary,
+    Function,
+    Procedure,
+    Sequence,
+    Index,
+    Type,
+}
+
+impl ObjectKind {
+    /// Parse the kind tag returned by the object listing queries
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "table" => Some(ObjectKind::Table),
+            "view" => Some(ObjectKind::View),
+            "matview" => Some(ObjectKind::MaterializedView),
+            "dictionary" => Some(ObjectKind::Dictionary),
+            "function" => Some(ObjectKind::Function),
+            "procedure" => Some(ObjectKind::Procedure),
+            "sequence" => Some(ObjectKind::Sequence),
+            "index" => Some(ObjectKind::Index),
+            "type" => Some(ObjectKind::Type),
+            _ => None,
+        }
+    }
+
+    /// Group label in the sidebar
+    pub fn label(&self) -> &'static str {
+        match self {
+            ObjectKind::Table => "Tables",
+            ObjectKind::View => "Views",
+            ObjectKind::MaterializedView => "Materialized Views",
+            ObjectKind::Dictionary => "Dictionaries",
+            ObjectKind::Function => "Functions",
+            ObjectKind::Procedure => "Procedures",
+            ObjectKind::Sequence => "Sequences",
+            ObjectKind::Index => "Indexes",
+            ObjectKind::Type => "Types",
+        }
+    }
+
+    /// Whether the object can be selected from like a table
+    pub fn is_relation(&self) -> bool {
+        matches!(
+            self,
+            ObjectKind::Table
+                | ObjectKind::View
+                | ObjectKind::MaterializedView
+                | ObjectKind::Dictionary
+        )
+    }
+}
+
+/// A named database object (table, view, function, ...)
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbObject {
+    pub kind: ObjectKind,
+    pub name: String,
+}
+
+impl DbObject {
+    pub fn new(kind: ObjectKind, name: impl Into<String>) -> Self {
+        Self {
+            kind,
+            name: name.into(),
+        }
+    }
+}
+
+/// Column metadata from the catalog
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub primary_key: bool,
+    pub foreign_key: bool,
+}
+
+/// Build objects from `(kind, name)` rows, skipping unknown kinds
+pub fn objects_from_rows<I>(rows: I) -> Vec<DbObject>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    rows.into_iter()
+        .filter_map(|(kind, name)| Some(DbObject::new(ObjectKind::parse(&kind)?, name)))
+        .collect()
+}
+
 /// Database client abstraction using enum dispatch
 pub enum DatabaseClient {
     Postgres(crate::postgres::PostgresClient),
@@ -34,25 +479,130 @@ impl DatabaseClient {
         }
     }
 
-    pub async fn list_tables(&self, schema: &str) -> Result<Vec<String>> {
+    /// List tables, views, functions and other objects in a database
+    pub async fn list_objects(&self, database: &str) -> Result<Vec<DbObject>> {
         match self {
-            DatabaseClient::Postgres(client) => client.list_tables(schema).await,
-            DatabaseClient::MySql(client) => client.list_tables(schema).await,
-            DatabaseClient::Cassandra(client) => client.list_tables(schema).await,
-            DatabaseClient::ClickHouse(client) => client.list_tables(schema).await,
-            DatabaseClient::Sqlite(client) => client.list_tables(schema).await,
-            DatabaseClient::SshSqlite(client) => client.list_tables(schema).await,
+            DatabaseClient::Postgres(client) => client.list_objects(database).await,
+            DatabaseClient::MySql(client) => client.list_objects(database).await,
+            DatabaseClient::Cassandra(client) => client.list_objects(database).await,
+            DatabaseClient::ClickHouse(client) => client.list_objects(database).await,
+            DatabaseClient::Sqlite(client) => client.list_objects(database).await,
+            DatabaseClient::SshSqlite(client) => client.list_objects(database).await,
         }
     }
 
+    /// Execute a query and read the whole result
     pub async fn execute_query(&self, query: &str) -> Result<QueryResult> {
+        collect_paged(self.execute_paged(query).await?).await
+    }
+
+    /// Execute a query, returning once the first page of rows is available.
+    /// Backends without streaming support return the whole result at once.
+    pub async fn execute_paged(&self, query: &str) -> Result<PagedResult> {
         match self {
-            DatabaseClient::Postgres(client) => client.execute_query(query).await,
-            DatabaseClient::MySql(client) => client.execute_query(query).await,
-            DatabaseClient::Cassandra(client) => client.execute_query(query).await,
-            DatabaseClient::ClickHouse(client) => client.execute_query(query).await,
-            DatabaseClient::Sqlite(client) => client.execute_query(query).await,
-            DatabaseClient::SshSqlite(client) => client.execute_query(query).await,
+            DatabaseClient::Postgres(client) => client.execute_paged(query).await,
+            DatabaseClient::MySql(client) => client.execute_paged(query).await,
+            DatabaseClient::Cassandra(client) => client.execute_paged(query).await,
+            DatabaseClient::ClickHouse(client) => client.execute_paged(query).await,
+            DatabaseClient::Sqlite(client) => client.execute_paged(query).await,
+            DatabaseClient::SshSqlite(client) => Ok((client.execute_query(query).await?, None)),
+        }
+    }
+
+    /// Apply a statement timeout on the server, where the backend supports it
+    pub async fn set_query_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        match self {
+            DatabaseClient::Postgres(client) => client.set_query_timeout(timeout).await,
+            DatabaseClient::MySql(client) => client.set_query_timeout(timeout).await,
+            DatabaseClient::ClickHouse(client) => client.set_query_timeout(timeout).await,
+            DatabaseClient::Cassandra(_)
+            | DatabaseClient::Sqlite(_)
+            | DatabaseClient::SshSqlite(_) => Ok(()),
+        }
+    }
+
+    /// Make the server refuse writes on this connection. SQLite files are
+    /// opened read-only instead, and Cassandra has no such setting.
+    pub async fn set_readonly(&self) -> Result<()> {
+        match self {
+            DatabaseClient::Postgres(client) => client.set_readonly().await,
+            DatabaseClient::MySql(client) => {
+                client.set_readonly();
+                Ok(())
+            }
+            DatabaseClient::ClickHouse(client) => {
+                client.set_readonly();
+                Ok(())
+            }
+            DatabaseClient::Cassandra(_)
+            | DatabaseClient::Sqlite(_)
+            | DatabaseClient::SshSqlite(_) => Ok(()),
+        }
+    }
+
+    /// Whether `set_query_timeout` is enforced by the server. Otherwise the
+    /// caller has to time out and cancel the query itself.
+    pub fn enforces_query_timeout(&self) -> bool {
+        match self {
+            DatabaseClient::Postgres(_)
+            | DatabaseClient::MySql(_)
+            | DatabaseClient::ClickHouse(_) => true,
+            DatabaseClient::Cassandra(_)
+            | DatabaseClient::Sqlite(_)
+            | DatabaseClient::SshSqlite(_) => false,
+        }
+    }
+
+    /// Ask the server to stop the statement currently executing on this client
+    pub async fn cancel_query(&self) -> Result<()> {
+        match self {
+            DatabaseClient::Postgres(client) => client.cancel_query().await,
+            DatabaseClient::MySql(client) => client.cancel_query().await,
+            DatabaseClient::Cassandra(_) => Err(SqliError::Other(
+                "server-side cancellation is not supported for Cassandra".to_string(),
+            )),
+            DatabaseClient::ClickHouse(client) => client.cancel_query().await,
+            DatabaseClient::Sqlite(client) => client.cancel_query().await,
+            DatabaseClient::SshSqlite(client) => client.cancel_query().await,
+        }
+    }
+
+    /// Open a transaction on the session that following statements run in
+    pub async fn begin(&self) -> Result<()> {
+        match self {
+            DatabaseClient::Postgres(client) => client.begin().await,
+            DatabaseClient::MySql(client) => client.begin().await,
+            DatabaseClient::Sqlite(client) => client.begin().await,
+            _ => Err(transactions_unsupported()),
+        }
+    }
+
+    pub async fn commit(&self) -> Result<()> {
+        match self {
+            DatabaseClient::Postgres(client) => client.commit().await,
+            DatabaseClient::MySql(client) => client.commit().await,
+            DatabaseClient::Sqlite(client) => client.commit().await,
+            _ => Err(transactions_unsupported()),
+        }
+    }
+
+    pub async fn rollback(&self) -> Result<()> {
+        match self {
+            DatabaseClient::Postgres(client) => client.rollback().await,
+            DatabaseClient::MySql(client) => client.rollback().await,
+            DatabaseClient::Sqlite(client) => client.rollback().await,
+            _ => Err(transactions_unsupported()),
+        }
+    }
+
+    /// Whether the session is inside a transaction block, however it was
+    /// opened. Only PostgreSQL can tell.
+    pub async fn in_transaction_block(&self) -> Result<bool> {
+        match self {
+            DatabaseClient::Postgres(client) => client.in_transaction_block().await,
+            _ => Err(SqliError::Other(
+                "the transaction state is only known for PostgreSQL".to_string(),
+            )),
         }
     }
 
@@ -80,8 +630,38 @@ impl DatabaseClient {
         }
     }
 
-    /// Get column names for a table
-    pub async fn list_columns(&self, table: &str, schema: Option<&str>) -> Result<Vec<String>> {
+    /// Generate a query to preview an object: rows for relations, the definition otherwise
+    pub fn preview_query(&self, obj: &DbObject, limit: usize, schema: Option<&str>) -> String {
+        if obj.kind.is_relation() {
+            self.select_table_query(&obj.name, limit, schema)
+        } else {
+            self.definition_query(obj, schema)
+        }
+    }
+
+    /// Generate a query to describe an object: columns for relations, the definition otherwise
+    pub fn describe_query(&self, obj: &DbObject, schema: Option<&str>) -> String {
+        if obj.kind.is_relation() {
+            self.describe_table_query(&obj.name, schema)
+        } else {
+            self.definition_query(obj, schema)
+        }
+    }
+
+    /// Generate a query showing the definition of a non-relation object
+    pub fn definition_query(&self, obj: &DbObject, schema: Option<&str>) -> String {
+        match self {
+            DatabaseClient::Postgres(client) => client.definition_query(obj, schema),
+            DatabaseClient::MySql(client) => client.definition_query(obj, schema),
+            DatabaseClient::Cassandra(client) => client.definition_query(obj, schema),
+            DatabaseClient::ClickHouse(client) => client.definition_query(obj, schema),
+            DatabaseClient::Sqlite(client) => client.definition_query(obj, schema),
+            DatabaseClient::SshSqlite(client) => client.definition_query(obj, schema),
+        }
+    }
+
+    /// Get columns of a table with their types and key flags
+    pub async fn list_columns(&self, table: &str, schema: Option<&str>) -> Result<Vec<ColumnInfo>> {
         match self {
             DatabaseClient::Postgres(client) => client.list_columns(table).await,
             DatabaseClient::MySql(client) => client.list_columns(table).await,
@@ -91,4 +671,18 @@ impl DatabaseClient {
             DatabaseClient::SshSqlite(client) => client.list_columns(table, schema).await,
         }
     }
+
+    /// Set the schema search path (PostgreSQL only)
+    pub async fn set_search_path(&self, schemas: &[&str]) -> Result<()> {
+        match self {
+            DatabaseClient::Postgres(client) => client.set_search_path(schemas).await,
+            _ => Err(SqliError::Other(
+                "Schemas are only supported for PostgreSQL".to_string(),
+            )),
+        }
+    }
+}
+
+fn transactions_unsupported() -> SqliError {
+    SqliError::Other("Transactions are only supported for PostgreSQL, MySQL and SQLite".to_string())
 }
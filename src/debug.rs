@@ -17,16 +17,13 @@ pub fn init(enabled: bool) {
 
     let log_path = PathBuf::from("/tmp/sqli.log");
 
-    match OpenOptions::new().create(true).append(true).open(&log_path) {
-        Ok(mut file) => {
-            let _ = writeln!(
-                file,
-                "\n=== sqli debug session started at {} ===",
-                Local::now().format("%Y-%m-%d %H:%M:%S")
-            );
-            *DEBUG_LOG.lock().unwrap() = Some(file);
-        }
-        Err(_) => {}
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&log_path) {
+        let _ = writeln!(
+            file,
+            "\n=== sqli debug session started at {} ===",
+            Local::now().format("%Y-%m-%d %H:%M:%S")
+        );
+        *DEBUG_LOG.lock().unwrap() = Some(file);
     }
 }
 
@@ -35,12 +32,12 @@ pub fn log(message: &str) {
         return;
     }
 
-    if let Ok(mut guard) = DEBUG_LOG.lock() {
-        if let Some(ref mut file) = *guard {
-            let timestamp = Local::now().format("%H:%M:%S%.3f");
-            let _ = writeln!(file, "[{}] {}", timestamp, message);
-            let _ = file.flush();
-        }
+    if let Ok(mut guard) = DEBUG_LOG.lock()
+        && let Some(ref mut file) = *guard
+    {
+        let timestamp = Local::now().format("%H:%M:%S%.3f");
+        let _ = writeln!(file, "[{}] {}", timestamp, message);
+        let _ = file.flush();
     }
 }
 
@@ -65,6 +65,18 @@ impl From<Box<dyn std::error::Error + Send + Sync>> for SqliError {
     }
 }
 
+impl From<native_tls::Error> for SqliError {
+    fn from(e: native_tls::Error) -> Self {
+        SqliError::Connection(format!("TLS: {}", e))
+    }
+}
+
+impl From<openssl::error::ErrorStack> for SqliError {
+    fn from(e: openssl::error::ErrorStack) -> Self {
+        SqliError::Connection(format!("TLS: {}", e))
+    }
+}
+
 impl From<reqwest::Error> for SqliError {
     fn from(e: reqwest::Error) -> Self {
         SqliError::Connection(e.to_string())
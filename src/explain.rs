@@ -0,0 +1,800 @@
+//! Query plans: the EXPLAIN statement each backend understands, and its
+//! output parsed into a tree of nodes for the plan popup.
+
+use crate::controller::DatabaseType;
+use crate::db::{CellValue, QueryResult};
+use crate::error::{Result, SqliError};
+use crate::lexer::{self, Dialect};
+
+use serde_json::Value;
+
+/// Nodes taking at least this share of the plan's cost or time are highlighted
+const HOT_SHARE: f64 = 0.2;
+
+/// Which plan `:explain` asks for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExplainMode {
+    /// The planner's estimate, without running the statement
+    Plan,
+    /// Run the statement and report actual rows and time
+    Analyze,
+    /// The processor pipeline (ClickHouse)
+    Pipeline,
+}
+
+impl ExplainMode {
+    pub fn parse(arg: &str) -> Option<Self> {
+        match arg.trim().to_lowercase().as_str() {
+            "" | "plan" => Some(ExplainMode::Plan),
+            "analyze" => Some(ExplainMode::Analyze),
+            "pipeline" => Some(ExplainMode::Pipeline),
+            _ => None,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ExplainMode::Plan => "Plan",
+            ExplainMode::Analyze => "Plan (analyze)",
+            ExplainMode::Pipeline => "Pipeline",
+        }
+    }
+}
+
+/// The EXPLAIN statement showing the plan of `statement`
+pub fn explain_query(db_type: &DatabaseType, mode: ExplainMode, statement: &str) -> Result<String> {
+    if lexer::first_keyword(statement, Dialect::from(db_type)).as_deref() == Some("EXPLAIN") {
+        return Err(SqliError::Query(
+            "Already an EXPLAIN statement, run it instead".to_string(),
+        ));
+    }
+
+    let query = match (db_type, mode) {
+        (DatabaseType::Postgres, ExplainMode::Plan) => {
+            format!("EXPLAIN (FORMAT JSON) {}", statement)
+        }
+        (DatabaseType::Postgres, ExplainMode::Analyze) => {
+            format!("EXPLAIN (ANALYZE, FORMAT JSON) {}", statement)
+        }
+        (DatabaseType::MySql, ExplainMode::Plan) => format!("EXPLAIN FORMAT=JSON {}", statement),
+        // Only the tree format reports actual rows and time
+        (DatabaseType::MySql, ExplainMode::Analyze) => format!("EXPLAIN ANALYZE {}", statement),
+        (DatabaseType::ClickHouse, ExplainMode::Plan) => {
+            format!("EXPLAIN PLAN json = 1, indexes = 1 {}", statement)
+        }
+        (DatabaseType::ClickHouse, ExplainMode::Pipeline) => {
+            format!("EXPLAIN PIPELINE {}", statement)
+        }
+        (DatabaseType::Sqlite, ExplainMode::Plan) => format!("EXPLAIN QUERY PLAN {}", statement),
+        (DatabaseType::Cassandra, _) => {
+            return Err(SqliError::Query("Cassandra has no EXPLAIN".to_string()));
+        }
+        (_, mode) => {
+            let name = match mode {
+                ExplainMode::Plan => "plan",
+                ExplainMode::Analyze => "analyze",
+                ExplainMode::Pipeline => "pipeline",
+            };
+            return Err(SqliError::Query(format!(
+                ":explain {} is not supported for {}",
+                name,
+                db_type.as_str()
+            )));
+        }
+    };
+    Ok(query)
+}
+
+/// One step of a plan and what the backend reports for it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlanNode {
+    pub label: String,
+    /// Relation, conditions and keys, shown after the label
+    pub detail: String,
+    /// Planner cost estimate, including the node's children
+    pub cost: Option<f64>,
+    /// Rows the node returns: estimated, or actual when analyzed
+    pub rows: Option<f64>,
+    /// Milliseconds spent over all loops, including the node's children
+    pub time: Option<f64>,
+    pub children: Vec<PlanNode>,
+}
+
+/// Parse the output of the statement built by [`explain_query`]
+pub fn parse_plan(
+    db_type: &DatabaseType,
+    mode: ExplainMode,
+    result: &QueryResult,
+) -> Result<Vec<PlanNode>> {
+    let QueryResult::Select { columns, rows, .. } = result else {
+        return Err(SqliError::Query("EXPLAIN returned no plan".to_string()));
+    };
+    // Plans arrive as one text cell, or one line of text per row
+    let text = || {
+        rows.iter()
+            .filter_map(|row| row.first())
+            .map(CellValue::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let nodes = match db_type {
+        DatabaseType::Postgres => postgres_plan(&parse_json(&text())?),
+        DatabaseType::MySql if mode == ExplainMode::Analyze => mysql_tree(&text()),
+        DatabaseType::MySql => mysql_children(&parse_json(&text())?),
+        DatabaseType::ClickHouse if mode == ExplainMode::Pipeline => clickhouse_pipeline(&text()),
+        DatabaseType::ClickHouse => clickhouse_plan(&text()),
+        DatabaseType::Sqlite => sqlite_plan(columns, rows)?,
+        DatabaseType::Cassandra => Vec::new(),
+    };
+    if nodes.is_empty() {
+        return Err(SqliError::Query("EXPLAIN returned no plan".to_string()));
+    }
+    Ok(nodes)
+}
+
+fn parse_json(text: &str) -> Result<Value> {
+    serde_json::from_str(text).map_err(|e| SqliError::Query(format!("Unreadable plan: {}", e)))
+}
+
+/// `EXPLAIN (FORMAT JSON)`: `[{"Plan": {"Node Type": ..., "Plans": [...]}}]`
+fn postgres_plan(doc: &Value) -> Vec<PlanNode> {
+    doc.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get("Plan"))
+        .map(postgres_node)
+        .collect()
+}
+
+fn postgres_node(plan: &Value) -> PlanNode {
+    let text = |key: &str| plan.get(key).and_then(Value::as_str);
+    let number = |key: &str| plan.get(key).and_then(Value::as_f64);
+
+    let mut label = text("Node Type").unwrap_or("?").to_string();
+    if let Some(join) = text("Join Type")
+        && join != "Inner"
+    {
+        label = format!("{} ({})", label, join);
+    }
+
+    let mut detail = Vec::new();
+    if let Some(relation) = text("Relation Name") {
+        match text("Alias") {
+            Some(alias) if alias != relation => detail.push(format!("on {} {}", relation, alias)),
+            _ => detail.push(format!("on {}", relation)),
+        }
+    }
+    if let Some(index) = text("Index Name") {
+        detail.push(format!("using {}", index));
+    }
+    for key in [
+        "Index Cond",
+        "Hash Cond",
+        "Merge Cond",
+        "Join Filter",
+        "Filter",
+    ] {
+        if let Some(cond) = text(key) {
+            detail.push(cond.to_string());
+        }
+    }
+    for key in ["Sort Key", "Group Key"] {
+        if let Some(keys) = plan.get(key).and_then(Value::as_array) {
+            let keys: Vec<_> = keys.iter().filter_map(Value::as_str).collect();
+            detail.push(format!("{}: {}", key.to_lowercase(), keys.join(", ")));
+        }
+    }
+
+    // Actual figures are per loop
+    let loops = number("Actual Loops").unwrap_or(1.0);
+    PlanNode {
+        label,
+        detail: detail.join(", "),
+        cost: number("Total Cost"),
+        rows: number("Actual Rows")
+            .map(|rows| rows * loops)
+            .or(number("Plan Rows")),
+        time: number("Actual Total Time").map(|time| time * loops),
+        children: plan
+            .get("Plans")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(postgres_node)
+            .collect(),
+    }
+}
+
+/// `EXPLAIN FORMAT=JSON`: nested operations such as `query_block`,
+/// `nested_loop` and `table`, each with its own `cost_info`
+fn mysql_children(value: &Value) -> Vec<PlanNode> {
+    let Some(fields) = value.as_object() else {
+        return Vec::new();
+    };
+    fields
+        .iter()
+        .filter_map(|(key, value)| match value {
+            Value::Object(_) if key != "cost_info" => Some(mysql_node(key, value)),
+            // nested_loop, query_specifications: lists of operations
+            Value::Array(items) if items.iter().any(Value::is_object) => Some(PlanNode {
+                label: key.replace('_', " "),
+                children: items.iter().flat_map(mysql_children).collect(),
+                ..Default::default()
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn mysql_node(key: &str, value: &Value) -> PlanNode {
+    let text = |key: &str| value.get(key).and_then(Value::as_str);
+    // Costs are strings, row counts numbers
+    let number =
+        |value: Option<&Value>| value.and_then(|v| v.as_f64().or_else(|| v.as_str()?.parse().ok()));
+    let cost_info = |key: &str| number(value.get("cost_info").and_then(|c| c.get(key)));
+
+    let mut label = key.replace('_', " ");
+    if let Some(id) = value.get("select_id") {
+        label = format!("{} #{}", label, id);
+    }
+    if let Some(table) = text("table_name") {
+        label = format!("{} {}", label, table);
+    }
+
+    let mut detail = Vec::new();
+    if let Some(access) = text("access_type") {
+        detail.push(access.to_string());
+    }
+    if let Some(index) = text("key") {
+        detail.push(format!("using {}", index));
+    }
+    if let Some(cond) = text("attached_condition") {
+        detail.push(cond.to_string());
+    }
+    for (flag, name) in [
+        ("using_filesort", "filesort"),
+        ("using_temporary_table", "temporary table"),
+    ] {
+        if value.get(flag).and_then(Value::as_bool) == Some(true) {
+            detail.push(name.to_string());
+        }
+    }
+
+    // A table's own cost is reading plus evaluating; prefix_cost adds the tables before it
+    let table_cost = cost_info("read_cost")
+        .zip(cost_info("eval_cost"))
+        .map(|(read, eval)| read + eval);
+    PlanNode {
+        label,
+        detail: detail.join(", "),
+        cost: cost_info("query_cost")
+            .or(table_cost)
+            .or(cost_info("sort_cost")),
+        rows: number(value.get("rows_produced_per_join"))
+            .or(number(value.get("rows_examined_per_scan"))),
+        time: None,
+        children: mysql_children(value),
+    }
+}
+
+/// `EXPLAIN ANALYZE` tree text:
+/// `-> Filter: (t.a > 1)  (cost=0.35 rows=1) (actual time=0.02..0.03 rows=3 loops=1)`
+fn mysql_tree(text: &str) -> Vec<PlanNode> {
+    indented_tree(text.lines().filter_map(|line| {
+        let trimmed = line.trim_start();
+        let step = trimmed.strip_prefix("-> ")?;
+        Some((line.len() - trimmed.len(), mysql_tree_node(step)))
+    }))
+}
+
+fn mysql_tree_node(step: &str) -> PlanNode {
+    let (label, stats) = step.split_once("  (").unwrap_or((step, ""));
+    let mut node = PlanNode {
+        label: label.to_string(),
+        ..Default::default()
+    };
+
+    // Estimated rows come first, actual ones after "actual"
+    let mut analyzed = false;
+    let (mut rows, mut time, mut loops) = (None, None, 1.0);
+    for token in stats.split(['(', ')', ' ']) {
+        match token.split_once('=') {
+            Some(("cost", value)) => node.cost = value.parse().ok(),
+            Some(("rows", value)) if analyzed => rows = value.parse::<f64>().ok(),
+            Some(("rows", value)) => node.rows = value.parse().ok(),
+            // First row..last row
+            Some(("time", value)) => {
+                time = value
+                    .rsplit("..")
+                    .next()
+                    .and_then(|t| t.parse::<f64>().ok())
+            }
+            Some(("loops", value)) => loops = value.parse().unwrap_or(1.0),
+            None if token == "actual" => analyzed = true,
+            _ => {}
+        }
+    }
+    // Actual figures are per loop
+    if let Some(rows) = rows {
+        node.rows = Some(rows * loops);
+    }
+    node.time = time.map(|t| t * loops);
+    node
+}
+
+/// `EXPLAIN PLAN json = 1`: `[{"Plan": {"Node Type": ..., "Plans": [...]}}]`,
+/// falling back to the indented text of servers without JSON plans
+fn clickhouse_plan(text: &str) -> Vec<PlanNode> {
+    match serde_json::from_str::<Value>(text) {
+        Ok(doc) => doc
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.get("Plan"))
+            .map(clickhouse_node)
+            .collect(),
+        Err(_) => indented_tree(text.lines().filter(|l| !l.trim().is_empty()).map(|line| {
+            let label = line.trim_start();
+            let node = PlanNode {
+                label: label.to_string(),
+                ..Default::default()
+            };
+            (line.len() - label.len(), node)
+        })),
+    }
+}
+
+fn clickhouse_node(plan: &Value) -> PlanNode {
+    let text = |key: &str| plan.get(key).and_then(Value::as_str);
+
+    let mut detail = Vec::new();
+    if let Some(description) = text("Description") {
+        detail.push(description.to_string());
+    }
+    for index in plan
+        .get("Indexes")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let field = |key: &str| index.get(key).map(|v| v.to_string()).unwrap_or_default();
+        detail.push(format!(
+            "{} {}/{} granules",
+            index.get("Type").and_then(Value::as_str).unwrap_or("index"),
+            field("Selected Granules"),
+            field("Initial Granules")
+        ));
+    }
+
+    PlanNode {
+        label: text("Node Type").unwrap_or("?").to_string(),
+        detail: detail.join(", "),
+        children: plan
+            .get("Plans")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(clickhouse_node)
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// `EXPLAIN PIPELINE`: `(Step)` lines, each followed by its processors
+/// at the same indent, with the steps feeding it indented below
+fn clickhouse_pipeline(text: &str) -> Vec<PlanNode> {
+    let mut steps: Vec<(usize, PlanNode)> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if let Some(step) = trimmed.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            let node = PlanNode {
+                label: step.to_string(),
+                ..Default::default()
+            };
+            steps.push((indent, node));
+        } else if !trimmed.is_empty()
+            && let Some((_, step)) = steps.last_mut()
+        {
+            if !step.detail.is_empty() {
+                step.detail.push_str(", ");
+            }
+            step.detail.push_str(trimmed);
+        }
+    }
+    indented_tree(steps)
+}
+
+/// `EXPLAIN QUERY PLAN`: rows of (id, parent, notused, detail). A row may
+/// come after its parent's later siblings, so the tree follows `parent`.
+fn sqlite_plan(columns: &[String], rows: &[Vec<CellValue>]) -> Result<Vec<PlanNode>> {
+    fn children(steps: &[(i64, i64, String)], parent: i64) -> Vec<PlanNode> {
+        steps
+            .iter()
+            .filter(|(_, p, _)| *p == parent)
+            .map(|(id, _, detail)| PlanNode {
+                label: detail.clone(),
+                children: children(steps, *id),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    let column = |name: &str| columns.iter().position(|c| c == name);
+    let (Some(id), Some(parent), Some(detail)) = (column("id"), column("parent"), column("detail"))
+    else {
+        return Err(SqliError::Query(
+            "Unexpected EXPLAIN QUERY PLAN columns".to_string(),
+        ));
+    };
+    let number = |row: &[CellValue], i: usize| row.get(i)?.to_string().parse().ok();
+
+    let steps: Vec<(i64, i64, String)> = rows
+        .iter()
+        .filter_map(|row| {
+            let detail = row
+                .get(detail)
+                .map(CellValue::to_string)
+                .unwrap_or_default();
+            Some((number(row, id)?, number(row, parent)?, detail))
+        })
+        .collect();
+    // Ids start at 1, and top-level steps have parent 0
+    Ok(children(&steps, 0))
+}
+
+/// Build a tree from nodes in order, each a child of the closest node
+/// before it with a smaller indent
+fn indented_tree(nodes: impl IntoIterator<Item = (usize, PlanNode)>) -> Vec<PlanNode> {
+    fn attach(open: &mut [(usize, PlanNode)], roots: &mut Vec<PlanNode>, node: PlanNode) {
+        match open.last_mut() {
+            Some((_, parent)) => parent.children.push(node),
+            None => roots.push(node),
+        }
+    }
+
+    let mut roots = Vec::new();
+    // The path from a root down to the last node
+    let mut open: Vec<(usize, PlanNode)> = Vec::new();
+    for (indent, node) in nodes {
+        while open.last().is_some_and(|(top, _)| *top >= indent)
+            && let Some((_, done)) = open.pop()
+        {
+            attach(&mut open, &mut roots, done);
+        }
+        open.push((indent, node));
+    }
+    while let Some((_, done)) = open.pop() {
+        attach(&mut open, &mut roots, done);
+    }
+    roots
+}
+
+/// A plan flattened into lines for display, with collapsible nodes
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub title: String,
+    pub lines: Vec<PlanLine>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlanLine {
+    pub depth: usize,
+    /// The node, without its children
+    pub node: PlanNode,
+    pub has_children: bool,
+    pub collapsed: bool,
+    /// Share of the plan's time, or cost when not analyzed, spent in
+    /// the node itself rather than its children
+    pub share: Option<f64>,
+}
+
+impl PlanLine {
+    pub fn is_hot(&self) -> bool {
+        self.share.is_some_and(|share| share >= HOT_SHARE)
+    }
+}
+
+impl Plan {
+    pub fn new(title: &str, nodes: Vec<PlanNode>) -> Self {
+        // Time is what was measured; cost is only the planner's guess at it
+        let timed = nodes.iter().any(PlanNode::timed);
+        let mut lines = Vec::new();
+        let total: f64 = nodes
+            .into_iter()
+            .map(|node| Self::flatten(node, 0, timed, &mut lines))
+            .sum();
+
+        // `share` holds each node's own weight until the total is known
+        for line in &mut lines {
+            line.share = line.share.filter(|_| total > 0.0).map(|own| own / total);
+        }
+        Self {
+            title: title.to_string(),
+            lines,
+        }
+    }
+
+    /// Add `node` and its children in preorder, returning the node's weight
+    /// including children: its cost or time, or its children's when it has none
+    fn flatten(mut node: PlanNode, depth: usize, timed: bool, lines: &mut Vec<PlanLine>) -> f64 {
+        let children = std::mem::take(&mut node.children);
+        let own = if timed { node.time } else { node.cost };
+        let index = lines.len();
+        lines.push(PlanLine {
+            depth,
+            node,
+            has_children: !children.is_empty(),
+            collapsed: false,
+            share: None,
+        });
+
+        let below: f64 = children
+            .into_iter()
+            .map(|child| Self::flatten(child, depth + 1, timed, lines))
+            .sum();
+        let weight = own.unwrap_or(below);
+        lines[index].share = Some((weight - below).max(0.0));
+        weight
+    }
+
+    /// Indexes of the lines not hidden under a collapsed node
+    pub fn visible(&self) -> Vec<usize> {
+        let mut visible = Vec::new();
+        let mut hidden_below = None;
+        for (i, line) in self.lines.iter().enumerate() {
+            if hidden_below.is_some_and(|depth| line.depth > depth) {
+                continue;
+            }
+            hidden_below = (line.collapsed && line.has_children).then_some(line.depth);
+            visible.push(i);
+        }
+        visible
+    }
+}
+
+impl PlanNode {
+    fn timed(&self) -> bool {
+        self.time.is_some() || self.children.iter().any(PlanNode::timed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(columns: &[&str], rows: Vec<Vec<CellValue>>) -> QueryResult {
+        QueryResult::Select {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            column_types: Vec::new(),
+            rows,
+        }
+    }
+
+    fn text_result(lines: &[&str]) -> QueryResult {
+        select(
+            &["QUERY PLAN"],
+            lines.iter().map(|l| vec![CellValue::from(*l)]).collect(),
+        )
+    }
+
+    fn labels(nodes: &[PlanNode]) -> Vec<&str> {
+        nodes.iter().map(|n| n.label.as_str()).collect()
+    }
+
+    #[test]
+    fn test_explain_query() {
+        let pg = explain_query(&DatabaseType::Postgres, ExplainMode::Analyze, "SELECT 1");
+        assert_eq!(pg.unwrap(), "EXPLAIN (ANALYZE, FORMAT JSON) SELECT 1");
+        let sqlite = explain_query(&DatabaseType::Sqlite, ExplainMode::Plan, "SELECT 1");
+        assert_eq!(sqlite.unwrap(), "EXPLAIN QUERY PLAN SELECT 1");
+        assert!(explain_query(&DatabaseType::Sqlite, ExplainMode::Pipeline, "SELECT 1").is_err());
+        assert!(explain_query(&DatabaseType::Cassandra, ExplainMode::Plan, "SELECT 1").is_err());
+        assert!(
+            explain_query(&DatabaseType::MySql, ExplainMode::Plan, "explain SELECT 1").is_err()
+        );
+    }
+
+    #[test]
+    fn test_postgres_analyze() {
+        let json = r#"[{"Plan": {"Node Type": "Hash Join", "Join Type": "Left",
+            "Hash Cond": "(a.id = b.a_id)", "Total Cost": 40.0, "Plan Rows": 10,
+            "Actual Total Time": 5.0, "Actual Rows": 8, "Actual Loops": 1,
+            "Plans": [
+              {"Node Type": "Seq Scan", "Relation Name": "a", "Alias": "a", "Total Cost": 10.0,
+               "Plan Rows": 100, "Actual Total Time": 1.0, "Actual Rows": 100, "Actual Loops": 1},
+              {"Node Type": "Index Scan", "Relation Name": "b", "Alias": "bb", "Index Name": "b_idx",
+               "Total Cost": 2.0, "Plan Rows": 1, "Actual Total Time": 0.5, "Actual Rows": 2,
+               "Actual Loops": 4}
+            ]}, "Planning Time": 0.1, "Execution Time": 5.1}]"#;
+        let result = select(
+            &["QUERY PLAN"],
+            vec![vec![CellValue::Json(json.to_string())]],
+        );
+        let nodes = parse_plan(&DatabaseType::Postgres, ExplainMode::Analyze, &result).unwrap();
+
+        let join = &nodes[0];
+        assert_eq!(join.label, "Hash Join (Left)");
+        assert_eq!(join.detail, "(a.id = b.a_id)");
+        assert_eq!(
+            (join.cost, join.rows, join.time),
+            (Some(40.0), Some(8.0), Some(5.0))
+        );
+        assert_eq!(labels(&join.children), vec!["Seq Scan", "Index Scan"]);
+        assert_eq!(join.children[1].detail, "on b bb, using b_idx");
+        // Actual rows and time are per loop
+        assert_eq!(join.children[1].rows, Some(8.0));
+        assert_eq!(join.children[1].time, Some(2.0));
+    }
+
+    #[test]
+    fn test_mysql_json() {
+        let json = r#"{"query_block": {"select_id": 1, "cost_info": {"query_cost": "2.00"},
+            "nested_loop": [
+              {"table": {"table_name": "a", "access_type": "ALL", "rows_produced_per_join": 3,
+                "cost_info": {"read_cost": "0.25", "eval_cost": "0.30"}}},
+              {"table": {"table_name": "b", "access_type": "ref", "key": "a_id",
+                "rows_examined_per_scan": 1,
+                "cost_info": {"read_cost": "1.00", "eval_cost": "0.50"}}}
+            ]}}"#;
+        let nodes = parse_plan(
+            &DatabaseType::MySql,
+            ExplainMode::Plan,
+            &text_result(&[json]),
+        )
+        .unwrap();
+
+        assert_eq!(labels(&nodes), vec!["query block #1"]);
+        assert_eq!(nodes[0].cost, Some(2.0));
+        let nested = &nodes[0].children[0];
+        assert_eq!(nested.label, "nested loop");
+        assert_eq!(labels(&nested.children), vec!["table a", "table b"]);
+        assert_eq!(nested.children[0].rows, Some(3.0));
+        assert_eq!(nested.children[1].detail, "ref, using a_id");
+        assert_eq!(nested.children[1].cost, Some(1.5));
+    }
+
+    #[test]
+    fn test_mysql_analyze_tree() {
+        let tree = "-> Nested loop inner join  (cost=0.70 rows=1) (actual time=0.04..0.05 rows=3 loops=1)\n    -> Table scan on a  (cost=0.35 rows=3) (actual time=0.02..0.03 rows=3 loops=1)\n    -> Index lookup on b using a_id (a_id=a.id)  (cost=0.12 rows=1) (actual time=0.125..0.25 rows=1 loops=3)\n";
+        let nodes = parse_plan(
+            &DatabaseType::MySql,
+            ExplainMode::Analyze,
+            &text_result(&[tree]),
+        )
+        .unwrap();
+
+        assert_eq!(labels(&nodes), vec!["Nested loop inner join"]);
+        assert_eq!(nodes[0].children.len(), 2);
+        let lookup = &nodes[0].children[1];
+        assert_eq!(lookup.label, "Index lookup on b using a_id (a_id=a.id)");
+        assert_eq!(lookup.cost, Some(0.12));
+        assert_eq!(lookup.rows, Some(3.0));
+        assert_eq!(lookup.time, Some(0.75));
+    }
+
+    #[test]
+    fn test_sqlite_plan() {
+        let row = |id: i64, parent: i64, detail: &str| {
+            vec![
+                CellValue::from(id),
+                CellValue::from(parent),
+                CellValue::from(0),
+                CellValue::from(detail),
+            ]
+        };
+        let result = select(
+            &["id", "parent", "notused", "detail"],
+            vec![
+                row(2, 0, "SCAN a"),
+                row(5, 0, "SEARCH b USING INDEX b_idx (a_id=?)"),
+                row(9, 0, "USE TEMP B-TREE FOR ORDER BY"),
+                row(11, 5, "CORRELATED SCALAR SUBQUERY 1"),
+            ],
+        );
+        let nodes = parse_plan(&DatabaseType::Sqlite, ExplainMode::Plan, &result).unwrap();
+        assert_eq!(
+            labels(&nodes),
+            vec![
+                "SCAN a",
+                "SEARCH b USING INDEX b_idx (a_id=?)",
+                "USE TEMP B-TREE FOR ORDER BY"
+            ]
+        );
+        assert_eq!(
+            labels(&nodes[1].children),
+            vec!["CORRELATED SCALAR SUBQUERY 1"]
+        );
+    }
+
+    #[test]
+    fn test_clickhouse_plan_json() {
+        let lines = [
+            "[",
+            "  {",
+            "    \"Plan\": {",
+            "      \"Node Type\": \"Expression\",",
+            "      \"Description\": \"(Projection + Before ORDER BY)\",",
+            "      \"Plans\": [",
+            "        {",
+            "          \"Node Type\": \"ReadFromMergeTree\",",
+            "          \"Description\": \"default.t\",",
+            "          \"Indexes\": [{\"Type\": \"PrimaryKey\", \"Initial Granules\": 12, \"Selected Granules\": 3}]",
+            "        }",
+            "      ]",
+            "    }",
+            "  }",
+            "]",
+        ];
+        let nodes = parse_plan(
+            &DatabaseType::ClickHouse,
+            ExplainMode::Plan,
+            &text_result(&lines),
+        )
+        .unwrap();
+        assert_eq!(labels(&nodes), vec!["Expression"]);
+        let read = &nodes[0].children[0];
+        assert_eq!(read.label, "ReadFromMergeTree");
+        assert_eq!(read.detail, "default.t, PrimaryKey 3/12 granules");
+    }
+
+    #[test]
+    fn test_clickhouse_pipeline() {
+        let lines = [
+            "(Expression)",
+            "ExpressionTransform × 4",
+            "  (Aggregating)",
+            "  Resize 4 → 4",
+            "    AggregatingTransform × 4",
+            "    (ReadFromMergeTree)",
+            "    MergeTreeThread × 4 0 → 1",
+        ];
+        let nodes = parse_plan(
+            &DatabaseType::ClickHouse,
+            ExplainMode::Pipeline,
+            &text_result(&lines),
+        )
+        .unwrap();
+        assert_eq!(labels(&nodes), vec!["Expression"]);
+        assert_eq!(nodes[0].detail, "ExpressionTransform × 4");
+        let aggregating = &nodes[0].children[0];
+        assert_eq!(aggregating.detail, "Resize 4 → 4, AggregatingTransform × 4");
+        assert_eq!(labels(&aggregating.children), vec!["ReadFromMergeTree"]);
+    }
+
+    #[test]
+    fn test_plan_shares_and_collapse() {
+        let leaf = |label: &str, cost: f64| PlanNode {
+            label: label.to_string(),
+            cost: Some(cost),
+            ..Default::default()
+        };
+        let root = PlanNode {
+            label: "Sort".to_string(),
+            cost: Some(100.0),
+            children: vec![leaf("Seq Scan", 90.0), leaf("Index Scan", 5.0)],
+            ..Default::default()
+        };
+        let mut plan = Plan::new("Plan", vec![root]);
+
+        let shares: Vec<_> = plan.lines.iter().map(|l| l.share.unwrap()).collect();
+        assert_eq!(shares, vec![0.05, 0.9, 0.05]);
+        let hot: Vec<_> = plan.lines.iter().map(PlanLine::is_hot).collect();
+        assert_eq!(hot, vec![false, true, false]);
+
+        assert_eq!(plan.visible(), vec![0, 1, 2]);
+        plan.lines[0].collapsed = true;
+        assert_eq!(plan.visible(), vec![0]);
+    }
+
+    #[test]
+    fn test_plan_without_metrics_has_no_shares() {
+        let node = PlanNode {
+            label: "SCAN a".to_string(),
+            ..Default::default()
+        };
+        let plan = Plan::new("Plan", vec![node]);
+        assert!(plan.lines.iter().all(|l| l.share.is_none() && !l.is_hot()));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::lexer::{self, Dialect, TokenKind};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// The oldest entries are dropped beyond this many
+const MAX_ENTRIES: usize = 5000;
+
+/// A statement that ran, as recorded in the history file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub query: String,
+    pub connection: String,
+    pub database: String,
+    /// Local time the run finished, `%Y-%m-%d %H:%M:%S`
+    pub timestamp: String,
+    pub duration_ms: u64,
+    /// Rows affected, or rows returned by the time the run finished
+    /// (the first page of a longer result)
+    pub rows: u64,
+    /// More rows followed than `rows` counts
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub more_rows: bool,
+    /// What the statement failed with, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// History kept in `~/.config/sqli/history.jsonl`, one JSON entry per line
+#[derive(Debug, Default)]
+pub struct HistoryStore {
+    /// Oldest first
+    pub entries: Vec<HistoryEntry>,
+    path: Option<PathBuf>,
+}
+
+impl HistoryStore {
+    pub fn load() -> Self {
+        let path = Self::get_history_path();
+        let entries = match &path {
+            Some(p) if p.exists() => match fs::read_to_string(p) {
+                Ok(content) => Self::parse(&content),
+                Err(_) => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+
+        let mut store = Self { entries, path };
+        if store.entries.len() > MAX_ENTRIES {
+            let excess = store.entries.len() - MAX_ENTRIES;
+            store.entries.drain(..excess);
+            if let Err(e) = store.rewrite() {
+                crate::debug_log!("Failed to trim history: {}", e);
+            }
+        }
+        store
+    }
+
+    fn get_history_path() -> Option<PathBuf> {
+        // Next to templates.sql
+        dirs::home_dir().map(|h| h.join(".config").join("sqli").join("history.jsonl"))
+    }
+
+    /// Record an entry, appending it to the history file. Once the history
+    /// is full the oldest entry is dropped and the file rewritten instead.
+    pub fn add(&mut self, entry: HistoryEntry) -> io::Result<()> {
+        let line = serde_json::to_string(&entry)?;
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+            return self.rewrite();
+        }
+
+        let path = self.path.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "Could not determine config directory",
+            )
+        })?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = Self::open_options().append(true).open(path)?;
+        writeln!(file, "{}", line)
+    }
+
+    fn rewrite(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut content = String::new();
+        for entry in &self.entries {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+        Self::open_options()
+            .write(true)
+            .truncate(true)
+            .open(path)?
+            .write_all(content.as_bytes())
+    }
+
+    fn open_options() -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options.create(true);
+        // Statements can hold passwords and personal data
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+    }
+
+    /// Entries newest first, of one connection when given, whose query
+    /// fuzzy-matches `filter`
+    pub fn search(&self, filter: &str, connection: Option<&str>) -> Vec<&HistoryEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|e| connection.is_none_or(|c| e.connection == c))
+            .filter(|e| fuzzy_match(filter, &e.query))
+            .collect()
+    }
+
+    /// One entry per line; lines that don't parse, such as one cut short
+    /// by a crash, are skipped
+    fn parse(content: &str) -> Vec<HistoryEntry> {
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+}
+
+/// Replace the string literals from the first `PASSWORD` or `IDENTIFIED`
+/// keyword on with `'***'`, so passwords set by a statement are not stored.
+/// Other literals after it, such as a user name, are replaced as well.
+pub fn redact_passwords(query: &str, dialect: Dialect) -> String {
+    let mut redacting = false;
+    let mut result = String::with_capacity(query.len());
+    for token in lexer::tokenize(query, dialect) {
+        redacting |= token.is_keyword("PASSWORD") || token.is_keyword("IDENTIFIED");
+        if redacting && token.kind == TokenKind::String {
+            result.push_str("'***'");
+        } else {
+            result.push_str(token.text);
+        }
+    }
+    result
+}
+
+/// Whether the characters of `pattern`, ignoring whitespace, appear in
+/// `text` in order. Case-insensitive.
+pub fn fuzzy_match(pattern: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .all(|p| text.any(|c| c == p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::DatabaseType;
+
+    fn entry(query: &str, connection: &str) -> HistoryEntry {
+        HistoryEntry {
+            query: query.to_string(),
+            connection: connection.to_string(),
+            database: "app".to_string(),
+            timestamp: "2026-10-17 09:30:00".to_string(),
+            duration_ms: 12,
+            rows: 3,
+            more_rows: false,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_skips_broken_lines() {
+        let ok = serde_json::to_string(&entry("SELECT 1", "local")).unwrap();
+        let content = format!("{}\n{{\"query\": \"SELECT\n\n{}\n", ok, ok);
+
+        let entries = HistoryStore::parse(&content);
+        assert_eq!(entries, vec![entry("SELECT 1", "local"); 2]);
+    }
+
+    #[test]
+    fn test_error_roundtrip() {
+        let mut failed = entry("SELEC 1", "local");
+        failed.error = Some("syntax error at or near \"SELEC\"".to_string());
+        let line = serde_json::to_string(&failed).unwrap();
+        assert_eq!(HistoryStore::parse(&line), vec![failed]);
+
+        // Successful entries leave the field out
+        let line = serde_json::to_string(&entry("SELECT 1", "local")).unwrap();
+        assert!(!line.contains("error"));
+    }
+
+    #[test]
+    fn test_more_rows_roundtrip() {
+        let mut partial = entry("SELECT * FROM big", "local");
+        partial.rows = 1000;
+        partial.more_rows = true;
+        let line = serde_json::to_string(&partial).unwrap();
+        assert_eq!(HistoryStore::parse(&line), vec![partial]);
+
+        let line = serde_json::to_string(&entry("SELECT 1", "local")).unwrap();
+        assert!(!line.contains("more_rows"));
+    }
+
+    #[test]
+    fn test_redact_passwords() {
+        let pg = Dialect::from(&DatabaseType::Postgres);
+        let mysql = Dialect::from(&DatabaseType::MySql);
+        assert_eq!(
+            redact_passwords("ALTER ROLE app WITH PASSWORD 'hunter2'", pg),
+            "ALTER ROLE app WITH PASSWORD '***'"
+        );
+        assert_eq!(
+            redact_passwords("CREATE USER 'app'@'%' IDENTIFIED BY 'it''s'", mysql),
+            "CREATE USER 'app'@'%' IDENTIFIED BY '***'"
+        );
+        assert_eq!(
+            redact_passwords("SET PASSWORD FOR 'app'@'%' = \"hunter2\"", mysql),
+            "SET PASSWORD FOR '***'@'***' = '***'"
+        );
+        // Words in strings or comments don't start redacting
+        assert_eq!(
+            redact_passwords("SELECT 'password', 'x' -- password\n", pg),
+            "SELECT 'password', 'x' -- password\n"
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert!(fuzzy_match("", "SELECT 1"));
+        assert!(fuzzy_match("selusr", "SELECT * FROM users"));
+        assert!(fuzzy_match("from users", "SELECT * FROM users"));
+        assert!(!fuzzy_match("usersfrom", "SELECT * FROM users"));
+        assert!(!fuzzy_match("orders", "SELECT * FROM users"));
+    }
+
+    #[test]
+    fn test_add_drops_oldest_beyond_max() {
+        let path = std::env::temp_dir().join(format!("sqli-history-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut store = HistoryStore {
+            entries: vec![entry("SELECT 1", "local"); MAX_ENTRIES],
+            path: Some(path.clone()),
+        };
+        store.add(entry("SELECT 2", "local")).unwrap();
+
+        assert_eq!(store.entries.len(), MAX_ENTRIES);
+        assert_eq!(store.entries.last().unwrap().query, "SELECT 2");
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(HistoryStore::parse(&content), store.entries);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_search() {
+        let store = HistoryStore {
+            entries: vec![
+                entry("SELECT * FROM users", "local"),
+                entry("SELECT * FROM orders", "prod"),
+                entry("DELETE FROM users WHERE id = 1", "local"),
+            ],
+            path: None,
+        };
+
+        let queries = |found: Vec<&HistoryEntry>| {
+            found
+                .into_iter()
+                .map(|e| e.query.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            queries(store.search("users", None)),
+            vec!["DELETE FROM users WHERE id = 1", "SELECT * FROM users"]
+        );
+        assert_eq!(
+            queries(store.search("", Some("prod"))),
+            vec!["SELECT * FROM orders"]
+        );
+        assert!(store.search("orders", Some("local")).is_empty());
+    }
+}
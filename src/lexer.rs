@@ -0,0 +1,711 @@
+//! SQL tokenizer shared by statement splitting, comment stripping,
+//! statement classification and completion.
+//!
+//! The lexer only knows enough of each dialect to find where strings,
+//! quoted identifiers, comments and statements begin and end.
+
+use crate::controller::DatabaseType;
+
+use std::ops::Range;
+
+/// Lexical rules that differ between backends.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Dialect {
+    /// `$$ ... $$` string bodies (PostgreSQL, CQL)
+    dollar_quotes: bool,
+    /// Tagged dollar quotes: `$body$ ... $body$` (PostgreSQL)
+    dollar_tags: bool,
+    /// `E'...'` strings with backslash escapes (PostgreSQL)
+    escape_strings: bool,
+    /// Backslash escapes in every string (MySQL, ClickHouse)
+    backslash_escapes: bool,
+    /// `"..."` is a string rather than an identifier (MySQL)
+    double_quote_strings: bool,
+    /// `` `...` `` identifiers
+    backticks: bool,
+    /// `[...]` identifiers (SQLite)
+    brackets: bool,
+    /// `# ...` line comments (MySQL, ClickHouse)
+    hash_comments: bool,
+    /// `// ...` line comments (CQL)
+    slash_comments: bool,
+    /// `/* /* ... */ */` comments nest (PostgreSQL)
+    nested_comments: bool,
+    /// `--` only starts a comment when followed by whitespace (MySQL)
+    dash_comment_space: bool,
+    /// The client-side `DELIMITER` command (MySQL)
+    delimiter_command: bool,
+}
+
+impl From<&DatabaseType> for Dialect {
+    fn from(db_type: &DatabaseType) -> Self {
+        match db_type {
+            DatabaseType::Postgres => Dialect {
+                dollar_quotes: true,
+                dollar_tags: true,
+                escape_strings: true,
+                nested_comments: true,
+                ..Default::default()
+            },
+            DatabaseType::MySql => Dialect {
+                backslash_escapes: true,
+                double_quote_strings: true,
+                backticks: true,
+                hash_comments: true,
+                dash_comment_space: true,
+                delimiter_command: true,
+                ..Default::default()
+            },
+            DatabaseType::Cassandra => Dialect {
+                dollar_quotes: true,
+                slash_comments: true,
+                ..Default::default()
+            },
+            DatabaseType::ClickHouse => Dialect {
+                backslash_escapes: true,
+                backticks: true,
+                hash_comments: true,
+                ..Default::default()
+            },
+            DatabaseType::Sqlite => Dialect {
+                backticks: true,
+                brackets: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Whitespace,
+    Comment,
+    /// Keyword, identifier or number
+    Word,
+    /// `"..."`, `` `...` `` or `[...]` identifier
+    QuotedIdent,
+    /// String literal, including `E'...'` and dollar-quoted bodies
+    String,
+    /// `;`, or the terminator set with `DELIMITER`
+    Terminator,
+    /// A `DELIMITER` line, handled by the client and never sent
+    DelimiterCommand,
+    /// Any other character
+    Punct,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// Byte offset in the input
+    pub start: usize,
+    /// A string, identifier or comment that runs to the end of the input
+    pub unterminated: bool,
+}
+
+impl Token<'_> {
+    /// Whitespace and comments
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenKind::Whitespace | TokenKind::Comment)
+    }
+
+    /// Keyword comparison for `Word` tokens, ignoring case
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    /// Identifier text without quotes; doubled quotes are unescaped
+    pub fn ident(&self) -> Option<String> {
+        match self.kind {
+            TokenKind::Word => Some(self.text.to_string()),
+            TokenKind::QuotedIdent => {
+                let close = match self.text.chars().next()? {
+                    '[' => ']',
+                    c => c,
+                };
+                let inner = &self.text[1..];
+                let inner = inner.strip_suffix(close).unwrap_or(inner);
+                let doubled: String = [close, close].iter().collect();
+                Some(inner.replace(&doubled, &close.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Split `sql` into tokens. Every byte of the input belongs to exactly one token.
+pub fn tokenize(sql: &str, dialect: Dialect) -> Vec<Token<'_>> {
+    let mut lexer = Lexer {
+        sql,
+        pos: 0,
+        dialect,
+        delimiter: ";".to_string(),
+        at_line_start: true,
+    };
+    let mut tokens = Vec::new();
+    while lexer.pos < sql.len() {
+        tokens.push(lexer.next_token());
+    }
+    tokens
+}
+
+/// Split into statements on terminators, dropping `DELIMITER` lines and
+/// statements that hold nothing but whitespace and comments.
+pub fn split_statements(sql: &str, dialect: Dialect) -> Vec<String> {
+    statement_ranges(sql, dialect)
+        .into_iter()
+        .map(|range| sql[range].to_string())
+        .collect()
+}
+
+/// Byte ranges of the statements in `sql`, from the first to the last token
+/// that isn't whitespace or a comment. Terminators are not included.
+pub fn statement_ranges(sql: &str, dialect: Dialect) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+
+    for token in tokenize(sql, dialect) {
+        match token.kind {
+            TokenKind::Terminator | TokenKind::DelimiterCommand => {
+                ranges.extend(current.take());
+            }
+            _ if token.is_trivia() => {}
+            _ => {
+                let end = token.start + token.text.len();
+                current.get_or_insert(token.start..end).end = end;
+            }
+        }
+    }
+    ranges.extend(current);
+
+    ranges
+}
+
+/// Remove comments, keeping their line breaks so line numbers still match.
+/// A comment within a line becomes a single space.
+pub fn strip_comments(sql: &str, dialect: Dialect) -> String {
+    let mut result = String::with_capacity(sql.len());
+    for token in tokenize(sql, dialect) {
+        if token.kind == TokenKind::Comment {
+            let newlines = token.text.matches('\n').count();
+            if newlines == 0 {
+                result.push(' ');
+            } else {
+                result.extend(std::iter::repeat_n('\n', newlines));
+            }
+        } else {
+            result.push_str(token.text);
+        }
+    }
+    result
+}
+
+/// The first keyword of a statement, uppercased
+pub fn first_keyword(sql: &str, dialect: Dialect) -> Option<String> {
+    tokenize(sql, dialect)
+        .into_iter()
+        .find(|t| t.kind == TokenKind::Word)
+        .map(|t| t.text.to_uppercase())
+}
+
+/// DELETE/UPDATE without WHERE, DROP, TRUNCATE and ALTER ... DROP, also as a
+/// data-modifying CTE. Only keywords outside parentheses count, so neither a
+/// `WHERE` in a subquery nor one in a string literal or comment does.
+pub fn is_destructive(sql: &str, dialect: Dialect) -> bool {
+    let tokens: Vec<Token> = tokenize(sql, dialect)
+        .into_iter()
+        .filter(|t| !t.is_trivia())
+        .collect();
+    destructive(&tokens)
+}
+
+fn destructive(tokens: &[Token]) -> bool {
+    let Some(first) = tokens.first() else {
+        return false;
+    };
+    if first.is_keyword("WITH") {
+        return destructive_with(&tokens[1..]);
+    }
+
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i].kind {
+            TokenKind::Punct if tokens[i].text == "(" => i = closing_paren(tokens, i),
+            TokenKind::Word => words.push(&tokens[i]),
+            _ => {}
+        }
+        i += 1;
+    }
+    let has = |keyword: &str| words.iter().any(|t| t.is_keyword(keyword));
+    match words.first().map(|t| t.text.to_uppercase()).as_deref() {
+        Some("DELETE" | "UPDATE") => !has("WHERE"),
+        Some("DROP" | "TRUNCATE") => true,
+        Some("ALTER") => has("DROP"),
+        _ => false,
+    }
+}
+
+/// The CTEs after `WITH`, each body on its own, then the statement they belong to
+fn destructive_with(tokens: &[Token]) -> bool {
+    const STATEMENTS: &[&str] = &[
+        "SELECT", "INSERT", "UPDATE", "DELETE", "MERGE", "VALUES", "TABLE",
+    ];
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        if token.kind == TokenKind::Punct && token.text == "(" {
+            let close = closing_paren(tokens, i);
+            // A column list follows the CTE name, the body `AS [NOT] MATERIALIZED`
+            let body = i > 0
+                && (tokens[i - 1].is_keyword("AS") || tokens[i - 1].is_keyword("MATERIALIZED"));
+            if body && destructive(&tokens[i + 1..close.min(tokens.len())]) {
+                return true;
+            }
+            i = close;
+        } else if STATEMENTS.iter().any(|k| token.is_keyword(k)) {
+            return destructive(&tokens[i..]);
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Index of the `)` closing the `(` at `open`, or the end of an unbalanced input
+fn closing_paren(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.kind != TokenKind::Punct {
+            continue;
+        }
+        match token.text {
+            "(" => depth += 1,
+            ")" => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+struct Lexer<'a> {
+    sql: &'a str,
+    pos: usize,
+    dialect: Dialect,
+    /// Statement terminator, changed by `DELIMITER`
+    delimiter: String,
+    /// Only whitespace precedes `pos` on its line
+    at_line_start: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn rest(&self) -> &'a str {
+        &self.sql[self.pos..]
+    }
+
+    fn peek(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn next_token(&mut self) -> Token<'a> {
+        let start = self.pos;
+        let (kind, unterminated) = self.scan();
+        let text = &self.sql[start..self.pos];
+        self.at_line_start = match text.rfind('\n') {
+            Some(i) => text[i + 1..].chars().all(char::is_whitespace),
+            None => self.at_line_start && text.chars().all(char::is_whitespace),
+        };
+        Token {
+            kind,
+            text,
+            start,
+            unterminated,
+        }
+    }
+
+    /// Advance past one token and return its kind
+    fn scan(&mut self) -> (TokenKind, bool) {
+        let rest = self.rest();
+        let c = self.peek(0).expect("scan is only called before the end");
+        let next = self.peek(1);
+
+        if self.dialect.delimiter_command && self.at_line_start && self.delimiter_line() {
+            return (TokenKind::DelimiterCommand, false);
+        }
+        if !c.is_whitespace() && rest.starts_with(self.delimiter.as_str()) {
+            self.pos += self.delimiter.len();
+            return (TokenKind::Terminator, false);
+        }
+
+        if c.is_whitespace() {
+            self.take_while(char::is_whitespace);
+            return (TokenKind::Whitespace, false);
+        }
+
+        let dash_comment = c == '-'
+            && next == Some('-')
+            && (!self.dialect.dash_comment_space || self.peek(2).is_none_or(char::is_whitespace));
+        let hash_comment = c == '#' && self.dialect.hash_comments;
+        let slash_comment = c == '/' && next == Some('/') && self.dialect.slash_comments;
+        if dash_comment || hash_comment || slash_comment {
+            self.take_while(|c| c != '\n');
+            return (TokenKind::Comment, self.pos == self.sql.len());
+        }
+        if c == '/' && next == Some('*') {
+            return (TokenKind::Comment, !self.block_comment());
+        }
+
+        match c {
+            '\'' => (
+                TokenKind::String,
+                !self.quoted('\'', self.dialect.backslash_escapes),
+            ),
+            'E' | 'e' if next == Some('\'') && self.dialect.escape_strings => {
+                self.pos += 1;
+                (TokenKind::String, !self.quoted('\'', true))
+            }
+            '"' if self.dialect.double_quote_strings => {
+                (TokenKind::String, !self.quoted('"', true))
+            }
+            '"' => {
+                let closed = self.quoted('"', self.dialect.backslash_escapes);
+                (TokenKind::QuotedIdent, !closed)
+            }
+            '`' if self.dialect.backticks => (TokenKind::QuotedIdent, !self.quoted('`', false)),
+            '[' if self.dialect.brackets => {
+                self.pos += 1;
+                let closed = self.skip_past("]");
+                (TokenKind::QuotedIdent, !closed)
+            }
+            '$' if self.dialect.dollar_quotes => match self.dollar_tag() {
+                Some(tag) => {
+                    self.pos += tag.len();
+                    let closed = self.skip_past(tag);
+                    (TokenKind::String, !closed)
+                }
+                None => {
+                    self.pos += 1;
+                    (TokenKind::Punct, false)
+                }
+            },
+            c if is_word_char(c) => {
+                self.take_while(|c| is_word_char(c) || c == '$');
+                (TokenKind::Word, false)
+            }
+            c => {
+                self.pos += c.len_utf8();
+                (TokenKind::Punct, false)
+            }
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) {
+        let len = self.rest().find(|c| !f(c)).unwrap_or(self.rest().len());
+        self.pos += len;
+    }
+
+    /// Move past `end`, or to the end of input. Returns whether `end` was found.
+    fn skip_past(&mut self, end: &str) -> bool {
+        match self.rest().find(end) {
+            Some(i) => {
+                self.pos += i + end.len();
+                true
+            }
+            None => {
+                self.pos = self.sql.len();
+                false
+            }
+        }
+    }
+
+    /// Quoted text starting at the opening quote. A doubled quote stands for
+    /// itself; with `backslash` a backslash escapes the next character.
+    fn quoted(&mut self, quote: char, backslash: bool) -> bool {
+        self.pos += quote.len_utf8();
+        let mut chars = self.rest().char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if backslash && c == '\\' {
+                chars.next();
+            } else if c == quote {
+                if chars.peek().is_some_and(|&(_, c)| c == quote) {
+                    chars.next();
+                } else {
+                    self.pos += i + c.len_utf8();
+                    return true;
+                }
+            }
+        }
+        self.pos = self.sql.len();
+        false
+    }
+
+    fn block_comment(&mut self) -> bool {
+        self.pos += 2;
+        let mut depth = 1;
+        while depth > 0 {
+            let rest = self.rest();
+            let close = rest.find("*/");
+            let open = rest.find("/*").filter(|_| self.dialect.nested_comments);
+            match (open, close) {
+                (Some(o), Some(c)) if o < c => {
+                    self.pos += o + 2;
+                    depth += 1;
+                }
+                (_, Some(c)) => {
+                    self.pos += c + 2;
+                    depth -= 1;
+                }
+                (_, None) => {
+                    self.pos = self.sql.len();
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// `$$` or `$tag$` at the current position. `$1` parameters don't match.
+    fn dollar_tag(&self) -> Option<&'a str> {
+        let rest = self.rest();
+        let end = rest[1..].find('$')? + 1;
+        let tag = &rest[1..end];
+        let valid = tag.is_empty()
+            || (self.dialect.dollar_tags
+                && !tag.starts_with(|c: char| c.is_ascii_digit())
+                && tag.chars().all(|c| c.is_alphanumeric() || c == '_'));
+        valid.then(|| &rest[..=end])
+    }
+
+    /// `DELIMITER <terminator>` up to the end of the line
+    fn delimiter_line(&mut self) -> bool {
+        let rest = self.rest();
+        let line = rest.split('\n').next().unwrap_or(rest);
+        let Some((keyword, arg)) = line.trim_end().split_once(char::is_whitespace) else {
+            return false;
+        };
+        let arg = arg.trim();
+        if !keyword.eq_ignore_ascii_case("DELIMITER") || arg.is_empty() {
+            return false;
+        }
+        self.delimiter = arg.to_string();
+        self.pos += line.len();
+        true
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pg() -> Dialect {
+        Dialect::from(&DatabaseType::Postgres)
+    }
+
+    fn mysql() -> Dialect {
+        Dialect::from(&DatabaseType::MySql)
+    }
+
+    #[test]
+    fn test_split_basic() {
+        let statements = split_statements("SELECT 1; SELECT 'a;b'; ;", Dialect::default());
+        assert_eq!(statements, vec!["SELECT 1", "SELECT 'a;b'"]);
+    }
+
+    #[test]
+    fn test_statement_ranges() {
+        let sql = "SELECT 1;\n\n-- two\nSELECT 2 ";
+        let ranges = statement_ranges(sql, Dialect::default());
+        assert_eq!(ranges, vec![0..8, 18..26]);
+        assert_eq!(&sql[ranges[1].clone()], "SELECT 2");
+    }
+
+    #[test]
+    fn test_split_skips_comment_only_statements() {
+        let statements = split_statements("SELECT 1; -- done\n", Dialect::default());
+        assert_eq!(statements, vec!["SELECT 1"]);
+    }
+
+    #[test]
+    fn test_split_postgres_dollar_quotes() {
+        let sql = "CREATE FUNCTION f() RETURNS int AS $body$ BEGIN RETURN 1; END; $body$ \
+                   LANGUAGE plpgsql; SELECT $$a;b$$, $1";
+        let statements = split_statements(sql, pg());
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].ends_with("LANGUAGE plpgsql"));
+        assert_eq!(statements[1], "SELECT $$a;b$$, $1");
+    }
+
+    #[test]
+    fn test_split_postgres_escape_string() {
+        let statements = split_statements(r"SELECT E'it\'s; fine'; SELECT 2", pg());
+        assert_eq!(statements, vec![r"SELECT E'it\'s; fine'", "SELECT 2"]);
+        // Standard strings don't treat the backslash as an escape
+        let statements = split_statements(r"SELECT 'C:\'; SELECT 2", pg());
+        assert_eq!(statements, vec![r"SELECT 'C:\'", "SELECT 2"]);
+    }
+
+    #[test]
+    fn test_split_mysql_delimiter() {
+        let sql = "DELIMITER //\n\
+                   CREATE PROCEDURE p() BEGIN SELECT 1; END//\n\
+                   DELIMITER ;\n\
+                   SELECT `a;b` FROM t;";
+        let statements = split_statements(sql, mysql());
+        assert_eq!(
+            statements,
+            vec![
+                "CREATE PROCEDURE p() BEGIN SELECT 1; END",
+                "SELECT `a;b` FROM t"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_delimiter_only_at_line_start() {
+        let statements = split_statements(
+            "SELECT 1 /* x */ DELIMITER //\n  DELIMITER //\nSELECT 2//",
+            mysql(),
+        );
+        assert_eq!(
+            statements,
+            vec!["SELECT 1 /* x */ DELIMITER //", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_mysql_string_escapes() {
+        let statements = split_statements(r#"SELECT 'a\';b', "c\";d"; SELECT 2"#, mysql());
+        assert_eq!(statements, vec![r#"SELECT 'a\';b', "c\";d""#, "SELECT 2"]);
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        let sql = "SELECT 1 -- one\n, '--x' /* two\nlines */, 3";
+        assert_eq!(
+            strip_comments(sql, Dialect::default()),
+            "SELECT 1  \n, '--x' \n, 3"
+        );
+        assert_eq!(
+            strip_comments("SELECT/**/1", Dialect::default()),
+            "SELECT 1"
+        );
+    }
+
+    #[test]
+    fn test_strip_nested_comments() {
+        let sql = "SELECT /* a /* b */ c */ 1";
+        assert_eq!(strip_comments(sql, pg()), "SELECT   1");
+        // Without nesting the comment ends at the first */
+        assert_eq!(strip_comments(sql, mysql()), "SELECT   c */ 1");
+    }
+
+    #[test]
+    fn test_mysql_comments() {
+        assert_eq!(strip_comments("SELECT 1 # note\n", mysql()), "SELECT 1  \n");
+        // `--` needs a following space in MySQL
+        assert_eq!(strip_comments("SELECT 1--1", mysql()), "SELECT 1--1");
+        assert_eq!(strip_comments("SELECT 1 -- x", mysql()), "SELECT 1  ");
+        // `#` is an operator in PostgreSQL
+        assert_eq!(strip_comments("SELECT 5 # 3", pg()), "SELECT 5 # 3");
+    }
+
+    #[test]
+    fn test_sqlite_bracket_identifiers() {
+        let sqlite = Dialect::from(&DatabaseType::Sqlite);
+        let tokens = tokenize("SELECT [a;b] FROM t", sqlite);
+        let ident = tokens.iter().find(|t| t.kind == TokenKind::QuotedIdent);
+        assert_eq!(ident.and_then(|t| t.ident()), Some("a;b".to_string()));
+    }
+
+    #[test]
+    fn test_unterminated_tokens() {
+        let tokens = tokenize("SELECT 'abc", Dialect::default());
+        assert!(tokens.last().unwrap().unterminated);
+        let tokens = tokenize("SELECT 'abc'", Dialect::default());
+        assert!(!tokens.last().unwrap().unterminated);
+        let tokens = tokenize("SELECT 1 -- note", Dialect::default());
+        assert!(tokens.last().unwrap().unterminated);
+    }
+
+    #[test]
+    fn test_first_keyword() {
+        let sql = "/* note */ (select 1)";
+        assert_eq!(first_keyword(sql, pg()), Some("SELECT".to_string()));
+        assert_eq!(first_keyword("-- only a comment", pg()), None);
+    }
+
+    #[test]
+    fn test_is_destructive() {
+        assert!(is_destructive("DELETE FROM t", pg()));
+        assert!(is_destructive("update t set a = 1", pg()));
+        assert!(!is_destructive("DELETE FROM t WHERE id = 1", pg()));
+        assert!(!is_destructive("UPDATE t SET a = 1 WHERE id = 1", pg()));
+        assert!(is_destructive("DROP TABLE t", pg()));
+        assert!(is_destructive("TRUNCATE t", pg()));
+        assert!(is_destructive("ALTER TABLE t DROP COLUMN c", pg()));
+        assert!(!is_destructive("ALTER TABLE t ADD COLUMN c int", pg()));
+        assert!(!is_destructive("SELECT * FROM t", pg()));
+        // WHERE in a string or comment is not a clause
+        assert!(is_destructive("DELETE FROM t -- WHERE id = 1", pg()));
+        assert!(is_destructive("UPDATE t SET a = 'WHERE'", pg()));
+    }
+
+    #[test]
+    fn test_is_destructive_subqueries() {
+        // WHERE only limits the statement at its own level
+        assert!(is_destructive(
+            "DELETE FROM t USING (SELECT id FROM u WHERE u.x = 1) s",
+            pg()
+        ));
+        assert!(is_destructive(
+            "UPDATE t SET a = (SELECT b FROM u WHERE u.id = 1)",
+            pg()
+        ));
+        assert!(!is_destructive(
+            "UPDATE t SET a = (SELECT b FROM u) WHERE t.id IN (SELECT id FROM u)",
+            pg()
+        ));
+        assert!(!is_destructive(
+            "ALTER TABLE t ADD CONSTRAINT c CHECK (drop > 0)",
+            pg()
+        ));
+    }
+
+    #[test]
+    fn test_is_destructive_with() {
+        assert!(is_destructive(
+            "WITH x AS (SELECT id FROM u WHERE u.x = 1) DELETE FROM t",
+            pg()
+        ));
+        assert!(!is_destructive(
+            "WITH x AS (SELECT id FROM u) DELETE FROM t WHERE id IN (SELECT id FROM x)",
+            pg()
+        ));
+        assert!(is_destructive(
+            "WITH RECURSIVE x (id) AS NOT MATERIALIZED (SELECT 1), y AS (SELECT 2) UPDATE t SET a = 1",
+            pg()
+        ));
+        // A data-modifying CTE counts like the statement it holds
+        assert!(is_destructive(
+            "WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d",
+            pg()
+        ));
+        assert!(!is_destructive(
+            "WITH d AS (DELETE FROM t WHERE id = 1 RETURNING *) SELECT * FROM d",
+            pg()
+        ));
+        assert!(!is_destructive(
+            "WITH x AS (SELECT 1) SELECT * FROM x",
+            pg()
+        ));
+    }
+}
@@ -8,13 +8,18 @@ mod db;
 mod debug;
 mod editor;
 mod error;
+mod explain;
 mod format;
+mod history;
+mod lexer;
 mod mysql;
 mod postgres;
 mod result_table;
 mod sqlite;
 mod ssh_sqlite;
+mod ssh_tunnel;
 mod templates;
+mod tls;
 
 use std::io;
 use std::path::PathBuf;
@@ -47,6 +52,7 @@ struct Args {
 
     /// Connect using a connection string: <type>://user:pass@host:port/db
     /// Types: pg, my, cs, ch, sq (add 's' for TLS: pgs, mys, css, chs)
+    /// Params: ?sslmode=verify-full&sslrootcert=/path/ca.pem&sslcert=...&sslkey=...
     /// Examples: pg://postgres@localhost/mydb, pgs://user@secure.host/db
     #[arg(long, value_name = "URL")]
     connect: Option<String>,
@@ -72,7 +78,13 @@ fn main() -> io::Result<()> {
     };
 
     // Load config before entering raw mode so errors are visible
-    let connections = config::load_config(args.config);
+    let connections = match config::load_config(args.config) {
+        Ok(connections) => connections,
+        Err(e) => {
+            eprintln!("Error in config file: {}", e);
+            std::process::exit(1);
+        }
+    };
     let mut terminal = setup_terminal()?;
     let result = run(&mut terminal, connections, cli_connection);
     restore_terminal()?;
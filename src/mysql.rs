@@ -1,24 +1,57 @@
-use crate::db::QueryResult;
+use crate::db::{
+    CellValue, ColumnInfo, DbObject, ObjectKind, PAGE_SIZE, PageWriter, PagedResult, RowStream,
+    objects_from_rows,
+};
 use crate::error::{Result, SqliError};
 use crate::format as fmt;
+use crate::tls::TlsConfig;
+use mysql_async::consts::{ColumnFlags, ColumnType};
 use mysql_async::prelude::*;
-use mysql_async::{Opts, OptsBuilder, Pool, Value};
+use mysql_async::{
+    ClientIdentity, Column, Conn, Opts, OptsBuilder, Pool, QueryResult, SslOpts, TextProtocol,
+    Value,
+};
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::PKey;
+use openssl::x509::X509;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::OwnedMutexGuard;
 
 pub struct MySqlClient {
     pool: Pool,
+    /// Server connection id of the statement being executed, for `KILL QUERY`
+    running: Mutex<Option<u32>>,
+    /// Statement applying the query timeout, run when the session connection opens
+    timeout_setup: Mutex<Option<String>>,
+    /// Connection every statement runs on, so transactions, session variables
+    /// and temporary tables carry over. Opened on first use and reopened if
+    /// the server drops it; metadata queries use the pool instead.
+    session: Arc<tokio::sync::Mutex<Option<Conn>>>,
+    /// A transaction opened by `begin` is in progress on the session
+    in_transaction: AtomicBool,
+    /// Statements on the session connection may only read
+    readonly: AtomicBool,
 }
 
 impl MySqlClient {
     pub async fn connect(
         host: &str,
         port: u16,
+        socket: Option<&str>,
         user: &str,
         password: &str,
         database: &str,
+        tls: &TlsConfig,
     ) -> Result<Self> {
+        // TLS is skipped by the driver for unix socket connections
         let opts = OptsBuilder::default()
             .ip_or_hostname(host)
             .tcp_port(port)
+            .socket(socket)
+            .ssl_opts(Self::ssl_opts(tls)?)
             .user(Some(user))
             .pass(Some(password))
             .db_name(if database.is_empty() {
@@ -33,7 +66,46 @@ impl MySqlClient {
         let mut conn = pool.get_conn().await?;
         conn.query_drop("SELECT 1").await?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            running: Mutex::new(None),
+            timeout_setup: Mutex::new(None),
+            session: Arc::new(tokio::sync::Mutex::new(None)),
+            in_transaction: AtomicBool::new(false),
+            readonly: AtomicBool::new(false),
+        })
+    }
+
+    /// Map TLS settings to driver options. The driver has no plaintext
+    /// fallback, so prefer behaves like require.
+    fn ssl_opts(tls: &TlsConfig) -> Result<Option<SslOpts>> {
+        if !tls.mode.is_enabled() {
+            return Ok(None);
+        }
+
+        let mut opts = SslOpts::default()
+            .with_danger_accept_invalid_certs(!tls.mode.verifies_ca())
+            .with_danger_skip_domain_validation(!tls.mode.verifies_hostname())
+            .with_danger_tls_hostname_override(tls.server_name.clone());
+
+        if let Some(ref path) = tls.root_cert {
+            opts = opts.with_root_certs(vec![PathBuf::from(path).into()]);
+        }
+
+        // native-tls expects the client identity as a PKCS#12 archive
+        if let Some((cert, key)) = tls.client_identity()? {
+            let cert = X509::from_pem(&cert)?;
+            let key = PKey::private_key_from_pem(&key)?;
+            let archive = Pkcs12::builder()
+                .name("sqli")
+                .cert(&cert)
+                .pkey(&key)
+                .build2("")?
+                .to_der()?;
+            opts = opts.with_client_identity(Some(ClientIdentity::new(archive.into())));
+        }
+
+        Ok(Some(opts))
     }
 
     pub async fn list_databases(&self, include_system: bool) -> Result<Vec<String>> {
@@ -51,60 +123,245 @@ impl MySqlClient {
         Ok(filtered)
     }
 
-    pub async fn list_tables(&self, database: &str) -> Result<Vec<String>> {
+    pub async fn list_objects(&self, database: &str) -> Result<Vec<DbObject>> {
         let mut conn = self.pool.get_conn().await?;
+        let db = database.replace('\'', "''");
         let query = format!(
-            "SELECT table_name FROM information_schema.tables WHERE table_schema = '{}' ORDER BY table_name",
-            database.replace('\'', "''")
+            "SELECT IF(table_type = 'VIEW', 'view', 'table'), table_name \
+             FROM information_schema.tables WHERE table_schema = '{0}' \
+             UNION ALL \
+             SELECT LOWER(routine_type), routine_name \
+             FROM information_schema.routines WHERE routine_schema = '{0}' \
+             UNION ALL \
+             SELECT DISTINCT 'index', CONCAT(table_name, '.', index_name) \
+             FROM information_schema.statistics WHERE table_schema = '{0}'",
+            db
         );
-        let tables: Vec<String> = conn.query(query).await?;
-        Ok(tables)
+        let rows: Vec<(String, String)> = conn.query(query).await?;
+        Ok(objects_from_rows(rows))
     }
 
-    pub async fn execute_query(&self, query: &str) -> Result<QueryResult> {
-        let mut conn = self.pool.get_conn().await?;
-        let query_upper = query.trim().to_uppercase();
+    /// Make the session connection read-only once it opens, so this has to
+    /// be called before the first statement. Metadata queries only read.
+    pub fn set_readonly(&self) {
+        self.readonly.store(true, Ordering::Relaxed);
+    }
 
-        if query_upper.starts_with("SELECT")
-            || query_upper.starts_with("SHOW")
-            || query_upper.starts_with("DESCRIBE")
-            || query_upper.starts_with("EXPLAIN")
-            || query_upper.starts_with("WITH")
-        {
-            let mut result = conn.query_iter(query).await?;
-
-            let columns: Vec<String> = result
-                .columns_ref()
-                .iter()
-                .map(|c| c.name_str().to_string())
-                .collect();
-
-            let rows: Vec<mysql_async::Row> = result.collect().await?;
-
-            let data_rows: Vec<Vec<String>> = rows
-                .iter()
-                .map(|row| {
-                    (0..columns.len())
-                        .map(|i| Self::format_value(row.get::<Value, _>(i)))
-                        .collect()
-                })
-                .collect();
+    /// Limit statement run time; `None` disables it. MySQL enforces
+    /// `max_execution_time` for SELECT only, MariaDB `max_statement_time`
+    /// for every statement.
+    pub async fn set_query_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        let ms = timeout.map_or(0, |t| t.as_millis());
+        let mut session = self.session().await?;
+        let conn = Self::session_conn(&mut session);
+        let mysql = format!("SET SESSION max_execution_time = {}", ms);
+        let setup = match conn.query_drop(&mysql).await {
+            Ok(()) => mysql,
+            Err(_) => {
+                let mariadb = format!("SET SESSION max_statement_time = {}", ms as f64 / 1000.0);
+                conn.query_drop(&mariadb).await?;
+                mariadb
+            }
+        };
+        *self.timeout_setup.lock().unwrap() = Some(setup);
+        Ok(())
+    }
 
-            Ok(QueryResult::Select {
-                columns,
-                rows: data_rows,
-            })
-        } else {
-            let result = conn.exec_drop(query, ()).await;
-            match result {
-                Ok(_) => Ok(QueryResult::Execute {
-                    rows_affected: conn.affected_rows(),
-                }),
-                Err(e) => Err(SqliError::Query(e.to_string())),
+    /// Lock the session connection, opening it if there is none yet.
+    /// A transaction open on a lost connection was rolled back by the
+    /// server, which is reported once the new connection is in place.
+    async fn session(&self) -> Result<OwnedMutexGuard<Option<Conn>>> {
+        let mut session = Arc::clone(&self.session).lock_owned().await;
+        if session.is_none() {
+            let mut conn = self.pool.get_conn().await?;
+            let setup = self.timeout_setup.lock().unwrap().clone();
+            if let Some(setup) = setup {
+                conn.query_drop(setup).await?;
+            }
+            if self.readonly.load(Ordering::Relaxed) {
+                conn.query_drop("SET SESSION TRANSACTION READ ONLY").await?;
+            }
+            *session = Some(conn);
+            if self.in_transaction.swap(false, Ordering::Relaxed) {
+                return Err(SqliError::Connection(
+                    "connection lost, the open transaction was rolled back".to_string(),
+                ));
+            }
+        }
+        Ok(session)
+    }
+
+    fn session_conn(session: &mut Option<Conn>) -> &mut Conn {
+        session
+            .as_mut()
+            .expect("session connection is opened on lock")
+    }
+
+    /// Forget a session connection the server has dropped, so the next
+    /// statement opens a new one instead of failing the same way
+    fn drop_lost_session(session: &mut Option<Conn>, error: &mysql_async::Error) {
+        if matches!(error, mysql_async::Error::Io(_)) {
+            *session = None;
+        }
+    }
+
+    pub async fn execute_paged(&self, query: &str) -> Result<PagedResult> {
+        let mut session = self.session().await?;
+        let id = Self::session_conn(&mut session).id();
+        *self.running.lock().unwrap() = Some(id);
+        let result = Self::run(self.pool.clone(), id, session, query).await;
+        self.running
+            .lock()
+            .unwrap()
+            .take_if(|running| *running == id);
+        result
+    }
+
+    async fn run(
+        pool: Pool,
+        id: u32,
+        mut session: OwnedMutexGuard<Option<Conn>>,
+        query: &str,
+    ) -> Result<PagedResult> {
+        // The task holds the session lock until the rows are read or
+        // the stream is dropped
+        let (writer, stream) = RowStream::channel(true, PAGE_SIZE);
+        let query = query.to_string();
+        let task = tokio::spawn(async move {
+            let conn = Self::session_conn(&mut session);
+            if let Err(e) = Self::stream_rows(&pool, id, conn, query, writer).await {
+                Self::drop_lost_session(&mut session, &e);
+            }
+        });
+        stream.with_task(task).first_page().await
+    }
+
+    /// Send the rows of a query to the writer, or its affected row count if
+    /// it has no result set. The driver error that ended it has already been
+    /// reported and is returned for the session check.
+    async fn stream_rows(
+        pool: &Pool,
+        id: u32,
+        conn: &mut Conn,
+        query: String,
+        mut writer: PageWriter,
+    ) -> std::result::Result<(), mysql_async::Error> {
+        // Text protocol, since statements like USE cannot be prepared
+        let mut result = match conn.query_iter(query).await {
+            Ok(result) => result,
+            Err(e) => {
+                writer.fail(SqliError::Query(e.to_string())).await;
+                return Err(e);
+            }
+        };
+
+        if result.columns_ref().is_empty() {
+            let rows_affected = result.affected_rows();
+            if let Err(e) = result.drop_result().await {
+                writer.fail(SqliError::Query(e.to_string())).await;
+                return Err(e);
+            }
+            writer.affected(rows_affected).await;
+            return Ok(());
+        }
+
+        let meta: Vec<Column> = result.columns_ref().to_vec();
+        let columns = meta.iter().map(|c| c.name_str().to_string()).collect();
+        let types = meta.iter().map(Self::type_name).collect();
+        if !writer.columns(columns, types).await {
+            return Self::stop_result(pool, id, result).await;
+        }
+
+        loop {
+            match result.next().await {
+                Ok(Some(row)) => {
+                    let values = meta
+                        .iter()
+                        .enumerate()
+                        .map(|(i, c)| Self::format_value(row.get::<Value, _>(i), c))
+                        .collect();
+                    if !writer.push(values).await {
+                        return Self::stop_result(pool, id, result).await;
+                    }
+                }
+                Ok(None) => {
+                    writer.finish().await;
+                    return Ok(());
+                }
+                Err(e) => {
+                    writer.fail(SqliError::Query(e.to_string())).await;
+                    return Err(e);
+                }
             }
         }
     }
 
+    /// Stop a statement whose rows are no longer read. The rest of a result
+    /// has to be read off the session before its next statement, so the
+    /// statement is killed from a second connection first and only the rows
+    /// already sent are discarded.
+    async fn stop_result(
+        pool: &Pool,
+        id: u32,
+        result: QueryResult<'_, 'static, TextProtocol>,
+    ) -> std::result::Result<(), mysql_async::Error> {
+        if let Err(e) = Self::kill_query(pool, id).await {
+            crate::debug_log!("KILL QUERY {} failed: {}", id, e);
+        }
+        match result.drop_result().await {
+            // Interrupted by the kill
+            Ok(()) | Err(mysql_async::Error::Server(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn kill_query(pool: &Pool, id: u32) -> std::result::Result<(), mysql_async::Error> {
+        let mut conn = pool.get_conn().await?;
+        conn.query_drop(format!("KILL QUERY {}", id)).await
+    }
+
+    /// Open a transaction that following statements run in
+    pub async fn begin(&self) -> Result<()> {
+        let mut session = self.session().await?;
+        Self::session_conn(&mut session)
+            .query_drop("START TRANSACTION")
+            .await?;
+        self.in_transaction.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub async fn commit(&self) -> Result<()> {
+        let mut session = self.session().await?;
+        if !self.in_transaction.load(Ordering::Relaxed) {
+            return Err(SqliError::Query(
+                "the transaction was rolled back when the connection was lost".to_string(),
+            ));
+        }
+        Self::session_conn(&mut session)
+            .query_drop("COMMIT")
+            .await?;
+        self.in_transaction.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub async fn rollback(&self) -> Result<()> {
+        let mut session = self.session().await?;
+        Self::session_conn(&mut session)
+            .query_drop("ROLLBACK")
+            .await?;
+        self.in_transaction.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Kill the running statement from a second connection.
+    pub async fn cancel_query(&self) -> Result<()> {
+        let Some(id) = *self.running.lock().unwrap() else {
+            return Ok(());
+        };
+        Self::kill_query(&self.pool, id).await?;
+        Ok(())
+    }
+
     pub fn select_table_query(&self, table: &str, limit: usize, _database: Option<&str>) -> String {
         format!("SELECT * FROM {} LIMIT {}", table, limit)
     }
@@ -113,36 +370,173 @@ impl MySqlClient {
         format!("DESCRIBE {}", table)
     }
 
-    pub async fn list_columns(&self, table: &str) -> Result<Vec<String>> {
-        let query = format!("SHOW COLUMNS FROM {}", table);
-        let rows: Vec<mysql_async::Row> = self.pool.get_conn().await?.query(query).await?;
-        Ok(rows.iter().filter_map(|r| r.get::<String, _>(0)).collect())
+    pub fn definition_query(&self, obj: &DbObject, _database: Option<&str>) -> String {
+        match obj.kind {
+            ObjectKind::Function => format!("SHOW CREATE FUNCTION {}", quote_ident(&obj.name)),
+            ObjectKind::Procedure => format!("SHOW CREATE PROCEDURE {}", quote_ident(&obj.name)),
+            ObjectKind::Index => {
+                let (table, index) = obj.name.split_once('.').unwrap_or((&obj.name, ""));
+                format!(
+                    "SHOW INDEX FROM {} WHERE Key_name = '{}'",
+                    quote_ident(table),
+                    index.replace('\'', "''")
+                )
+            }
+            _ => self.describe_table_query(&obj.name, None),
+        }
+    }
+
+    pub async fn list_columns(&self, table: &str) -> Result<Vec<ColumnInfo>> {
+        let (schema, table) = match table.split_once('.') {
+            Some((db, t)) => (format!("'{}'", db.replace('\'', "''")), t),
+            None => ("DATABASE()".to_string(), table),
+        };
+        let query = format!(
+            "SELECT c.column_name, c.column_type, c.is_nullable = 'YES', c.column_key = 'PRI', \
+                    EXISTS (SELECT 1 FROM information_schema.key_column_usage k \
+                            WHERE k.table_schema = c.table_schema AND k.table_name = c.table_name \
+                            AND k.column_name = c.column_name AND k.referenced_table_name IS NOT NULL) \
+             FROM information_schema.columns c \
+             WHERE c.table_schema = {} AND c.table_name = '{}' \
+             ORDER BY c.ordinal_position",
+            schema,
+            table.trim_matches('`').replace('\'', "''")
+        );
+        let rows: Vec<(String, String, bool, bool, bool)> =
+            self.pool.get_conn().await?.query(query).await?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(name, data_type, nullable, primary_key, foreign_key)| ColumnInfo {
+                    name,
+                    data_type,
+                    nullable,
+                    primary_key,
+                    foreign_key,
+                },
+            )
+            .collect())
     }
 
-    fn format_value(value: Option<Value>) -> String {
+    /// The text protocol sends every value as bytes, so the column type
+    /// decides how they are read.
+    fn format_value(value: Option<Value>, column: &Column) -> CellValue {
         match value {
-            None | Some(Value::NULL) => "NULL".to_string(),
-            Some(Value::Bytes(b)) => String::from_utf8_lossy(&b).to_string(),
-            Some(Value::Int(i)) => i.to_string(),
-            Some(Value::UInt(u)) => u.to_string(),
-            Some(Value::Float(f)) => f.to_string(),
-            Some(Value::Double(d)) => d.to_string(),
+            None | Some(Value::NULL) => CellValue::Null,
+            Some(Value::Bytes(b)) => Self::typed_bytes(b, column),
+            Some(Value::Int(i)) => CellValue::from(i),
+            Some(Value::UInt(u)) => CellValue::from(u),
+            Some(Value::Float(f)) => CellValue::from(f),
+            Some(Value::Double(d)) => CellValue::from(d),
             Some(Value::Date(y, m, d, h, min, s, _us)) => {
-                if h == 0 && min == 0 && s == 0 {
+                CellValue::Temporal(if h == 0 && min == 0 && s == 0 {
                     fmt::date(y as i32, m as u32, d as u32)
                 } else {
                     fmt::datetime(y as i32, m as u32, d as u32, h as u32, min as u32, s as u32)
-                }
+                })
             }
             Some(Value::Time(neg, d, h, m, s, us)) => {
                 let sign = if neg { "-" } else { "" };
                 let total_hours = d * 24 + h as u32;
-                if us == 0 {
+                CellValue::Temporal(if us == 0 {
                     format!("{}{}:{:02}:{:02}", sign, total_hours, m, s)
                 } else {
                     format!("{}{}:{:02}:{:02}.{:06}", sign, total_hours, m, s, us)
-                }
+                })
             }
         }
     }
+
+    fn typed_bytes(bytes: Vec<u8>, column: &Column) -> CellValue {
+        let text = || String::from_utf8_lossy(&bytes).to_string();
+        match column.column_type() {
+            ColumnType::MYSQL_TYPE_TINY
+            | ColumnType::MYSQL_TYPE_SHORT
+            | ColumnType::MYSQL_TYPE_INT24
+            | ColumnType::MYSQL_TYPE_LONG
+            | ColumnType::MYSQL_TYPE_LONGLONG
+            | ColumnType::MYSQL_TYPE_YEAR => text()
+                .parse()
+                .map_or_else(|_| CellValue::Text(text()), CellValue::Int),
+            ColumnType::MYSQL_TYPE_FLOAT | ColumnType::MYSQL_TYPE_DOUBLE => text()
+                .parse()
+                .map_or_else(|_| CellValue::Text(text()), CellValue::Float),
+            ColumnType::MYSQL_TYPE_DECIMAL | ColumnType::MYSQL_TYPE_NEWDECIMAL => {
+                CellValue::Decimal(text())
+            }
+            ColumnType::MYSQL_TYPE_DATE
+            | ColumnType::MYSQL_TYPE_NEWDATE
+            | ColumnType::MYSQL_TYPE_TIME
+            | ColumnType::MYSQL_TYPE_TIME2
+            | ColumnType::MYSQL_TYPE_DATETIME
+            | ColumnType::MYSQL_TYPE_DATETIME2
+            | ColumnType::MYSQL_TYPE_TIMESTAMP
+            | ColumnType::MYSQL_TYPE_TIMESTAMP2 => CellValue::Temporal(text()),
+            ColumnType::MYSQL_TYPE_JSON => CellValue::Json(text()),
+            ColumnType::MYSQL_TYPE_BIT | ColumnType::MYSQL_TYPE_GEOMETRY => CellValue::Bytes(bytes),
+            _ if is_binary(column) => CellValue::Bytes(bytes),
+            _ => CellValue::Text(text()),
+        }
+    }
+
+    /// SQL type name of a result column, e.g. `int unsigned` or `varchar`
+    fn type_name(column: &Column) -> String {
+        let binary = is_binary(column);
+        let name = match column.column_type() {
+            ColumnType::MYSQL_TYPE_TINY => "tinyint",
+            ColumnType::MYSQL_TYPE_SHORT => "smallint",
+            ColumnType::MYSQL_TYPE_INT24 => "mediumint",
+            ColumnType::MYSQL_TYPE_LONG => "int",
+            ColumnType::MYSQL_TYPE_LONGLONG => "bigint",
+            ColumnType::MYSQL_TYPE_FLOAT => "float",
+            ColumnType::MYSQL_TYPE_DOUBLE => "double",
+            ColumnType::MYSQL_TYPE_DECIMAL | ColumnType::MYSQL_TYPE_NEWDECIMAL => "decimal",
+            ColumnType::MYSQL_TYPE_YEAR => "year",
+            ColumnType::MYSQL_TYPE_DATE | ColumnType::MYSQL_TYPE_NEWDATE => "date",
+            ColumnType::MYSQL_TYPE_TIME | ColumnType::MYSQL_TYPE_TIME2 => "time",
+            ColumnType::MYSQL_TYPE_DATETIME | ColumnType::MYSQL_TYPE_DATETIME2 => "datetime",
+            ColumnType::MYSQL_TYPE_TIMESTAMP | ColumnType::MYSQL_TYPE_TIMESTAMP2 => "timestamp",
+            ColumnType::MYSQL_TYPE_JSON => "json",
+            ColumnType::MYSQL_TYPE_BIT => "bit",
+            ColumnType::MYSQL_TYPE_ENUM => "enum",
+            ColumnType::MYSQL_TYPE_SET => "set",
+            ColumnType::MYSQL_TYPE_GEOMETRY => "geometry",
+            ColumnType::MYSQL_TYPE_NULL => "null",
+            ColumnType::MYSQL_TYPE_STRING if binary => "binary",
+            ColumnType::MYSQL_TYPE_STRING => "char",
+            ColumnType::MYSQL_TYPE_VAR_STRING | ColumnType::MYSQL_TYPE_VARCHAR if binary => {
+                "varbinary"
+            }
+            ColumnType::MYSQL_TYPE_VAR_STRING | ColumnType::MYSQL_TYPE_VARCHAR => "varchar",
+            ColumnType::MYSQL_TYPE_TINY_BLOB
+            | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+            | ColumnType::MYSQL_TYPE_LONG_BLOB
+            | ColumnType::MYSQL_TYPE_BLOB
+                if binary =>
+            {
+                "blob"
+            }
+            ColumnType::MYSQL_TYPE_TINY_BLOB
+            | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+            | ColumnType::MYSQL_TYPE_LONG_BLOB
+            | ColumnType::MYSQL_TYPE_BLOB => "text",
+            _ => "",
+        };
+        if column.flags().contains(ColumnFlags::UNSIGNED_FLAG)
+            && column.column_type().is_numeric_type()
+        {
+            format!("{} unsigned", name)
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+/// Binary strings and blobs carry the `binary` collation (id 63)
+fn is_binary(column: &Column) -> bool {
+    column.character_set() == 63
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("`{}`", ident.replace('`', "``"))
 }
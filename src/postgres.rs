@@ -1,15 +1,33 @@
-use crate::db::QueryResult;
-use crate::error::Result;
-use crate::format as fmt;
+use crate::controller::DatabaseType;
+use crate::db::{
+    CellValue, ColumnInfo, DbObject, ObjectKind, PAGE_SIZE, PagedResult, QueryResult, RowStream,
+    objects_from_rows,
+};
+use crate::error::{Result, SqliError};
+use crate::lexer::{self, Dialect, TokenKind};
+use crate::tls::{SslMode, TlsConfig};
 
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use futures_util::{StreamExt, pin_mut};
+use postgres_native_tls::MakeTlsConnector;
 use serde_json::Value as JsonValue;
-use tokio_postgres::types::{FromSql, Kind, Type};
-use tokio_postgres::{Client, NoTls, Row};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_postgres::config::SslMode as PgSslMode;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::types::{FromSql, Kind, ToSql, Type};
+use tokio_postgres::{CancelToken, Client, Config, Connection, NoTls, Row, SimpleQueryMessage};
 use uuid::Uuid;
 
+/// Cursor a query's rows are fetched through, one page at a time
+const CURSOR: &str = "sqli_rows";
+
 pub struct PostgresClient {
-    client: Client,
+    client: Arc<Client>,
+    /// Connector for the out-of-band cancel request, `None` without TLS
+    cancel_tls: Option<MakeTlsConnector>,
 }
 
 impl PostgresClient {
@@ -19,28 +37,62 @@ impl PostgresClient {
         user: &str,
         password: &str,
         database: &str,
+        tls: &TlsConfig,
     ) -> Result<Self> {
         crate::debug_log!(
-            "Postgres connecting to {}:{} database='{}' user='{}'",
+            "Postgres connecting to {}:{} database='{}' user='{}' sslmode={}",
             host,
             port,
             database,
-            user
-        );
-        let conn_string = format!(
-            "host={} port={} user={} password={} dbname={}",
-            host, port, user, password, database
+            user,
+            tls.mode.as_str()
         );
+        let mut config = Config::new();
+        // Through a tunnel, connect to the local address but verify the
+        // certificate against the real server name
+        match (&tls.server_name, host.parse::<IpAddr>()) {
+            (Some(name), Ok(addr)) => config.host(name).hostaddr(addr),
+            _ => config.host(host),
+        };
+        config
+            .port(port)
+            .user(user)
+            .password(password)
+            .dbname(database)
+            .ssl_mode(match tls.mode {
+                SslMode::Disable => PgSslMode::Disable,
+                SslMode::Prefer => PgSslMode::Prefer,
+                SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => PgSslMode::Require,
+            });
+
+        let (client, cancel_tls) = if tls.mode.is_enabled() {
+            let connector = MakeTlsConnector::new(tls.native_connector()?);
+            let (client, connection) = config.connect(connector.clone()).await?;
+            Self::spawn_connection(connection);
+            (client, Some(connector))
+        } else {
+            let (client, connection) = config.connect(NoTls).await?;
+            Self::spawn_connection(connection);
+            (client, None)
+        };
 
-        let (client, connection) = tokio_postgres::connect(&conn_string, NoTls).await?;
+        Ok(Self {
+            client: Arc::new(client),
+            cancel_tls,
+        })
+    }
 
+    /// Drive the connection in the background until the client is dropped
+    fn spawn_connection<S, T>(connection: Connection<S, T>)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
         tokio::spawn(async move {
             if let Err(e) = connection.await {
                 crate::debug_log!("Postgres connection error: {}", e);
             }
         });
-
-        Ok(Self { client })
     }
 
     pub async fn list_databases(&self, include_system: bool) -> Result<Vec<String>> {
@@ -60,131 +112,381 @@ impl PostgresClient {
         Ok(databases)
     }
 
-    pub async fn list_tables(&self, schema: &str) -> Result<Vec<String>> {
-        crate::debug_log!("Postgres listing tables for schema '{}'", schema);
-        let rows = self
-            .client
-            .query(
-                "SELECT tablename FROM pg_tables WHERE schemaname = $1 ORDER BY tablename",
-                &[&schema],
-            )
-            .await?;
+    /// List objects in all non-system schemas as `schema.name`.
+    /// Functions carry their argument types so overloads stay distinct
+    /// and the name can be cast to `regprocedure`.
+    pub async fn list_objects(&self, _database: &str) -> Result<Vec<DbObject>> {
+        let query = format!(
+            "SELECT 'table', quote_ident(schemaname) || '.' || quote_ident(tablename) FROM pg_tables WHERE {} \
+             UNION ALL \
+             SELECT 'view', quote_ident(schemaname) || '.' || quote_ident(viewname) FROM pg_views WHERE {} \
+             UNION ALL \
+             SELECT 'matview', quote_ident(schemaname) || '.' || quote_ident(matviewname) FROM pg_matviews WHERE {} \
+             UNION ALL \
+             SELECT 'sequence', quote_ident(schemaname) || '.' || quote_ident(sequencename) FROM pg_sequences WHERE {} \
+             UNION ALL \
+             SELECT 'index', quote_ident(schemaname) || '.' || quote_ident(indexname) FROM pg_indexes WHERE {} \
+             UNION ALL \
+             SELECT CASE p.prokind WHEN 'p' THEN 'procedure' ELSE 'function' END, \
+                    quote_ident(n.nspname) || '.' || quote_ident(p.proname) \
+                    || '(' || oidvectortypes(p.proargtypes) || ')' \
+             FROM pg_proc p JOIN pg_namespace n ON n.oid = p.pronamespace \
+             WHERE p.prokind IN ('f', 'p') AND {}",
+            user_schema_filter("schemaname"),
+            user_schema_filter("schemaname"),
+            user_schema_filter("schemaname"),
+            user_schema_filter("schemaname"),
+            user_schema_filter("schemaname"),
+            user_schema_filter("n.nspname"),
+        );
+        let rows = self.client.query(&query, &[]).await?;
 
-        let tables: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
-        crate::debug_log!("Found {} tables: {:?}", tables.len(), tables);
-        Ok(tables)
+        let objects = objects_from_rows(
+            rows.iter()
+                .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1))),
+        );
+        crate::debug_log!("Found {} objects", objects.len());
+        Ok(objects)
+    }
+
+    /// Set the schema search path for this session.
+    pub async fn set_search_path(&self, schemas: &[&str]) -> Result<()> {
+        let path: Vec<String> = schemas.iter().map(|s| quote_ident(s)).collect();
+        let query = format!("SET search_path TO {}", path.join(", "));
+        self.client.batch_execute(&query).await?;
+        Ok(())
+    }
+
+    /// Make every transaction of this session read-only
+    pub async fn set_readonly(&self) -> Result<()> {
+        self.client
+            .batch_execute("SET SESSION default_transaction_read_only = on")
+            .await?;
+        Ok(())
     }
 
-    pub async fn execute_query(&self, query: &str) -> Result<QueryResult> {
+    pub async fn execute_paged(&self, query: &str) -> Result<PagedResult> {
         crate::debug_log!("Postgres executing: {}", query.trim().replace('\n', " "));
-        let query_upper = query.trim().to_uppercase();
 
-        if query_upper.starts_with("SELECT") || query_upper.starts_with("WITH") {
-            let rows = self.client.query(query, &[]).await?;
+        // The statement description tells whether it returns rows, which
+        // covers RETURNING, VALUES, TABLE, SHOW and friends alike
+        let statement = self.client.prepare(query).await?;
+        if statement.columns().is_empty() {
+            let rows_affected = self.client.execute(&statement, &[]).await?;
+            return Ok((QueryResult::Execute { rows_affected }, None));
+        }
 
-            if rows.is_empty() {
-                return Ok(QueryResult::Select {
-                    columns: vec![],
-                    rows: vec![],
-                });
+        let columns: Vec<String> = statement
+            .columns()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect();
+        let types: Vec<String> = statement
+            .columns()
+            .iter()
+            .map(|c| type_name(c.type_()))
+            .collect();
+        if is_cursor_query(query) {
+            return self.fetch_paged(query, columns, types).await;
+        }
+        let rows = self
+            .client
+            .query_raw(&statement, std::iter::empty::<&(dyn ToSql + Sync)>())
+            .await?;
+
+        // Rows arrive in order on the shared connection, so nothing else
+        // can run on it until the stream has been read. A cancel would roll
+        // back a statement such as INSERT ... RETURNING, so the rest of the
+        // rows is read instead.
+        let (mut writer, stream) = RowStream::channel(true, PAGE_SIZE);
+        let task = tokio::spawn(async move {
+            pin_mut!(rows);
+            if !writer.columns(columns, types).await {
+                return Self::discard_rows(rows).await;
+            }
+            while let Some(row) = rows.next().await {
+                let row = match row {
+                    Ok(row) => row,
+                    Err(e) => return writer.fail(e.into()).await,
+                };
+                let values = (0..row.len())
+                    .map(|i| Self::get_column_value(&row, i))
+                    .collect();
+                if !writer.push(values).await {
+                    return Self::discard_rows(rows).await;
+                }
             }
+            writer.finish().await;
+        });
+        stream.with_task(task).first_page().await
+    }
 
-            let columns: Vec<String> = rows[0]
-                .columns()
-                .iter()
-                .map(|c| c.name().to_string())
-                .collect();
+    /// Read a query through a cursor, one `FETCH` per page. The connection is
+    /// free between pages, and closing the stream only closes the cursor.
+    /// Outside a transaction block, the cursor gets its own.
+    async fn fetch_paged(
+        &self,
+        query: &str,
+        columns: Vec<String>,
+        types: Vec<String>,
+    ) -> Result<PagedResult> {
+        let (begin, end) = cursor_bounds(self.in_transaction_block().await?);
+        let own_transaction = begin.is_some();
+        if let Some(begin) = begin {
+            self.client.batch_execute(begin).await?;
+        }
+        let declared = async {
+            let declare = format!("DECLARE {} NO SCROLL CURSOR FOR {}", CURSOR, query);
+            self.client.batch_execute(&declare).await?;
+            self.client
+                .prepare(&format!("FETCH {} FROM {}", PAGE_SIZE, CURSOR))
+                .await
+        };
+        let fetch = match declared.await {
+            Ok(fetch) => fetch,
+            Err(e) => {
+                if own_transaction {
+                    let _ = self.client.batch_execute("ROLLBACK").await;
+                }
+                return Err(e.into());
+            }
+        };
 
-            let data_rows: Vec<Vec<String>> = rows
-                .iter()
-                .map(|row| {
-                    (0..row.len())
+        let client = Arc::clone(&self.client);
+        let (mut writer, stream) = RowStream::channel(true, PAGE_SIZE);
+        let task = tokio::spawn(async move {
+            let mut done = !writer.columns(columns, types).await;
+            while !done {
+                let rows = match client.query(&fetch, &[]).await {
+                    Ok(rows) => rows,
+                    Err(e) => {
+                        if own_transaction {
+                            let _ = client.batch_execute("ROLLBACK").await;
+                        }
+                        return writer.fail(e.into()).await;
+                    }
+                };
+                done = rows.len() < PAGE_SIZE;
+                for row in &rows {
+                    let values = (0..row.len())
                         .map(|i| Self::get_column_value(row, i))
-                        .collect()
-                })
-                .collect();
+                        .collect();
+                    if !writer.push(values).await {
+                        done = true;
+                        break;
+                    }
+                }
+            }
+            if let Err(e) = client.batch_execute(&end).await {
+                return writer.fail(e.into()).await;
+            }
+            writer.finish().await;
+        });
+        stream.with_task(task).first_page().await
+    }
 
-            Ok(QueryResult::Select {
-                columns,
-                rows: data_rows,
-            })
-        } else {
-            let rows_affected = self.client.execute(query, &[]).await?;
-            Ok(QueryResult::Execute { rows_affected })
+    /// Read what the server sends for a statement whose rows are no longer
+    /// wanted, leaving the connection ready for the next query.
+    async fn discard_rows(mut rows: std::pin::Pin<&mut tokio_postgres::RowStream>) {
+        while let Some(Ok(_)) = rows.next().await {}
+    }
+
+    async fn send_cancel(token: &CancelToken, tls: Option<MakeTlsConnector>) -> Result<()> {
+        match tls {
+            Some(tls) => token.cancel_query(tls).await?,
+            None => token.cancel_query(NoTls).await?,
+        }
+        Ok(())
+    }
+
+    /// Set `statement_timeout` for this session; `None` disables it.
+    pub async fn set_query_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        let ms = timeout.map_or(0, |t| t.as_millis());
+        self.client
+            .batch_execute(&format!("SET statement_timeout = {}", ms))
+            .await?;
+        Ok(())
+    }
+
+    /// Open a transaction that following statements run in
+    pub async fn begin(&self) -> Result<()> {
+        self.client.batch_execute("BEGIN").await?;
+        Ok(())
+    }
+
+    /// Commit the open transaction. The server turns COMMIT of a transaction
+    /// aborted by an error into a silent rollback, so that is checked first.
+    pub async fn commit(&self) -> Result<()> {
+        if let Err(e) = self.client.batch_execute("SELECT 1").await {
+            if e.code() != Some(&SqlState::IN_FAILED_SQL_TRANSACTION) {
+                return Err(e.into());
+            }
+            self.rollback().await?;
+            return Err(SqliError::Query(
+                "the transaction was aborted by an error and has been rolled back".to_string(),
+            ));
         }
+        self.client.batch_execute("COMMIT").await?;
+        Ok(())
+    }
+
+    pub async fn rollback(&self) -> Result<()> {
+        self.client.batch_execute("ROLLBACK").await?;
+        Ok(())
+    }
+
+    /// Whether the session is inside a transaction block, whether `begin` or
+    /// a statement the user typed opened it. Outside one, each statement runs
+    /// in a transaction starting with it; this needs the simple protocol,
+    /// where the check is a single statement.
+    pub async fn in_transaction_block(&self) -> Result<bool> {
+        let messages = self
+            .client
+            .simple_query("SELECT transaction_timestamp() <> statement_timestamp()")
+            .await?;
+        Ok(messages
+            .iter()
+            .any(|m| matches!(m, SimpleQueryMessage::Row(row) if row.get(0) == Some("t"))))
+    }
+
+    /// Ask the server to cancel the statement running on this connection.
+    pub async fn cancel_query(&self) -> Result<()> {
+        Self::send_cancel(&self.client.cancel_token(), self.cancel_tls.clone()).await
     }
 
     pub fn select_table_query(&self, table: &str, limit: usize, _schema: Option<&str>) -> String {
-        format!("SELECT * FROM \"{}\" LIMIT {}", table, limit)
+        format!("SELECT * FROM {} LIMIT {}", quote_table(table), limit)
     }
 
     pub fn describe_table_query(&self, table: &str, _schema: Option<&str>) -> String {
+        let (schema, name) = split_table(table);
+        let schema = match schema {
+            Some(s) => format!("'{}'", s.replace('\'', "''")),
+            None => "current_schema()".to_string(),
+        };
         format!(
             "SELECT column_name, data_type, is_nullable, column_default \n\
              FROM information_schema.columns \n\
-             WHERE table_name = '{}' \n\
+             WHERE table_schema = {} AND table_name = '{}' \n\
              ORDER BY ordinal_position",
-            table
+            schema,
+            name.replace('\'', "''")
         )
     }
 
-    pub async fn list_columns(&self, table: &str) -> Result<Vec<String>> {
-        let query = format!(
-            "SELECT column_name FROM information_schema.columns \
-             WHERE table_name = '{}' ORDER BY ordinal_position",
-            table
-        );
-        let rows = self.client.query(&query, &[]).await?;
-        Ok(rows.iter().map(|r| r.get::<_, String>(0)).collect())
+    pub fn definition_query(&self, obj: &DbObject, _schema: Option<&str>) -> String {
+        let (schema, name) = split_table(&obj.name);
+        let schema = schema.as_deref().unwrap_or("public").replace('\'', "''");
+        let name = name.replace('\'', "''");
+        match obj.kind {
+            ObjectKind::Function | ObjectKind::Procedure => format!(
+                "SELECT pg_get_functiondef('{}'::regprocedure) AS definition",
+                obj.name.replace('\'', "''")
+            ),
+            ObjectKind::Sequence => format!(
+                "SELECT * FROM pg_sequences WHERE schemaname = '{}' AND sequencename = '{}'",
+                schema, name
+            ),
+            ObjectKind::Index => format!(
+                "SELECT tablename, indexdef FROM pg_indexes \
+                 WHERE schemaname = '{}' AND indexname = '{}'",
+                schema, name
+            ),
+            _ => self.describe_table_query(&obj.name, None),
+        }
+    }
+
+    /// List columns of `table` (`schema.table`, or a bare name resolved via search_path).
+    pub async fn list_columns(&self, table: &str) -> Result<Vec<ColumnInfo>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT a.attname, format_type(a.atttypid, a.atttypmod), NOT a.attnotnull, \
+                        EXISTS (SELECT 1 FROM pg_constraint c WHERE c.conrelid = a.attrelid \
+                                AND c.contype = 'p' AND a.attnum = ANY (c.conkey)), \
+                        EXISTS (SELECT 1 FROM pg_constraint c WHERE c.conrelid = a.attrelid \
+                                AND c.contype = 'f' AND a.attnum = ANY (c.conkey)) \
+                 FROM pg_attribute a \
+                 WHERE a.attrelid = $1::text::regclass AND a.attnum > 0 AND NOT a.attisdropped \
+                 ORDER BY a.attnum",
+                &[&quote_table(table)],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|r| ColumnInfo {
+                name: r.get(0),
+                data_type: r.get(1),
+                nullable: r.get(2),
+                primary_key: r.get(3),
+                foreign_key: r.get(4),
+            })
+            .collect())
     }
 
-    fn get_column_value(row: &Row, idx: usize) -> String {
+    fn get_column_value(row: &Row, idx: usize) -> CellValue {
         let col_type = row.columns()[idx].type_();
 
-        // Helper macro for scalar types: get Option<T>, format with null_or
+        // Helper macro for scalar types: get Option<T>, NULL maps to CellValue::Null
         macro_rules! get_value {
             ($t:ty) => {
-                row.try_get::<_, Option<$t>>(idx).map(fmt::null_or)
+                row.try_get::<_, Option<$t>>(idx).map(CellValue::from)
             };
             ($t:ty, $f:expr) => {
                 row.try_get::<_, Option<$t>>(idx)
-                    .map(|v| fmt::null_or_else(v, $f))
+                    .map(|v| v.map_or(CellValue::Null, $f))
             };
         }
 
-        // Helper macro for array types: get Option<Vec<T>>, format as {a,b,c}
+        // Helper macro for array types: get Option<Vec<Option<T>>> as CellValue::Array
         macro_rules! get_array {
             ($t:ty) => {
-                row.try_get::<_, Option<Vec<$t>>>(idx)
-                    .map(|v| fmt::null_or_else(v, |arr| fmt::pg_array(&arr)))
+                row.try_get::<_, Option<Vec<Option<$t>>>>(idx)
+                    .map(|v| v.map_or(CellValue::Null, CellValue::array))
+            };
+            ($t:ty, $f:expr) => {
+                row.try_get::<_, Option<Vec<Option<$t>>>>(idx).map(|v| {
+                    v.map_or(CellValue::Null, |arr| {
+                        CellValue::Array(
+                            arr.into_iter()
+                                .map(|x| x.map_or(CellValue::Null, $f))
+                                .collect(),
+                        )
+                    })
+                })
             };
         }
 
-        let result: std::result::Result<String, _> = match *col_type {
+        let timestamp =
+            |t: NaiveDateTime| CellValue::Temporal(t.format("%Y-%m-%d %H:%M:%S%.3f").to_string());
+        let timestamptz = |t: DateTime<Utc>| {
+            CellValue::Temporal(t.format("%Y-%m-%d %H:%M:%S%.3f %Z").to_string())
+        };
+        let date = |d: NaiveDate| CellValue::Temporal(d.format("%Y-%m-%d").to_string());
+        let numeric = |d: rust_decimal::Decimal| CellValue::Decimal(d.to_string());
+
+        let result: std::result::Result<CellValue, _> = match *col_type {
             // Scalar types
             Type::BOOL => get_value!(bool),
             Type::INT2 => get_value!(i16),
-            Type::INT4 | Type::OID => get_value!(i32),
+            Type::INT4 => get_value!(i32),
+            Type::OID => get_value!(u32),
             Type::INT8 => get_value!(i64),
             Type::FLOAT4 => get_value!(f32),
             Type::FLOAT8 => get_value!(f64),
-            Type::NUMERIC => get_value!(rust_decimal::Decimal),
-            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => row
-                .try_get::<_, Option<String>>(idx)
-                .map(|v| v.unwrap_or_else(|| "NULL".to_string())),
+            Type::NUMERIC => get_value!(rust_decimal::Decimal, numeric),
+            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+                get_value!(String)
+            }
             Type::UUID => get_value!(Uuid),
             Type::JSON | Type::JSONB => get_value!(JsonValue),
-            Type::TIMESTAMP => get_value!(NaiveDateTime, |t: NaiveDateTime| t
-                .format("%Y-%m-%d %H:%M:%S%.3f")
-                .to_string()),
-            Type::TIMESTAMPTZ => get_value!(DateTime<Utc>, |t: DateTime<Utc>| t
-                .format("%Y-%m-%d %H:%M:%S%.3f %Z")
-                .to_string()),
-            Type::DATE => get_value!(NaiveDate, |d: NaiveDate| d.format("%Y-%m-%d").to_string()),
-            Type::TIME => get_value!(NaiveTime, |t: NaiveTime| t
-                .format("%H:%M:%S%.3f")
-                .to_string()),
-            Type::BYTEA => get_value!(Vec<u8>, |b: Vec<u8>| fmt::bytes(&b, 32)),
+            Type::TIMESTAMP => get_value!(NaiveDateTime, timestamp),
+            Type::TIMESTAMPTZ => get_value!(DateTime<Utc>, timestamptz),
+            Type::DATE => get_value!(NaiveDate, date),
+            Type::TIME => get_value!(NaiveTime, |t: NaiveTime| CellValue::Temporal(
+                t.format("%H:%M:%S%.3f").to_string()
+            )),
+            Type::BYTEA => get_value!(Vec<u8>, CellValue::Bytes),
 
             // Array types
             Type::BOOL_ARRAY => get_array!(bool),
@@ -193,25 +495,22 @@ impl PostgresClient {
             Type::INT8_ARRAY => get_array!(i64),
             Type::FLOAT4_ARRAY => get_array!(f32),
             Type::FLOAT8_ARRAY => get_array!(f64),
-            Type::NUMERIC_ARRAY => get_array!(rust_decimal::Decimal),
+            Type::NUMERIC_ARRAY => get_array!(rust_decimal::Decimal, numeric),
             Type::TEXT_ARRAY | Type::VARCHAR_ARRAY | Type::BPCHAR_ARRAY | Type::NAME_ARRAY => {
                 get_array!(String)
             }
             Type::UUID_ARRAY => get_array!(Uuid),
             Type::JSON_ARRAY | Type::JSONB_ARRAY => get_array!(JsonValue),
-            Type::TIMESTAMP_ARRAY => get_array!(NaiveDateTime),
-            Type::TIMESTAMPTZ_ARRAY => get_array!(DateTime<Utc>),
-            Type::DATE_ARRAY => get_array!(NaiveDate),
-            Type::BYTEA_ARRAY => {
-                row.try_get::<_, Option<Vec<Vec<u8>>>>(idx)
-                    .map(|v| fmt::null_or_else(v, |arr| fmt::collection("bytea[]", arr.len())))
-            }
+            Type::TIMESTAMP_ARRAY => get_array!(NaiveDateTime, timestamp),
+            Type::TIMESTAMPTZ_ARRAY => get_array!(DateTime<Utc>, timestamptz),
+            Type::DATE_ARRAY => get_array!(NaiveDate, date),
+            Type::BYTEA_ARRAY => get_array!(Vec<u8>, CellValue::Bytes),
 
             // Custom and unknown types — use Kind metadata + RawText fallback
             _ => Self::get_custom_value(row, idx, col_type),
         };
 
-        result.unwrap_or_else(|_| format!("<{}>", col_type.name()))
+        result.unwrap_or_else(|_| CellValue::Text(format!("<{}>", col_type.name())))
     }
 
     /// Handle custom types (enums, domains, arrays of custom types, etc.)
@@ -220,23 +519,23 @@ impl PostgresClient {
         row: &Row,
         idx: usize,
         col_type: &Type,
-    ) -> std::result::Result<String, tokio_postgres::Error> {
+    ) -> std::result::Result<CellValue, tokio_postgres::Error> {
         match col_type.kind() {
             // Arrays of custom types (e.g. enum[], domain[])
             Kind::Array(_) => row
-                .try_get::<_, Option<Vec<RawText>>>(idx)
-                .map(|v| fmt::null_or_else(v, |arr| fmt::pg_array(&arr))),
+                .try_get::<_, Option<Vec<Option<RawText>>>>(idx)
+                .map(|v| v.map_or(CellValue::Null, CellValue::array)),
 
             // Domain types wrap an underlying type — try RawText which reads
             // the binary representation (same encoding as the base type)
             Kind::Domain(_) | Kind::Enum(_) | Kind::Composite(_) | Kind::Range(_) => row
                 .try_get::<_, Option<RawText>>(idx)
-                .map(fmt::null_or),
+                .map(CellValue::from),
 
             // Any other unknown type
             _ => row
                 .try_get::<_, Option<RawText>>(idx)
-                .map(fmt::null_or),
+                .map(CellValue::from),
         }
     }
 }
@@ -246,20 +545,26 @@ impl PostgresClient {
 /// PostgreSQL enums, domains over text types, and many extension types transmit
 /// their values as UTF-8 strings even in the binary protocol. For types with true
 /// binary representations (e.g. numeric domains, geometric types), this falls back
-/// to hex display.
-struct RawText(String);
+/// to a bytes value.
+enum RawText {
+    Text(String),
+    Bytes(Vec<u8>),
+}
 
-impl std::fmt::Display for RawText {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.0)
+impl From<RawText> for CellValue {
+    fn from(value: RawText) -> Self {
+        match value {
+            RawText::Text(s) => CellValue::Text(s),
+            RawText::Bytes(b) => CellValue::Bytes(b),
+        }
     }
 }
 
 impl<'a> FromSql<'a> for RawText {
     fn from_sql(_ty: &Type, raw: &'a [u8]) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         match std::str::from_utf8(raw) {
-            Ok(s) => Ok(RawText(s.to_string())),
-            Err(_) => Ok(RawText(fmt::bytes(raw, 32))),
+            Ok(s) => Ok(RawText::Text(s.to_string())),
+            Err(_) => Ok(RawText::Bytes(raw.to_vec())),
         }
     }
 
@@ -267,3 +572,117 @@ impl<'a> FromSql<'a> for RawText {
         true
     }
 }
+
+/// Display name of a type, `int4[]` rather than the internal `_int4` for arrays.
+fn type_name(ty: &Type) -> String {
+    match ty.kind() {
+        Kind::Array(element) => format!("{}[]", element.name()),
+        _ => ty.name().to_string(),
+    }
+}
+
+/// The statement opening a transaction for a cursor read when the session is
+/// outside a transaction block, and the one ending the read. The cursor is
+/// gone once its own transaction ends, so that ends before the last page is
+/// sent and the next statement cannot run inside it; a transaction the
+/// session already had is left open.
+fn cursor_bounds(in_transaction_block: bool) -> (Option<&'static str>, String) {
+    if in_transaction_block {
+        (None, format!("CLOSE {}", CURSOR))
+    } else {
+        (Some("BEGIN"), "COMMIT".to_string())
+    }
+}
+
+/// Queries read through a cursor: SELECT, VALUES and TABLE, and WITH unless
+/// a CTE modifies data (`WITH d AS (DELETE ...) SELECT ...`), which DECLARE
+/// refuses. Any INSERT, UPDATE, DELETE or MERGE word counts as one.
+fn is_cursor_query(query: &str) -> bool {
+    let dialect = Dialect::from(&DatabaseType::Postgres);
+    match lexer::first_keyword(query, dialect).as_deref() {
+        Some("SELECT" | "VALUES" | "TABLE") => true,
+        Some("WITH") => !lexer::tokenize(query, dialect).iter().any(|t| {
+            ["INSERT", "UPDATE", "DELETE", "MERGE"]
+                .iter()
+                .any(|k| t.is_keyword(k))
+        }),
+        _ => false,
+    }
+}
+
+/// SQL condition excluding system schemas for the given schema column.
+fn user_schema_filter(column: &str) -> String {
+    format!(
+        "{0} NOT IN ('pg_catalog', 'information_schema') \
+         AND {0} NOT LIKE 'pg\\_toast%' AND {0} NOT LIKE 'pg\\_temp%'",
+        column
+    )
+}
+
+/// Split `schema.table` into its parts; bare names have no schema. Quoted
+/// parts keep their case and may contain dots, unquoted ones are folded to
+/// lower case as the server does.
+fn split_table(table: &str) -> (Option<String>, String) {
+    let tokens = lexer::tokenize(table, Dialect::from(&DatabaseType::Postgres));
+    let part = |t: &lexer::Token| match t.kind {
+        TokenKind::Word => Some(t.text.to_lowercase()),
+        TokenKind::QuotedIdent => t.ident(),
+        _ => None,
+    };
+    let parts: Vec<_> = tokens.iter().filter(|t| !t.is_trivia()).collect();
+    match parts.as_slice() {
+        [schema, dot, name] if dot.text == "." => match (part(schema), part(name)) {
+            (Some(schema), Some(name)) => (Some(schema), name),
+            _ => (None, table.to_string()),
+        },
+        [name] => (None, part(name).unwrap_or_else(|| table.to_string())),
+        _ => (None, table.to_string()),
+    }
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Quote a possibly schema-qualified table name: `"schema"."table"`.
+fn quote_table(table: &str) -> String {
+    match split_table(table) {
+        (Some(schema), name) => format!("{}.{}", quote_ident(&schema), quote_ident(&name)),
+        (None, name) => quote_ident(&name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_bounds_keep_open_transaction() {
+        // A transaction the user opened with BEGIN is neither committed nor
+        // rolled back by a cursor SELECT
+        let (begin, end) = cursor_bounds(true);
+        assert_eq!(begin, None);
+        assert_eq!(end, "CLOSE sqli_rows");
+
+        let (begin, end) = cursor_bounds(false);
+        assert_eq!(begin, Some("BEGIN"));
+        assert_eq!(end, "COMMIT");
+    }
+
+    #[test]
+    fn test_split_quoted_table_names() {
+        assert_eq!(
+            split_table("public.users"),
+            (Some("public".to_string()), "users".to_string())
+        );
+        assert_eq!(split_table("Users"), (None, "users".to_string()));
+        assert_eq!(
+            split_table("\"My Schema\".\"a.b\"\"c\""),
+            (Some("My Schema".to_string()), "a.b\"c".to_string())
+        );
+        assert_eq!(
+            quote_table("\"My Schema\".\"a.b\"\"c\""),
+            "\"My Schema\".\"a.b\"\"c\""
+        );
+    }
+}
@@ -1,4 +1,6 @@
 /// Result table layout helpers used by both rendering and navigation.
+use crate::db::CellValue;
+
 use std::cmp::Reverse;
 
 pub const CELL_PADDING: usize = 2;
@@ -7,13 +9,13 @@ pub const MIN_SHRUNK_COL_WIDTH: usize = 6;
 /// Measure natural column widths from headers + row values.
 ///
 /// Widths include a small right-side padding so cells breathe.
-pub fn measure_column_widths(columns: &[String], rows: &[Vec<String>]) -> Vec<usize> {
+pub fn measure_column_widths(columns: &[String], rows: &[Vec<CellValue>]) -> Vec<usize> {
     let mut widths: Vec<usize> = columns.iter().map(|h| h.len() + CELL_PADDING).collect();
 
     for row in rows {
         for (i, cell) in row.iter().enumerate() {
             if i < widths.len() {
-                widths[i] = widths[i].max(cell.len() + CELL_PADDING);
+                widths[i] = widths[i].max(cell.display_len() + CELL_PADDING);
             }
         }
     }
@@ -102,7 +104,7 @@ pub fn fit_column_widths(widths: &[usize], available_width: usize) -> Vec<usize>
 /// Build final base widths for the query result table.
 pub fn result_table_widths(
     columns: &[String],
-    rows: &[Vec<String>],
+    rows: &[Vec<CellValue>],
     available_width: usize,
 ) -> Vec<usize> {
     let measured = measure_column_widths(columns, rows);
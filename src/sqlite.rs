@@ -1,30 +1,48 @@
-use crate::db::QueryResult;
+use crate::db::{
+    CellValue, ColumnInfo, DbObject, PAGE_SIZE, PagedResult, RowStream, objects_from_rows,
+};
 use crate::error::{Result, SqliError};
 
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
-use rusqlite::Connection;
 use rusqlite::types::Value;
+use rusqlite::{Connection, InterruptHandle, OpenFlags};
 
 pub struct SqliteClient {
-    conn: Mutex<Connection>,
+    /// Shared with the thread streaming a result, which holds the lock until done
+    conn: Arc<Mutex<Connection>>,
+    /// Aborts the running statement without taking the connection lock
+    interrupt: InterruptHandle,
     path: PathBuf,
 }
 
 impl SqliteClient {
-    pub async fn connect(path: &str) -> Result<Self> {
+    pub async fn connect(path: &str, readonly: bool) -> Result<Self> {
         let path = PathBuf::from(path);
 
-        let conn =
-            Connection::open(&path).map_err(|e| SqliError::Connection(format!("SQLite: {}", e)))?;
+        let conn = if readonly {
+            Connection::open_with_flags(
+                &path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | OpenFlags::SQLITE_OPEN_URI
+                    | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )
+        } else {
+            Connection::open(&path)
+        }
+        .map_err(|e| SqliError::Connection(format!("SQLite: {}", e)))?;
 
-        // Enable WAL mode for better concurrent read performance
-        conn.execute_batch("PRAGMA journal_mode=WAL;")
-            .map_err(|e| SqliError::Connection(format!("SQLite PRAGMA: {}", e)))?;
+        // Enable WAL mode for better concurrent read performance; switching
+        // needs write access, so a read-only file keeps its journal mode
+        if !readonly {
+            conn.execute_batch("PRAGMA journal_mode=WAL;")
+                .map_err(|e| SqliError::Connection(format!("SQLite PRAGMA: {}", e)))?;
+        }
 
         Ok(Self {
-            conn: Mutex::new(conn),
+            interrupt: conn.get_interrupt_handle(),
+            conn: Arc::new(Mutex::new(conn)),
             path,
         })
     }
@@ -39,75 +57,129 @@ impl SqliteClient {
         Ok(vec![name])
     }
 
-    pub async fn list_tables(&self, _schema: &str) -> Result<Vec<String>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| SqliError::Other(e.to_string()))?;
-        let mut stmt = conn
-            .prepare(
-                "SELECT name FROM sqlite_master \
-                 WHERE type = 'table' AND name NOT LIKE 'sqlite_%' \
-                 ORDER BY name",
-            )
-            .map_err(|e| SqliError::Query(e.to_string()))?;
-
-        let tables: Vec<String> = stmt
-            .query_map([], |row| row.get(0))
-            .map_err(|e| SqliError::Query(e.to_string()))?
-            .filter_map(|r| r.ok())
-            .collect();
+    pub async fn list_objects(&self, _schema: &str) -> Result<Vec<DbObject>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT type, name FROM sqlite_master \
+                     WHERE type IN ('table', 'view', 'index') AND name NOT LIKE 'sqlite_%' \
+                     ORDER BY name",
+                )
+                .map_err(|e| SqliError::Query(e.to_string()))?;
+
+            let rows: Vec<(String, String)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| SqliError::Query(e.to_string()))?
+                .filter_map(|r| r.ok())
+                .collect();
+
+            Ok(objects_from_rows(rows))
+        })
+        .await
+    }
 
-        Ok(tables)
+    /// Run `f` on the connection from a blocking thread, since the lock can
+    /// be held for as long as a result is being streamed
+    async fn with_conn<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().map_err(|e| SqliError::Other(e.to_string()))?;
+            f(&conn)
+        })
+        .await
+        .map_err(|e| SqliError::Other(e.to_string()))?
     }
 
-    pub async fn execute_query(&self, query: &str) -> Result<QueryResult> {
-        // Use block_in_place to signal to tokio that this is a blocking operation
-        // This prevents blocking the async runtime's worker threads
+    pub async fn execute_paged(&self, query: &str) -> Result<PagedResult> {
         let query = query.to_string();
-        let conn_guard = self
-            .conn
-            .lock()
-            .map_err(|e| SqliError::Other(e.to_string()))?;
-
-        tokio::task::block_in_place(|| {
-            let query_upper = query.trim().to_uppercase();
-
-            if query_upper.starts_with("SELECT")
-                || query_upper.starts_with("PRAGMA")
-                || query_upper.starts_with("WITH")
-                || query_upper.starts_with("EXPLAIN")
-            {
-                let mut stmt = conn_guard
-                    .prepare(&query)
-                    .map_err(|e| SqliError::Query(e.to_string()))?;
-
-                let columns: Vec<String> =
-                    stmt.column_names().iter().map(|c| c.to_string()).collect();
-
-                let rows: Vec<Vec<String>> = stmt
-                    .query_map([], |row| {
-                        let mut values = Vec::with_capacity(columns.len());
-                        for i in 0..columns.len() {
-                            values.push(Self::format_value(row.get_ref(i).ok()));
-                        }
-                        Ok(values)
-                    })
-                    .map_err(|e| SqliError::Query(e.to_string()))?
-                    .filter_map(|r| r.ok())
-                    .collect();
-
-                Ok(QueryResult::Select { columns, rows })
-            } else {
-                let rows_affected = conn_guard
-                    .execute(&query, [])
-                    .map_err(|e| SqliError::Query(e.to_string()))?;
-
-                Ok(QueryResult::Execute {
-                    rows_affected: rows_affected as u64,
+
+        // The statement borrows the connection, so rows are read on a
+        // blocking thread that keeps the lock until the stream is done
+        let conn = Arc::clone(&self.conn);
+        let (mut writer, stream) = RowStream::channel(true, PAGE_SIZE);
+        let task = tokio::task::spawn_blocking(move || {
+            let conn_guard = match conn.lock() {
+                Ok(guard) => guard,
+                Err(e) => return writer.blocking_fail(SqliError::Other(e.to_string())),
+            };
+            let mut stmt = match conn_guard.prepare(&query) {
+                Ok(stmt) => stmt,
+                Err(e) => return writer.blocking_fail(SqliError::Query(e.to_string())),
+            };
+
+            // Statements without result columns only report their changes
+            if stmt.column_count() == 0 {
+                return match stmt.execute([]) {
+                    Ok(rows_affected) => writer.blocking_affected(rows_affected as u64),
+                    Err(e) => writer.blocking_fail(SqliError::Query(e.to_string())),
+                };
+            }
+
+            // Declared types only; expressions have none
+            let (columns, types): (Vec<String>, Vec<String>) = stmt
+                .columns()
+                .iter()
+                .map(|c| {
+                    let decl_type = c.decl_type().unwrap_or_default();
+                    (c.name().to_string(), decl_type.to_string())
                 })
+                .unzip();
+            let width = columns.len();
+            if !writer.blocking_columns(columns, types) {
+                return;
             }
+
+            let mut rows = match stmt.query([]) {
+                Ok(rows) => rows,
+                Err(e) => return writer.blocking_fail(SqliError::Query(e.to_string())),
+            };
+            loop {
+                match rows.next() {
+                    Ok(Some(row)) => {
+                        let values = (0..width)
+                            .map(|i| Self::format_value(row.get_ref(i).ok()))
+                            .collect();
+                        if !writer.blocking_push(values) {
+                            return;
+                        }
+                    }
+                    Ok(None) => return writer.blocking_finish(),
+                    Err(e) => return writer.blocking_fail(SqliError::Query(e.to_string())),
+                }
+            }
+        });
+        stream.with_task(task).first_page().await
+    }
+
+    /// Open a transaction that following statements run in
+    pub async fn begin(&self) -> Result<()> {
+        self.execute_batch("BEGIN").await
+    }
+
+    pub async fn commit(&self) -> Result<()> {
+        self.execute_batch("COMMIT").await
+    }
+
+    pub async fn rollback(&self) -> Result<()> {
+        self.execute_batch("ROLLBACK").await
+    }
+
+    async fn execute_batch(&self, sql: &'static str) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute_batch(sql)
+                .map_err(|e| SqliError::Query(e.to_string()))
         })
+        .await
+    }
+
+    /// Interrupt the running statement; it fails with "interrupted".
+    pub async fn cancel_query(&self) -> Result<()> {
+        self.interrupt.interrupt();
+        Ok(())
     }
 
     pub fn select_table_query(&self, table: &str, limit: usize, _schema: Option<&str>) -> String {
@@ -118,40 +190,62 @@ impl SqliteClient {
         format!("PRAGMA table_info(\"{}\")", table)
     }
 
-    pub async fn list_columns(&self, table: &str, _schema: Option<&str>) -> Result<Vec<String>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| SqliError::Other(e.to_string()))?;
-        let mut stmt = conn
-            .prepare(&format!("PRAGMA table_info(\"{}\")", table))
-            .map_err(|e| SqliError::Query(e.to_string()))?;
-
-        let columns: Vec<String> = stmt
-            .query_map([], |row| row.get(1)) // column 1 is "name"
-            .map_err(|e| SqliError::Query(e.to_string()))?
-            .filter_map(|r| r.ok())
-            .collect();
-
-        Ok(columns)
+    pub fn definition_query(&self, obj: &DbObject, _schema: Option<&str>) -> String {
+        format!(
+            "SELECT sql FROM sqlite_master WHERE name = '{}'",
+            obj.name.replace('\'', "''")
+        )
     }
 
-    fn format_value(value: Option<rusqlite::types::ValueRef<'_>>) -> String {
-        match value {
-            None => "NULL".to_string(),
-            Some(v) => match Value::from(v) {
-                Value::Null => "NULL".to_string(),
-                Value::Integer(i) => i.to_string(),
-                Value::Real(f) => f.to_string(),
-                Value::Text(s) => s,
-                Value::Blob(b) => {
-                    if b.len() <= 32 {
-                        format!("x'{}'", hex::encode(&b))
-                    } else {
-                        format!("<blob: {} bytes>", b.len())
-                    }
-                }
-            },
+    pub async fn list_columns(
+        &self,
+        table: &str,
+        _schema: Option<&str>,
+    ) -> Result<Vec<ColumnInfo>> {
+        let query = table_info_query(table);
+        self.with_conn(move |conn| {
+            let mut stmt = conn
+                .prepare(&query)
+                .map_err(|e| SqliError::Query(e.to_string()))?;
+
+            let columns: Vec<ColumnInfo> = stmt
+                .query_map([], |row| {
+                    Ok(ColumnInfo {
+                        name: row.get(0)?,
+                        data_type: row.get(1)?,
+                        nullable: row.get(2)?,
+                        primary_key: row.get(3)?,
+                        foreign_key: row.get(4)?,
+                    })
+                })
+                .map_err(|e| SqliError::Query(e.to_string()))?
+                .filter_map(|r| r.ok())
+                .collect();
+
+            Ok(columns)
+        })
+        .await
+    }
+
+    fn format_value(value: Option<rusqlite::types::ValueRef<'_>>) -> CellValue {
+        match value.map(Value::from) {
+            None | Some(Value::Null) => CellValue::Null,
+            Some(Value::Integer(i)) => CellValue::from(i),
+            Some(Value::Real(f)) => CellValue::from(f),
+            Some(Value::Text(s)) => CellValue::Text(s),
+            Some(Value::Blob(b)) => CellValue::Bytes(b),
         }
     }
 }
+
+/// Column name, type, nullability and key flags in one query, so the SSH
+/// client (which can only run plain SQL) can share it.
+pub fn table_info_query(table: &str) -> String {
+    format!(
+        "SELECT p.name, p.type, p.\"notnull\" = 0 AS nullable, p.pk > 0 AS pk, \
+                EXISTS (SELECT 1 FROM pragma_foreign_key_list('{0}') f \
+                        WHERE f.\"from\" = p.name) AS fk \
+         FROM pragma_table_info('{0}') p",
+        table.replace('\'', "''")
+    )
+}
@@ -1,9 +1,12 @@
-use crate::db::QueryResult;
+use crate::controller::DatabaseType;
+use crate::db::{CellValue, ColumnInfo, DbObject, QueryResult, objects_from_rows};
 use crate::error::{Result, SqliError};
+use crate::lexer::{self, Dialect};
 
 use std::process::Stdio;
+use std::sync::Mutex;
 
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
 
 /// SQLite client that executes queries on a remote machine via SSH.
@@ -13,18 +16,24 @@ pub struct SshSqliteClient {
     ssh_dest: String,
     ssh_port: Option<u16>,
     db_path: String,
+    /// PID of the remote sqlite3 process running the current query
+    remote_pid: Mutex<Option<u32>>,
+    /// Open the database with `sqlite3 -readonly`
+    readonly: bool,
 }
 
 impl SshSqliteClient {
     /// Connect to a remote SQLite database via SSH.
     /// `host` should be in format "user@hostname" or "user@hostname:port"
-    pub async fn connect(host: &str, db_path: &str) -> Result<Self> {
+    pub async fn connect(host: &str, db_path: &str, readonly: bool) -> Result<Self> {
         let (ssh_dest, ssh_port) = Self::parse_ssh_host(host)?;
 
         let client = Self {
             ssh_dest,
             ssh_port,
             db_path: db_path.to_string(),
+            remote_pid: Mutex::new(None),
+            readonly,
         };
 
         // Verify connectivity by checking if the database file exists
@@ -108,13 +117,20 @@ impl SshSqliteClient {
     async fn run_sqlite_query(&self, query: &str) -> Result<String> {
         let mut cmd = self.build_ssh_command();
 
-        // Build sqlite3 command with JSON output mode
-        let sqlite_cmd = format!("sqlite3 -json '{}'", self.db_path.replace("'", "'\\''"));
+        // Build sqlite3 command with JSON output mode, which the shell otherwise
+        // replaces with a table for EXPLAIN. The remote shell prints its PID
+        // first and `exec`s sqlite3 under it, so the query can be killed.
+        let sqlite_cmd = format!(
+            "echo $$; exec sqlite3 -json -cmd '.explain off'{} '{}'",
+            if self.readonly { " -readonly" } else { "" },
+            self.db_path.replace("'", "'\\''")
+        );
         cmd.arg(sqlite_cmd);
 
         cmd.stdin(Stdio::piped());
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
+        cmd.kill_on_drop(true);
 
         let mut child = cmd
             .spawn()
@@ -132,26 +148,61 @@ impl SshSqliteClient {
                 .map_err(|e| SqliError::Query(format!("Failed to send query: {}", e)))?;
         }
 
-        let output = child
-            .wait_with_output()
+        let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+
+        let mut pid_line = String::new();
+        stdout
+            .read_line(&mut pid_line)
             .await
             .map_err(|e| SqliError::Query(format!("Failed to get output: {}", e)))?;
+        let pid = pid_line.trim().parse::<u32>().ok();
+        *self.remote_pid.lock().unwrap() = pid;
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let (out_res, err_res) =
+            tokio::join!(stdout.read_to_end(&mut out), stderr.read_to_end(&mut err));
+        let status = child.wait().await;
+        self.remote_pid
+            .lock()
+            .unwrap()
+            .take_if(|running| Some(*running) == pid);
+
+        let status = out_res
+            .and(err_res)
+            .and(status)
+            .map_err(|e| SqliError::Query(format!("Failed to get output: {}", e)))?;
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        let stdout = String::from_utf8_lossy(&out).to_string();
+        let stderr = String::from_utf8_lossy(&err).to_string();
 
         // Check for SQL errors
         if stdout.starts_with("Error:") || stdout.starts_with("Parse error") {
             return Err(SqliError::Query(stdout.trim().to_string()));
         }
 
-        if !output.status.success() && !stderr.is_empty() {
-            return Err(SqliError::Query(stderr));
+        if !status.success() {
+            // No stderr means sqlite3 was killed (e.g. the query was cancelled)
+            return Err(SqliError::Query(if stderr.is_empty() {
+                format!("sqlite3 terminated ({})", status)
+            } else {
+                stderr
+            }));
         }
 
         Ok(stdout)
     }
 
+    /// Kill the remote sqlite3 process running the current query.
+    pub async fn cancel_query(&self) -> Result<()> {
+        let Some(pid) = *self.remote_pid.lock().unwrap() else {
+            return Ok(());
+        };
+        self.run_ssh_command(&format!("kill {}", pid)).await?;
+        Ok(())
+    }
+
     pub async fn list_databases(&self, _include_system: bool) -> Result<Vec<String>> {
         // SQLite is single-database — return filename as name
         let name = self
@@ -163,67 +214,127 @@ impl SshSqliteClient {
         Ok(vec![name])
     }
 
-    pub async fn list_tables(&self, _schema: &str) -> Result<Vec<String>> {
+    pub async fn list_objects(&self, _schema: &str) -> Result<Vec<DbObject>> {
         let output = self
             .run_sqlite_query(
-                "SELECT name FROM sqlite_master \
-                 WHERE type = 'table' AND name NOT LIKE 'sqlite_%' \
+                "SELECT type, name FROM sqlite_master \
+                 WHERE type IN ('table', 'view', 'index') AND name NOT LIKE 'sqlite_%' \
                  ORDER BY name;",
             )
             .await?;
+        let output = output.trim();
 
-        self.parse_single_column_json(&output)
-    }
+        if output.is_empty() || output == "[]" {
+            return Ok(vec![]);
+        }
 
-    pub async fn execute_query(&self, query: &str) -> Result<QueryResult> {
-        let query_upper = query.trim().to_uppercase();
+        let json_rows: Vec<serde_json::Map<String, serde_json::Value>> =
+            serde_json::from_str(output)
+                .map_err(|e| SqliError::Query(format!("Failed to parse JSON: {}", e)))?;
 
-        if query_upper.starts_with("SELECT")
-            || query_upper.starts_with("PRAGMA")
-            || query_upper.starts_with("WITH")
-            || query_upper.starts_with("EXPLAIN")
-        {
-            let output = self.run_sqlite_query(query).await?;
-            self.parse_json_result(&output)
-        } else {
-            // For non-SELECT queries, use changes() to get affected rows
-            let combined = format!(
-                "{}; SELECT changes() as affected;",
-                query.trim_end_matches(';')
-            );
-            let output = self.run_sqlite_query(&combined).await?;
-            let rows_affected = self.parse_changes_result(&output)?;
-            Ok(QueryResult::Execute { rows_affected })
-        }
+        // JSON object key order is not preserved, so look columns up by name
+        Ok(objects_from_rows(json_rows.iter().map(|row| {
+            (
+                Self::format_json_value(row.get("type")).to_string(),
+                Self::format_json_value(row.get("name")).to_string(),
+            )
+        })))
     }
 
-    fn parse_json_result(&self, output: &str) -> Result<QueryResult> {
-        let output = output.trim();
+    pub async fn execute_query(&self, query: &str) -> Result<QueryResult> {
+        // sqlite3 prints nothing for a statement without rows. Its bytecode,
+        // listed first, shows whether it has result columns, and the count of
+        // changed rows follows as a result of its own. EXPLAIN cannot be
+        // explained again, and always has rows.
+        let statement = query.trim_end().trim_end_matches(';');
+        let dialect = Dialect::from(&DatabaseType::Sqlite);
+        let list_program = lexer::first_keyword(statement, dialect).as_deref() != Some("EXPLAIN");
+        let mut combined = String::new();
+        if list_program {
+            combined.push_str(&format!("EXPLAIN {};\n", statement));
+        }
+        combined.push_str(&format!("{}\n;\nSELECT changes() AS affected;", statement));
+        let output = self.run_sqlite_query(&combined).await?;
 
-        if output.is_empty() || output == "[]" {
-            return Ok(QueryResult::Select {
-                columns: vec![],
-                rows: vec![],
+        let mut results = Self::parse_results(&output)?.into_iter();
+        let returns_rows = !list_program
+            || results.next().is_some_and(|program| {
+                program
+                    .iter()
+                    .any(|op| op.get("opcode").and_then(|v| v.as_str()) == Some("ResultRow"))
             });
+        let mut results: Vec<_> = results.collect();
+        let changes = results.pop().unwrap_or_default();
+        match results.pop() {
+            Some(rows) => Ok(Self::rows_result(rows)),
+            None if returns_rows => {
+                let (columns, column_types) = self.result_columns(statement).await;
+                Ok(QueryResult::Select {
+                    columns,
+                    column_types,
+                    rows: vec![],
+                })
+            }
+            None => Ok(QueryResult::Execute {
+                rows_affected: Self::parse_changes(&changes)?,
+            }),
         }
+    }
 
-        let json_rows: Vec<serde_json::Map<String, serde_json::Value>> =
-            serde_json::from_str(output).map_err(|e| {
-                SqliError::Query(format!("Failed to parse JSON: {} (raw: {})", e, output))
-            })?;
+    /// Column names and declared types of a statement that returned no rows
+    /// to take them from. They are read from a temporary view over it, so
+    /// statements a view cannot be defined by get none.
+    async fn result_columns(&self, statement: &str) -> (Vec<String>, Vec<String>) {
+        let query = format!(
+            "CREATE TEMP VIEW sqli_columns AS {};\nPRAGMA temp.table_info(sqli_columns);",
+            statement
+        );
+        let Ok(output) = self.run_sqlite_query(&query).await else {
+            return (Vec::new(), Vec::new());
+        };
+        let info = Self::parse_results(&output)
+            .ok()
+            .and_then(|results| results.into_iter().next())
+            .unwrap_or_default();
+        info.iter()
+            .map(|col| {
+                (
+                    Self::format_json_value(col.get("name")).to_string(),
+                    Self::format_json_value(col.get("type")).to_string(),
+                )
+            })
+            .unzip()
+    }
+
+    /// Split the output of several statements into the rows of each. Statements
+    /// without rows print nothing, so they have no entry.
+    fn parse_results(output: &str) -> Result<Vec<Vec<serde_json::Map<String, serde_json::Value>>>> {
+        serde_json::Deserializer::from_str(output)
+            .into_iter()
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| {
+                SqliError::Query(format!(
+                    "Failed to parse JSON: {} (raw: {})",
+                    e,
+                    output.trim()
+                ))
+            })
+    }
 
+    fn rows_result(json_rows: Vec<serde_json::Map<String, serde_json::Value>>) -> QueryResult {
         if json_rows.is_empty() {
-            return Ok(QueryResult::Select {
+            return QueryResult::Select {
                 columns: vec![],
+                column_types: vec![],
                 rows: vec![],
-            });
+            };
         }
 
         // Extract column names from first row
         let columns: Vec<String> = json_rows[0].keys().cloned().collect();
 
         // Extract row values
-        let rows: Vec<Vec<String>> = json_rows
+        let rows: Vec<Vec<CellValue>> = json_rows
             .iter()
             .map(|row| {
                 columns
@@ -233,40 +344,16 @@ impl SshSqliteClient {
             })
             .collect();
 
-        Ok(QueryResult::Select { columns, rows })
-    }
-
-    fn parse_single_column_json(&self, output: &str) -> Result<Vec<String>> {
-        let output = output.trim();
-
-        if output.is_empty() || output == "[]" {
-            return Ok(vec![]);
+        // The sqlite3 JSON output carries no column types
+        let column_types = vec![String::new(); columns.len()];
+        QueryResult::Select {
+            columns,
+            column_types,
+            rows,
         }
-
-        let json_rows: Vec<serde_json::Map<String, serde_json::Value>> =
-            serde_json::from_str(output)
-                .map_err(|e| SqliError::Query(format!("Failed to parse JSON: {}", e)))?;
-
-        Ok(json_rows
-            .iter()
-            .filter_map(|row| {
-                row.values()
-                    .next()
-                    .map(|v| Self::format_json_value(Some(v)))
-            })
-            .collect())
     }
 
-    fn parse_changes_result(&self, output: &str) -> Result<u64> {
-        let output = output.trim();
-
-        if output.is_empty() || output == "[]" {
-            return Ok(0);
-        }
-
-        let json_rows: Vec<serde_json::Map<String, serde_json::Value>> =
-            serde_json::from_str(output).unwrap_or_default();
-
+    fn parse_changes(json_rows: &[serde_json::Map<String, serde_json::Value>]) -> Result<u64> {
         if let Some(row) = json_rows.first()
             && let Some(val) = row.get("affected")
         {
@@ -283,16 +370,22 @@ impl SshSqliteClient {
         Ok(0)
     }
 
-    fn format_json_value(value: Option<&serde_json::Value>) -> String {
+    fn format_json_value(value: Option<&serde_json::Value>) -> CellValue {
         match value {
-            None => "NULL".to_string(),
+            None => CellValue::Null,
             Some(v) => match v {
-                serde_json::Value::Null => "NULL".to_string(),
-                serde_json::Value::Bool(b) => b.to_string(),
-                serde_json::Value::Number(n) => n.to_string(),
-                serde_json::Value::String(s) => s.clone(),
-                serde_json::Value::Array(arr) => serde_json::to_string(arr).unwrap_or_default(),
-                serde_json::Value::Object(obj) => serde_json::to_string(obj).unwrap_or_default(),
+                serde_json::Value::Null => CellValue::Null,
+                serde_json::Value::Bool(b) => CellValue::Bool(*b),
+                serde_json::Value::Number(n) => match n.as_i64() {
+                    Some(i) => CellValue::from(i),
+                    None => n
+                        .as_f64()
+                        .map_or_else(|| CellValue::Text(n.to_string()), CellValue::Float),
+                },
+                serde_json::Value::String(s) => CellValue::Text(s.clone()),
+                serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+                    CellValue::Json(v.to_string())
+                }
             },
         }
     }
@@ -305,8 +398,19 @@ impl SshSqliteClient {
         format!("PRAGMA table_info(\"{}\")", table)
     }
 
-    pub async fn list_columns(&self, table: &str, _schema: Option<&str>) -> Result<Vec<String>> {
-        let query = format!("PRAGMA table_info(\"{}\");", table);
+    pub fn definition_query(&self, obj: &DbObject, _schema: Option<&str>) -> String {
+        format!(
+            "SELECT sql FROM sqlite_master WHERE name = '{}'",
+            obj.name.replace('\'', "''")
+        )
+    }
+
+    pub async fn list_columns(
+        &self,
+        table: &str,
+        _schema: Option<&str>,
+    ) -> Result<Vec<ColumnInfo>> {
+        let query = format!("{};", crate::sqlite::table_info_query(table));
         let output = self.run_sqlite_query(&query).await?;
 
         let output = output.trim();
@@ -318,9 +422,24 @@ impl SshSqliteClient {
             serde_json::from_str(output)
                 .map_err(|e| SqliError::Query(format!("Failed to parse PRAGMA: {}", e)))?;
 
+        let flag = |row: &serde_json::Map<String, serde_json::Value>, key: &str| {
+            row.get(key).and_then(|v| v.as_i64()).unwrap_or(0) != 0
+        };
         Ok(json_rows
             .iter()
-            .filter_map(|row| row.get("name").and_then(|v| v.as_str()).map(String::from))
+            .filter_map(|row| {
+                Some(ColumnInfo {
+                    name: row.get("name")?.as_str()?.to_string(),
+                    data_type: row
+                        .get("type")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    nullable: flag(row, "nullable"),
+                    primary_key: flag(row, "pk"),
+                    foreign_key: flag(row, "fk"),
+                })
+            })
             .collect())
     }
 }
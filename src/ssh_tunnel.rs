@@ -0,0 +1,161 @@
+use crate::error::{Result, SqliError};
+
+use std::net::TcpListener;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::{Child, ChildStderr, Command};
+
+/// How long to wait for the forwarded port to start accepting connections
+const TUNNEL_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// SSH jump host settings for a connection.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SshConfig {
+    /// Host name or alias from ~/.ssh/config
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// Private key file passed to `ssh -i`
+    pub identity: Option<String>,
+}
+
+/// Local port-forward through the system `ssh` command.
+/// The ssh process is killed when the tunnel is dropped.
+pub struct SshTunnel {
+    child: Mutex<Child>,
+    local_port: u16,
+    /// Jump host and remote end, to tell whether a connection can reuse it
+    ssh: SshConfig,
+    remote: (String, u16),
+}
+
+impl SshTunnel {
+    /// Forward a free local port to `remote_host:remote_port` as seen from the SSH host.
+    pub async fn open(ssh: &SshConfig, remote_host: &str, remote_port: u16) -> Result<Self> {
+        let local_port = Self::free_local_port()?;
+
+        let mut cmd = Command::new("ssh");
+        cmd.arg("-N");
+        // BatchMode=yes prevents password prompts (relies on key auth)
+        cmd.arg("-o").arg("BatchMode=yes");
+        cmd.arg("-o").arg("ConnectTimeout=10");
+        cmd.arg("-o").arg("ExitOnForwardFailure=yes");
+        cmd.arg("-L").arg(format!(
+            "127.0.0.1:{}:{}:{}",
+            local_port, remote_host, remote_port
+        ));
+        if let Some(port) = ssh.port {
+            cmd.arg("-p").arg(port.to_string());
+        }
+        if let Some(ref identity) = ssh.identity {
+            cmd.arg("-i").arg(identity);
+        }
+        if let Some(ref user) = ssh.user {
+            cmd.arg("-l").arg(user);
+        }
+        cmd.arg(&ssh.host);
+
+        cmd.stdin(Stdio::null());
+        cmd.stdout(Stdio::null());
+        cmd.stderr(Stdio::piped());
+        cmd.kill_on_drop(true);
+
+        crate::debug_log!(
+            "Opening SSH tunnel 127.0.0.1:{} -> {}:{} via {}",
+            local_port,
+            remote_host,
+            remote_port,
+            ssh.host
+        );
+
+        let child = cmd
+            .spawn()
+            .map_err(|e| SqliError::Connection(format!("Failed to spawn SSH: {}", e)))?;
+
+        let mut tunnel = Self {
+            child: Mutex::new(child),
+            local_port,
+            ssh: ssh.clone(),
+            remote: (remote_host.to_string(), remote_port),
+        };
+        tunnel.wait_ready().await?;
+        Ok(tunnel)
+    }
+
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+
+    /// Whether this tunnel is still up and forwards to `remote_host:remote_port`
+    /// through `ssh`, so a new connection can go through it instead of a new one.
+    pub fn forwards(&self, ssh: &SshConfig, remote_host: &str, remote_port: u16) -> bool {
+        self.ssh == *ssh
+            && self.remote.0 == remote_host
+            && self.remote.1 == remote_port
+            && matches!(self.child.lock().unwrap().try_wait(), Ok(None))
+    }
+
+    /// Ask the OS for an unused port. The listener is closed right away so
+    /// ssh can bind it; another process grabbing it in between is unlikely.
+    fn free_local_port() -> Result<u16> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        Ok(listener.local_addr()?.port())
+    }
+
+    /// Poll until the forwarded port accepts connections or ssh exits.
+    async fn wait_ready(&mut self) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + TUNNEL_TIMEOUT;
+        let child = self.child.get_mut().unwrap();
+        loop {
+            if let Some(status) = child.try_wait()? {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = child.stderr.take() {
+                    let _ = pipe.read_to_string(&mut stderr).await;
+                }
+                let reason = if stderr.trim().is_empty() {
+                    status.to_string()
+                } else {
+                    stderr.trim().to_string()
+                };
+                return Err(SqliError::Connection(format!(
+                    "SSH tunnel failed: {}",
+                    reason
+                )));
+            }
+
+            if TcpStream::connect(("127.0.0.1", self.local_port))
+                .await
+                .is_ok()
+            {
+                // ssh would stall on a full pipe once nothing reads it
+                if let Some(pipe) = child.stderr.take() {
+                    tokio::spawn(Self::log_stderr(pipe));
+                }
+                return Ok(());
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                return Err(SqliError::Connection("SSH tunnel timed out".to_string()));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Pass what ssh reports while the tunnel is up to the debug log
+    async fn log_stderr(pipe: ChildStderr) {
+        let mut lines = BufReader::new(pipe).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            crate::debug_log!("ssh: {}", line);
+        }
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        crate::debug_log!("Closing SSH tunnel on port {}", self.local_port);
+    }
+}
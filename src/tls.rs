@@ -0,0 +1,223 @@
+//! TLS settings shared by the network database clients.
+//!
+//! Modes follow libpq's `sslmode` semantics so the same config keys work for
+//! every backend that supports TLS.
+
+use crate::error::{Result, SqliError};
+
+use std::fs;
+
+use native_tls::{Certificate, Identity, TlsConnector};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_native_tls::TlsStream;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SslMode {
+    /// Plaintext only
+    #[default]
+    Disable,
+    /// Try TLS first, fall back to plaintext (no certificate checks)
+    Prefer,
+    /// TLS without certificate verification
+    Require,
+    /// TLS, verify the certificate chain but not the hostname
+    VerifyCa,
+    /// TLS, verify the certificate chain and the hostname
+    VerifyFull,
+}
+
+impl SslMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "disable" => Some(SslMode::Disable),
+            "prefer" => Some(SslMode::Prefer),
+            "require" => Some(SslMode::Require),
+            "verify-ca" | "verify_ca" => Some(SslMode::VerifyCa),
+            "verify-full" | "verify_full" => Some(SslMode::VerifyFull),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyCa => "verify-ca",
+            SslMode::VerifyFull => "verify-full",
+        }
+    }
+
+    /// Whether a TLS handshake should be attempted at all
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, SslMode::Disable)
+    }
+
+    /// Whether the server certificate chain must be trusted
+    pub fn verifies_ca(&self) -> bool {
+        matches!(self, SslMode::VerifyCa | SslMode::VerifyFull)
+    }
+
+    /// Whether the server certificate must match the host name
+    pub fn verifies_hostname(&self) -> bool {
+        matches!(self, SslMode::VerifyFull)
+    }
+}
+
+/// Resolved TLS settings for a single connection.
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    pub mode: SslMode,
+    /// PEM file with the CA certificate(s) used to verify the server
+    pub root_cert: Option<String>,
+    /// PEM file with the client certificate
+    pub client_cert: Option<String>,
+    /// PEM file with the client private key (PKCS#8)
+    pub client_key: Option<String>,
+    /// Host name to verify the certificate against when the driver connects
+    /// to a different address (e.g. the local end of an SSH tunnel)
+    pub server_name: Option<String>,
+}
+
+impl TlsConfig {
+    /// Build a native-tls connector honouring the mode, CA and client certificate.
+    pub fn native_connector(&self) -> Result<TlsConnector> {
+        let mut builder = TlsConnector::builder();
+
+        if !self.mode.verifies_ca() {
+            builder.danger_accept_invalid_certs(true);
+        }
+        if !self.mode.verifies_hostname() {
+            builder.danger_accept_invalid_hostnames(true);
+        }
+
+        if let Some(ref path) = self.root_cert {
+            let pem = read_pem(path)?;
+            builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+
+        if let Some((cert, key)) = self.client_identity()? {
+            builder.identity(Identity::from_pkcs8(&cert, &key)?);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    /// Read the client certificate and key, if configured.
+    /// Returns an error if only one of the two is set.
+    pub fn client_identity(&self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => Ok(Some((read_pem(cert)?, read_pem(key)?))),
+            (None, None) => Ok(None),
+            _ => Err(SqliError::Connection(
+                "TLS client certificate and key must be set together".to_string(),
+            )),
+        }
+    }
+}
+
+/// Loopback listener that wraps every accepted connection in TLS to the
+/// server, for drivers whose own TLS cannot take the full `TlsConfig`.
+/// The listener stops when the relay is dropped.
+pub struct TlsRelay {
+    local_port: u16,
+    task: JoinHandle<()>,
+}
+
+impl TlsRelay {
+    /// Listen on a free local port and relay to `host:port`, verifying the
+    /// certificate against `server_name` when set. One handshake is made up
+    /// front so certificate errors surface here rather than as a dropped
+    /// connection in the driver.
+    pub async fn start(tls: &TlsConfig, host: &str, port: u16) -> Result<Self> {
+        let connector = tokio_native_tls::TlsConnector::from(tls.native_connector()?);
+        let addr = (host.to_string(), port);
+        let domain = tls.server_name.clone().unwrap_or_else(|| host.to_string());
+
+        let mut first = Some(Self::handshake(&connector, &addr, &domain).await?);
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let local_port = listener.local_addr()?.port();
+
+        let task = tokio::spawn(async move {
+            while let Ok((mut local, _)) = listener.accept().await {
+                let ready = first.take();
+                let (connector, addr, domain) = (connector.clone(), addr.clone(), domain.clone());
+                tokio::spawn(async move {
+                    let mut remote = match ready {
+                        Some(remote) => remote,
+                        None => match Self::handshake(&connector, &addr, &domain).await {
+                            Ok(remote) => remote,
+                            Err(e) => {
+                                crate::debug_log!("TLS relay to {}: {}", domain, e);
+                                return;
+                            }
+                        },
+                    };
+                    let _ = tokio::io::copy_bidirectional(&mut local, &mut remote).await;
+                });
+            }
+        });
+
+        Ok(Self { local_port, task })
+    }
+
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+
+    async fn handshake(
+        connector: &tokio_native_tls::TlsConnector,
+        addr: &(String, u16),
+        domain: &str,
+    ) -> Result<TlsStream<TcpStream>> {
+        let stream = TcpStream::connect((addr.0.as_str(), addr.1)).await?;
+        Ok(connector.connect(domain, stream).await?)
+    }
+}
+
+impl Drop for TlsRelay {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|e| SqliError::Connection(format!("Failed to read '{}': {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sslmode() {
+        assert_eq!(SslMode::parse("disable"), Some(SslMode::Disable));
+        assert_eq!(SslMode::parse("prefer"), Some(SslMode::Prefer));
+        assert_eq!(SslMode::parse("Require"), Some(SslMode::Require));
+        assert_eq!(SslMode::parse("verify-ca"), Some(SslMode::VerifyCa));
+        assert_eq!(SslMode::parse("verify_full"), Some(SslMode::VerifyFull));
+        assert_eq!(SslMode::parse("allow"), None);
+    }
+
+    #[test]
+    fn test_sslmode_verification() {
+        assert!(!SslMode::Disable.is_enabled());
+        assert!(SslMode::Prefer.is_enabled());
+        assert!(!SslMode::Require.verifies_ca());
+        assert!(SslMode::VerifyCa.verifies_ca());
+        assert!(!SslMode::VerifyCa.verifies_hostname());
+        assert!(SslMode::VerifyFull.verifies_hostname());
+    }
+
+    #[test]
+    fn test_client_identity_requires_both() {
+        let tls = TlsConfig {
+            mode: SslMode::Require,
+            client_cert: Some("/tmp/client.pem".to_string()),
+            ..Default::default()
+        };
+        assert!(tls.client_identity().is_err());
+        assert!(TlsConfig::default().client_identity().unwrap().is_none());
+    }
+}